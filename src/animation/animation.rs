@@ -0,0 +1,18 @@
+use animation::sampler;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Animation {
+  pub name: String,
+  pub duration: f32,
+  pub tracks: Vec<TransformTrack>
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TransformTrack {
+  pub group: String,
+  pub bone: String,
+
+  pub orientation: Option<sampler::Sampler>,
+  pub position: Option<sampler::Sampler>,
+  pub scale_shear: Option<sampler::Sampler>
+}
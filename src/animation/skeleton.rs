@@ -1,8 +1,22 @@
+use math;
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Skeleton {
   pub bones: Vec<Bone>
 }
 
+impl Skeleton {
+  // From the space of the bone to the space of the skeleton
+  pub fn world_transform(&self, i: usize) -> math::Matrix4 {
+    let local = self.bones[i].transform();
+
+    return match self.bones[i].parent_index {
+      Some(parent) => math::multiply(&self.world_transform(parent), &local),
+      None => local
+    };
+  }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Bone {
   pub name: String,
@@ -11,4 +25,16 @@ pub struct Bone {
   pub position: [f32; 3],
   pub orientation: [f32; 4],
   pub scale_shear: [f32; 9]
-}
\ No newline at end of file
+}
+
+impl Bone {
+  // Scales and shears first, then rotates, then translates
+  pub fn transform(&self) -> math::Matrix4 {
+    let q = math::normalize_quaternion(self.orientation);
+
+    let s = self.scale_shear;
+    let scale_shear = [[s[0], s[1], s[2], 0.0], [s[3], s[4], s[5], 0.0], [s[6], s[7], s[8], 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+    return math::multiply(&math::compose(self.position, q, [1.0, 1.0, 1.0]), &scale_shear);
+  }
+}
@@ -3,9 +3,12 @@ use std::sync::Arc;
 use buffer;
 use model;
 
+use animation::animation;
+
 #[derive(Debug, Clone)]
 pub enum Object {
-  Model(model::Model)
+  Model(model::Model),
+  Animation(animation::Animation)
 }
 
 #[derive(Debug, Clone)]
@@ -13,3 +16,17 @@ pub struct Asset {
   pub buffers: Vec<Arc<buffer::Buffer>>,
   pub objects: Vec<Object>
 }
+
+impl Asset {
+  // Named after the first model, which is what exporters name their files after
+  pub fn name(&self) -> String {
+    for object in &self.objects {
+      match object {
+        &Object::Model(ref m) => return m.name.clone(),
+        _ => ()
+      }
+    }
+
+    return "asset".to_owned();
+  }
+}
@@ -1,18 +1,58 @@
+use std::fs;
+use std::io;
 use std::ops;
+use std::path;
 
 use std::sync::Arc;
 
+use memmap;
+
+#[derive(Debug, Clone)]
+enum Data {
+  Owned(Vec<u8>),
+  Mapped(Arc<memmap::MmapViewSync>)
+}
+
+impl Data {
+  #[inline(always)]
+  fn as_slice(&self) -> &[u8] {
+    return match *self {
+      Data::Owned(ref data) => data.as_slice(),
+      // The mapping is read only, but writes to the file by other processes would still show through
+      Data::Mapped(ref mmap) => unsafe { mmap.as_slice() }
+    };
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct Buffer {
   pub uri: Option<String>,
   pub name: Option<String>,
 
-  data: Vec<u8>
+  data: Data
 }
 
 impl Buffer {
   pub fn new(uri: Option<String>, name: Option<String>, data: Vec<u8>) -> Arc<Buffer> {
-    return Arc::new(Buffer { uri: uri, name: name, data: data });
+    return Arc::new(Buffer { uri: uri, name: name, data: Data::Owned(data) });
+  }
+
+  pub fn map<P: AsRef<path::Path>>(uri: Option<String>, name: Option<String>, path: P) -> io::Result<Arc<Buffer>> {
+    // Empty files cannot be mapped
+    if try!(fs::metadata(path.as_ref())).len() == 0 {
+      return Ok(Buffer::new(uri, name, Vec::new()));
+    }
+
+    let mmap = try!(memmap::Mmap::open_path(path, memmap::Protection::Read));
+
+    return Ok(Arc::new(Buffer { uri: uri, name: name, data: Data::Mapped(Arc::new(mmap.into_view_sync())) }));
+  }
+
+  pub fn is_mapped(&self) -> bool {
+    return match self.data {
+      Data::Owned(_) => false,
+      Data::Mapped(_) => true
+    };
   }
 
   #[inline(always)]
@@ -26,7 +66,7 @@ impl ops::Index<usize> for Buffer {
 
   #[inline(always)]
   fn index(&self, index: usize) -> &u8 {
-    return &self.data[index];
+    return &self.data.as_slice()[index];
   }
 }
 
@@ -35,7 +75,7 @@ impl ops::Index<ops::Range<usize>> for Buffer {
 
   #[inline(always)]
   fn index(&self, index: ops::Range<usize>) -> &[u8] {
-    return &self.data[index];
+    return &self.data.as_slice()[index];
   }
 }
 
@@ -44,7 +84,7 @@ impl ops::Index<ops::RangeTo<usize>> for Buffer {
 
   #[inline(always)]
   fn index(&self, index: ops::RangeTo<usize>) -> &[u8] {
-    return &self.data[index];
+    return &self.data.as_slice()[index];
   }
 }
 
@@ -53,7 +93,7 @@ impl ops::Index<ops::RangeFrom<usize>> for Buffer {
 
   #[inline(always)]
   fn index(&self, index: ops::RangeFrom<usize>) -> &[u8] {
-    return &self.data[index];
+    return &self.data.as_slice()[index];
   }
 }
 
@@ -62,7 +102,7 @@ impl ops::Index<ops::RangeFull> for Buffer {
 
   #[inline(always)]
   fn index(&self, index: ops::RangeFull) -> &[u8] {
-    return &self.data[index];
+    return &self.data.as_slice()[index];
   }
 }
 
@@ -70,7 +110,7 @@ impl ops::Deref for Buffer {
   type Target = [u8];
 
   fn deref(&self) -> &[u8] {
-    return self.data.deref();
+    return self.data.as_slice();
   }
 }
 
@@ -78,6 +118,12 @@ impl ops::Deref for Buffer {
 mod tests {
   use super::*;
 
+  use std::env;
+  use std::fs;
+  use std::process;
+
+  use std::io::Write;
+
   #[test]
   fn test_as_slice() {
     let buffer = Buffer::new(None, None, vec![0, 1, 2, 3]);
@@ -101,4 +147,20 @@ mod tests {
     assert_eq!(&buffer[.. 2], &[0, 1]);
     assert_eq!(&buffer[2 ..], &[2, 3]);
   }
+
+  #[test]
+  fn test_map() {
+    // Named after the process, so test runs going on at the same time do not share the file
+    let path = env::temp_dir().join(format!("engine_test_map_{}.bin", process::id()));
+
+    fs::File::create(&path).unwrap().write_all(&[0, 1, 2, 3]).unwrap();
+
+    let buffer = Buffer::map(None, None, &path).unwrap();
+
+    assert!(buffer.is_mapped());
+    assert_eq!(&buffer[1 .. 3], &[1, 2]);
+
+    drop(buffer);
+    fs::remove_file(&path).unwrap();
+  }
 }
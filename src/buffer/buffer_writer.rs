@@ -0,0 +1,118 @@
+use std::io;
+
+use std::sync::Arc;
+
+use byteorder::{NativeEndian, WriteBytesExt};
+
+use buffer::Buffer;
+use buffer::untyped_view::ScalarValue;
+
+#[derive(Debug, Clone)]
+pub struct BufferWriter {
+  data: Vec<u8>
+}
+
+impl BufferWriter {
+  pub fn new() -> BufferWriter {
+    return BufferWriter { data: Vec::new() };
+  }
+
+  pub fn with_capacity(capacity: usize) -> BufferWriter {
+    return BufferWriter { data: Vec::with_capacity(capacity) };
+  }
+
+  #[inline(always)]
+  pub fn len(&self) -> usize {
+    return self.data.len();
+  }
+
+  pub fn align(&mut self, alignment: usize) {
+    while self.data.len() % alignment != 0 {
+      self.data.push(0);
+    }
+  }
+
+  pub fn pad_to(&mut self, length: usize) {
+    assert!(length >= self.data.len());
+
+    self.data.resize(length, 0);
+  }
+
+  pub fn write_scalar_value(&mut self, value: &ScalarValue) {
+    let data = &mut self.data;
+
+    let _ = match *value {
+      ScalarValue::f16(x) => data.write_i16::<NativeEndian>(x),
+      ScalarValue::f32(x) => data.write_f32::<NativeEndian>(x),
+      ScalarValue::u8(x) => data.write_u8(x),
+      ScalarValue::u16(x) => data.write_u16::<NativeEndian>(x),
+      ScalarValue::u32(x) => data.write_u32::<NativeEndian>(x),
+      ScalarValue::u8_normalized(x) => data.write_u8(x),
+      ScalarValue::u16_normalized(x) => data.write_u16::<NativeEndian>(x),
+      ScalarValue::u32_normalized(x) => data.write_u32::<NativeEndian>(x),
+      ScalarValue::i8(x) => data.write_i8(x),
+      ScalarValue::i16(x) => data.write_i16::<NativeEndian>(x),
+      ScalarValue::i32(x) => data.write_i32::<NativeEndian>(x),
+      ScalarValue::i8_normalized(x) => data.write_i8(x),
+      ScalarValue::i16_normalized(x) => data.write_i16::<NativeEndian>(x),
+      ScalarValue::i32_normalized(x) => data.write_i32::<NativeEndian>(x),
+
+      // Every component of a packed value shares the same word, so only the first one writes it
+      ScalarValue::u10_10_10_2_normalized(x, 0) => data.write_u32::<NativeEndian>(x),
+      ScalarValue::i10_10_10_2_normalized(x, 0) => data.write_u32::<NativeEndian>(x),
+      ScalarValue::f11_11_10(x, 0) => data.write_u32::<NativeEndian>(x),
+      ScalarValue::f9_9_9_e5(x, 0) => data.write_u32::<NativeEndian>(x),
+      _ => Ok(())
+    }.unwrap();
+  }
+
+  pub fn into_buffer(self, uri: Option<String>, name: Option<String>) -> Arc<Buffer> {
+    return Buffer::new(uri, name, self.data);
+  }
+}
+
+impl io::Write for BufferWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    return self.data.write(buf);
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    return Ok(());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::io::Write;
+
+  use buffer::buffer_view::BufferView;
+  use buffer::typed_view::ScalarTypedView;
+  use buffer::untyped_view::ScalarValue;
+
+  #[test]
+  fn test_align() {
+    let mut writer = BufferWriter::new();
+
+    writer.write_all(&[1, 2, 3]).unwrap();
+    writer.align(4);
+
+    assert_eq!(writer.into_buffer(None, None).as_slice(), &[1, 2, 3, 0]);
+  }
+
+  #[test]
+  fn test_write_scalar_value() {
+    let mut writer = BufferWriter::new();
+
+    writer.write_scalar_value(&ScalarValue::u8(1));
+    writer.align(2);
+    writer.write_scalar_value(&ScalarValue::u16(0x0302));
+
+    let buffer = writer.into_buffer(None, None);
+    let view = BufferView::new(None, buffer, 0, 4);
+
+    assert_eq!(view[0], 1);
+    assert_eq!(&ScalarTypedView::<u16>::new(None, &view, 2, 0, 1)[0], &0x0302);
+  }
+}
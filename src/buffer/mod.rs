@@ -1,9 +1,11 @@
 mod buffer;
 mod buffer_view;
+mod buffer_writer;
 mod typed_view;
 mod untyped_view;
 
 pub use buffer::buffer::*;
 pub use buffer::buffer_view::*;
+pub use buffer::buffer_writer::*;
 pub use buffer::typed_view::*;
 pub use buffer::untyped_view::*;
\ No newline at end of file
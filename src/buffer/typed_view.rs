@@ -67,6 +67,10 @@ impl<'a, T: 'a> TypedView<'a, T> {
   pub fn len(&self) -> usize {
     return self.scalar_view.length;
   }
+
+  pub fn elements(&self) -> usize {
+    return self.width.elements();
+  }
 }
 
 impl<'a, T: 'a> ops::Index<usize> for TypedView<'a, T> {
@@ -9,17 +9,88 @@ use vertex;
 extern {
   #[link_name = "llvm.convert.from.fp16.f32"]
   pub fn convert_from_fp16_to_f32(a: i16) -> f32;
+
+  #[link_name = "llvm.convert.to.fp16.f32"]
+  pub fn convert_from_f32_to_fp16(a: f32) -> i16;
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ScalarValue {
   f16(i16), f32(f32),
   u8(u8), u16(u16), u32(u32),
   u8_normalized(u8), u16_normalized(u16), u32_normalized(u32),
   i8(i8), i16(i16), i32(i32),
-  i8_normalized(i8), i16_normalized(i16), i32_normalized(i32)
+  i8_normalized(i8), i16_normalized(i16), i32_normalized(i32),
+  u10_10_10_2_normalized(u32, usize), i10_10_10_2_normalized(u32, usize), // Packed word and component
+  f11_11_10(u32, usize), f9_9_9_e5(u32, usize)
 }
 
 impl ScalarValue {
+  // Packed scalars hold several components in one word, so they have no value of their own
+  pub fn from_f32(scalar: vertex::Scalar, x: f32) -> Option<ScalarValue> {
+    return Some(match scalar {
+      vertex::Scalar::f16 if x.is_nan() => ScalarValue::f16(unsafe { convert_from_f32_to_fp16(x) }),
+      vertex::Scalar::f16 => ScalarValue::f16(unsafe { convert_from_f32_to_fp16(x.max(-65504.0).min(65504.0)) }),
+      vertex::Scalar::f32 => ScalarValue::f32(x),
+      vertex::Scalar::u8 => ScalarValue::u8(clamp_round(x as f64, 0.0, std::u8::MAX as f64) as u8),
+      vertex::Scalar::u16 => ScalarValue::u16(clamp_round(x as f64, 0.0, std::u16::MAX as f64) as u16),
+      vertex::Scalar::u32 => ScalarValue::u32(clamp_round(x as f64, 0.0, std::u32::MAX as f64) as u32),
+      vertex::Scalar::u8_normalized => ScalarValue::u8_normalized(clamp_round(x as f64 * std::u8::MAX as f64, 0.0, std::u8::MAX as f64) as u8),
+      vertex::Scalar::u16_normalized => ScalarValue::u16_normalized(clamp_round(x as f64 * std::u16::MAX as f64, 0.0, std::u16::MAX as f64) as u16),
+      vertex::Scalar::u32_normalized => ScalarValue::u32_normalized(clamp_round(x as f64 * std::u32::MAX as f64, 0.0, std::u32::MAX as f64) as u32),
+      vertex::Scalar::i8 => ScalarValue::i8(clamp_round(x as f64, std::i8::MIN as f64, std::i8::MAX as f64) as i8),
+      vertex::Scalar::i16 => ScalarValue::i16(clamp_round(x as f64, std::i16::MIN as f64, std::i16::MAX as f64) as i16),
+      vertex::Scalar::i32 => ScalarValue::i32(clamp_round(x as f64, std::i32::MIN as f64, std::i32::MAX as f64) as i32),
+      vertex::Scalar::i8_normalized => ScalarValue::i8_normalized(clamp_round(x as f64 * std::i8::MAX as f64, -(std::i8::MAX as f64), std::i8::MAX as f64) as i8),
+      vertex::Scalar::i16_normalized => ScalarValue::i16_normalized(clamp_round(x as f64 * std::i16::MAX as f64, -(std::i16::MAX as f64), std::i16::MAX as f64) as i16),
+      vertex::Scalar::i32_normalized => ScalarValue::i32_normalized(clamp_round(x as f64 * std::i32::MAX as f64, -(std::i32::MAX as f64), std::i32::MAX as f64) as i32),
+      _ => return None
+    });
+  }
+
+  pub fn from_f32_vector(format: vertex::Format, values: &[f32]) -> Vec<ScalarValue> {
+    let scalar = format.scalar();
+
+    // Missing elements are filled with zero and extra ones are dropped
+    if !scalar.is_packed() {
+      return (0 .. format.elements()).map(|i| ScalarValue::from_f32(scalar, if i < values.len() { values[i] } else { 0.0 }).unwrap()).collect();
+    }
+
+    let word = vertex::packed::pack(scalar, values);
+
+    return (0 .. format.elements()).map(|component| {
+      match scalar {
+        vertex::Scalar::u10_10_10_2_normalized => ScalarValue::u10_10_10_2_normalized(word, component),
+        vertex::Scalar::i10_10_10_2_normalized => ScalarValue::i10_10_10_2_normalized(word, component),
+        vertex::Scalar::f11_11_10 => ScalarValue::f11_11_10(word, component),
+        _ => ScalarValue::f9_9_9_e5(word, component)
+      }
+    }).collect();
+  }
+
+  pub fn scalar(&self) -> vertex::Scalar {
+    return match *self {
+      ScalarValue::f16(_) => vertex::Scalar::f16,
+      ScalarValue::f32(_) => vertex::Scalar::f32,
+      ScalarValue::u8(_) => vertex::Scalar::u8,
+      ScalarValue::u16(_) => vertex::Scalar::u16,
+      ScalarValue::u32(_) => vertex::Scalar::u32,
+      ScalarValue::u8_normalized(_) => vertex::Scalar::u8_normalized,
+      ScalarValue::u16_normalized(_) => vertex::Scalar::u16_normalized,
+      ScalarValue::u32_normalized(_) => vertex::Scalar::u32_normalized,
+      ScalarValue::i8(_) => vertex::Scalar::i8,
+      ScalarValue::i16(_) => vertex::Scalar::i16,
+      ScalarValue::i32(_) => vertex::Scalar::i32,
+      ScalarValue::i8_normalized(_) => vertex::Scalar::i8_normalized,
+      ScalarValue::i16_normalized(_) => vertex::Scalar::i16_normalized,
+      ScalarValue::i32_normalized(_) => vertex::Scalar::i32_normalized,
+      ScalarValue::u10_10_10_2_normalized(_, _) => vertex::Scalar::u10_10_10_2_normalized,
+      ScalarValue::i10_10_10_2_normalized(_, _) => vertex::Scalar::i10_10_10_2_normalized,
+      ScalarValue::f11_11_10(_, _) => vertex::Scalar::f11_11_10,
+      ScalarValue::f9_9_9_e5(_, _) => vertex::Scalar::f9_9_9_e5
+    };
+  }
+
   pub fn to_f32(&self) -> f32 {
     return match *self {
       ScalarValue::f16(x) => unsafe { convert_from_fp16_to_f32(x) },
@@ -35,7 +106,11 @@ impl ScalarValue {
       ScalarValue::i32(x) => x as f32,
       ScalarValue::i8_normalized(x) => (x as f32 / std::i8::MAX as f32).max(-1.0),
       ScalarValue::i16_normalized(x) => (x as f32 / std::i16::MAX as f32).max(-1.0),
-      ScalarValue::i32_normalized(x) => (x as f32 / std::i32::MAX as f32).max(-1.0)
+      ScalarValue::i32_normalized(x) => (x as f32 / std::i32::MAX as f32).max(-1.0),
+      ScalarValue::u10_10_10_2_normalized(x, i) => vertex::packed::unpack(vertex::Scalar::u10_10_10_2_normalized, x, i),
+      ScalarValue::i10_10_10_2_normalized(x, i) => vertex::packed::unpack(vertex::Scalar::i10_10_10_2_normalized, x, i),
+      ScalarValue::f11_11_10(x, i) => vertex::packed::unpack(vertex::Scalar::f11_11_10, x, i),
+      ScalarValue::f9_9_9_e5(x, i) => vertex::packed::unpack(vertex::Scalar::f9_9_9_e5, x, i)
     };
   }
 
@@ -49,6 +124,13 @@ impl ScalarValue {
   }
 }
 
+fn clamp_round(x: f64, min: f64, max: f64) -> f64 {
+  if x.is_nan() { return 0.0 };
+
+  return x.round().max(min).min(max);
+}
+
+#[derive(Clone)]
 pub enum ScalarUntypedView<'a> {
   f16(ScalarTypedView<'a, i16>),
   f32(ScalarTypedView<'a, f32>),
@@ -128,7 +210,11 @@ pub enum UntypedView<'a> {
   i32(TypedView<'a, i32>),
   i8_normalized(TypedView<'a, i8>),
   i16_normalized(TypedView<'a, i16>),
-  i32_normalized(TypedView<'a, i32>)
+  i32_normalized(TypedView<'a, i32>),
+  u10_10_10_2_normalized(TypedView<'a, u32>, usize), // Packed words and element count
+  i10_10_10_2_normalized(TypedView<'a, u32>, usize),
+  f11_11_10(TypedView<'a, u32>, usize),
+  f9_9_9_e5(TypedView<'a, u32>, usize)
 }
 
 impl<'a> UntypedView<'a> {
@@ -148,6 +234,10 @@ impl<'a> UntypedView<'a> {
       vertex::Format(vertex::Scalar::i8_normalized, width) => UntypedView::i8_normalized(TypedView::<i8>::new(None, view, width, offset, stride, length)),
       vertex::Format(vertex::Scalar::i16_normalized, width) => UntypedView::i16_normalized(TypedView::<i16>::new(None, view, width, offset, stride, length)),
       vertex::Format(vertex::Scalar::i32_normalized, width) => UntypedView::i32_normalized(TypedView::<i32>::new(None, view, width, offset, stride, length)),
+      vertex::Format(vertex::Scalar::u10_10_10_2_normalized, width) => UntypedView::u10_10_10_2_normalized(TypedView::<u32>::new(None, view, vertex::Width::Scalar, offset, stride, length), width.elements()),
+      vertex::Format(vertex::Scalar::i10_10_10_2_normalized, width) => UntypedView::i10_10_10_2_normalized(TypedView::<u32>::new(None, view, vertex::Width::Scalar, offset, stride, length), width.elements()),
+      vertex::Format(vertex::Scalar::f11_11_10, width) => UntypedView::f11_11_10(TypedView::<u32>::new(None, view, vertex::Width::Scalar, offset, stride, length), width.elements()),
+      vertex::Format(vertex::Scalar::f9_9_9_e5, width) => UntypedView::f9_9_9_e5(TypedView::<u32>::new(None, view, vertex::Width::Scalar, offset, stride, length), width.elements())
     };
   }
   
@@ -166,7 +256,11 @@ impl<'a> UntypedView<'a> {
       UntypedView::i32(ref x) => x.len(),
       UntypedView::i8_normalized(ref x) => x.len(),
       UntypedView::i16_normalized(ref x) => x.len(),
-      UntypedView::i32_normalized(ref x) => x.len()
+      UntypedView::i32_normalized(ref x) => x.len(),
+      UntypedView::u10_10_10_2_normalized(ref x, _) => x.len(),
+      UntypedView::i10_10_10_2_normalized(ref x, _) => x.len(),
+      UntypedView::f11_11_10(ref x, _) => x.len(),
+      UntypedView::f9_9_9_e5(ref x, _) => x.len()
     };
   }
 
@@ -185,13 +279,68 @@ impl<'a> UntypedView<'a> {
       UntypedView::i32(ref x) => x[i].iter().map(|v| ScalarValue::i32(*v)).collect(),
       UntypedView::i8_normalized(ref x) => x[i].iter().map(|v| ScalarValue::i8_normalized(*v)).collect(),
       UntypedView::i16_normalized(ref x) => x[i].iter().map(|v| ScalarValue::i16_normalized(*v)).collect(),
-      UntypedView::i32_normalized(ref x) => x[i].iter().map(|v| ScalarValue::i32_normalized(*v)).collect()
+      UntypedView::i32_normalized(ref x) => x[i].iter().map(|v| ScalarValue::i32_normalized(*v)).collect(),
+      UntypedView::u10_10_10_2_normalized(ref x, n) => (0 .. n).map(|c| ScalarValue::u10_10_10_2_normalized(x[i][0], c)).collect(),
+      UntypedView::i10_10_10_2_normalized(ref x, n) => (0 .. n).map(|c| ScalarValue::i10_10_10_2_normalized(x[i][0], c)).collect(),
+      UntypedView::f11_11_10(ref x, n) => (0 .. n).map(|c| ScalarValue::f11_11_10(x[i][0], c)).collect(),
+      UntypedView::f9_9_9_e5(ref x, n) => (0 .. n).map(|c| ScalarValue::f9_9_9_e5(x[i][0], c)).collect()
     };
   }
 
   pub fn get_f32(&self, i: usize) -> Vec<f32> {
     return self.get_vector_value(i).iter().map(|x| x.to_f32()).collect();
   }
+
+  pub fn elements(&self) -> usize {
+    return match *self {
+      UntypedView::f16(ref x) => x.elements(),
+      UntypedView::f32(ref x) => x.elements(),
+      UntypedView::u8(ref x) => x.elements(),
+      UntypedView::u16(ref x) => x.elements(),
+      UntypedView::u32(ref x) => x.elements(),
+      UntypedView::u8_normalized(ref x) => x.elements(),
+      UntypedView::u16_normalized(ref x) => x.elements(),
+      UntypedView::u32_normalized(ref x) => x.elements(),
+      UntypedView::i8(ref x) => x.elements(),
+      UntypedView::i16(ref x) => x.elements(),
+      UntypedView::i32(ref x) => x.elements(),
+      UntypedView::i8_normalized(ref x) => x.elements(),
+      UntypedView::i16_normalized(ref x) => x.elements(),
+      UntypedView::i32_normalized(ref x) => x.elements(),
+      UntypedView::u10_10_10_2_normalized(_, n) => n,
+      UntypedView::i10_10_10_2_normalized(_, n) => n,
+      UntypedView::f11_11_10(_, n) => n,
+      UntypedView::f9_9_9_e5(_, n) => n
+    };
+  }
+
+  // One element of a vector, without collecting the others
+  pub fn get_element_value(&self, i: usize, c: usize) -> ScalarValue {
+    return match *self {
+      UntypedView::f16(ref x) => ScalarValue::f16(x[i][c]),
+      UntypedView::f32(ref x) => ScalarValue::f32(x[i][c]),
+      UntypedView::u8(ref x) => ScalarValue::u8(x[i][c]),
+      UntypedView::u16(ref x) => ScalarValue::u16(x[i][c]),
+      UntypedView::u32(ref x) => ScalarValue::u32(x[i][c]),
+      UntypedView::u8_normalized(ref x) => ScalarValue::u8_normalized(x[i][c]),
+      UntypedView::u16_normalized(ref x) => ScalarValue::u16_normalized(x[i][c]),
+      UntypedView::u32_normalized(ref x) => ScalarValue::u32_normalized(x[i][c]),
+      UntypedView::i8(ref x) => ScalarValue::i8(x[i][c]),
+      UntypedView::i16(ref x) => ScalarValue::i16(x[i][c]),
+      UntypedView::i32(ref x) => ScalarValue::i32(x[i][c]),
+      UntypedView::i8_normalized(ref x) => ScalarValue::i8_normalized(x[i][c]),
+      UntypedView::i16_normalized(ref x) => ScalarValue::i16_normalized(x[i][c]),
+      UntypedView::i32_normalized(ref x) => ScalarValue::i32_normalized(x[i][c]),
+      UntypedView::u10_10_10_2_normalized(ref x, _) => ScalarValue::u10_10_10_2_normalized(x[i][0], c),
+      UntypedView::i10_10_10_2_normalized(ref x, _) => ScalarValue::i10_10_10_2_normalized(x[i][0], c),
+      UntypedView::f11_11_10(ref x, _) => ScalarValue::f11_11_10(x[i][0], c),
+      UntypedView::f9_9_9_e5(ref x, _) => ScalarValue::f9_9_9_e5(x[i][0], c)
+    };
+  }
+
+  pub fn get_element_f32(&self, i: usize, c: usize) -> f32 {
+    return self.get_element_value(i, c).to_f32();
+  }
 }
 
 #[cfg(test)]
@@ -201,6 +350,7 @@ mod tests {
   use buffer::Buffer;
   use buffer::buffer_view::BufferView;
   use buffer::typed_view::{TypedView, ScalarTypedView};
+  use vertex;
   use vertex::Width;
 
   #[test]
@@ -223,5 +373,35 @@ mod tests {
 
     assert_eq!(uv.get_f32(0), &[0x0302 as f32, 0x0504 as f32]);
     assert_eq!(uv.get_f32(1), &[0x0706 as f32, 0x0908 as f32]);
+    assert_eq!(uv.elements(), 2);
+    assert_eq!(uv.get_element_f32(1, 1), 0x0908 as f32);
+  }
+
+  #[test]
+  fn test_from_f32() {
+    assert_eq!(ScalarValue::from_f32(vertex::Scalar::f32, 0.5), Some(ScalarValue::f32(0.5)));
+    assert_eq!(ScalarValue::from_f32(vertex::Scalar::u8, 300.0), Some(ScalarValue::u8(255)));
+    assert_eq!(ScalarValue::from_f32(vertex::Scalar::u8_normalized, 0.5), Some(ScalarValue::u8_normalized(128)));
+    assert_eq!(ScalarValue::from_f32(vertex::Scalar::i16_normalized, -2.0), Some(ScalarValue::i16_normalized(-32767)));
+    assert_eq!(ScalarValue::from_f32(vertex::Scalar::f16, 1.5).unwrap().to_f32(), 1.5);
+    assert_eq!(ScalarValue::from_f32(vertex::Scalar::f11_11_10, 1.5), None);
+  }
+
+  #[test]
+  fn test_packed() {
+    let format = vertex::Format(vertex::Scalar::i10_10_10_2_normalized, vertex::Width::Vector3);
+    let values = ScalarValue::from_f32_vector(format, &[0.0, -1.0, 1.0]);
+
+    assert_eq!(values.iter().map(|x| x.to_f32()).collect::<Vec<f32>>(), &[0.0, -1.0, 1.0]);
+
+    let word = match values[0] { ScalarValue::i10_10_10_2_normalized(word, 0) => word, _ => unreachable!() };
+
+    let bytes = vec![word as u8, (word >> 8) as u8, (word >> 16) as u8, (word >> 24) as u8];
+    let view = BufferView::new(None, Buffer::new(None, None, bytes), 0, 4);
+    let uv = UntypedView::new(&view, format, 0, 4, 1);
+
+    assert_eq!(uv.get_f32(0), &[0.0, -1.0, 1.0]);
+    assert_eq!(uv.elements(), 3);
+    assert_eq!(uv.get_element_f32(0, 1), -1.0);
   }
 }
@@ -0,0 +1,694 @@
+use std::fmt;
+use std::io;
+use std::mem;
+
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+use asset;
+use index;
+use math;
+use model;
+use vertex;
+
+use animation::skeleton;
+use mesh::{Mesh, transform};
+
+use exporter;
+
+use exporter::{Output, joints, number, unique_name};
+
+const NAMESPACE: &'static str = "http://www.collada.org/2005/11/COLLADASchema";
+
+// Exports are meant to be reproducible, so they do not carry the actual time
+const TIMESTAMP: &'static str = "1970-01-01T00:00:00Z";
+
+pub struct Exporter;
+
+impl Exporter {
+  pub fn default() -> Exporter {
+    return Exporter;
+  }
+}
+
+impl exporter::Exporter for Exporter {
+  fn name(&self) -> &'static str {
+    return "collada";
+  }
+
+  fn extensions(&self) -> Vec<&'static str> {
+    return vec!["dae"];
+  }
+
+  fn export(&self, asset: &asset::Asset, output: &mut Output) -> io::Result<()> {
+    return export_to(asset, output);
+  }
+}
+
+pub fn export(asset: &asset::Asset) -> Vec<(String, Vec<u8>)> {
+  return exporter::in_memory(|output| export_to(asset, output));
+}
+
+pub fn export_to(asset: &asset::Asset, output: &mut Output) -> io::Result<()> {
+  try!(output.start_file(&format!("{}.dae", asset.name())));
+
+  return write_dae(asset, output);
+}
+
+pub fn export_dae(asset: &asset::Asset) -> Vec<u8> {
+  let mut result = Vec::new();
+
+  write_dae(asset, &mut result).unwrap();
+
+  return result;
+}
+
+// One scene with a node for every model. Skinned models get a skin controller and their bones
+// as joint nodes, blend shapes become a morph controller the skin builds on. COLLADA is right
+// handed, so the asset is mirrored into the Y up space glTF uses as well
+pub fn write_dae<W: Write>(asset: &asset::Asset, writer: W) -> io::Result<()> {
+  let asset = &transform::convert_asset(asset, &transform::EVE, &transform::GLTF);
+
+  let mut ids = Vec::new();
+  let mut models = Vec::new();
+
+  for object in &asset.objects {
+    match object {
+      &asset::Object::Model(ref m) => models.push((unique_name(&id(&m.name), &mut ids), m)),
+      _ => ()
+    }
+  }
+
+  let mut materials = Vec::new();
+
+  for &(_, model) in &models {
+    for submesh in &model.mesh.submeshes {
+      if !materials.contains(&id(&submesh.name)) {
+        materials.push(id(&submesh.name));
+      }
+    }
+  }
+
+  let mut w = Writer {
+    events: EmitterConfig::new().perform_indent(true).create_writer(writer),
+    text: String::new(),
+    separate: false,
+    error: None
+  };
+
+  w.start("COLLADA", &[("xmlns", NAMESPACE), ("version", "1.4.1")]);
+
+  w.start("asset", &[]);
+  w.start("contributor", &[]);
+  w.element("authoring_tool", &[], "eve-toolbox");
+  w.end();
+  w.element("created", &[], TIMESTAMP);
+  w.element("modified", &[], TIMESTAMP);
+  w.element("unit", &[("name", "meter"), ("meter", "1")], "");
+  w.element("up_axis", &[], "Y_UP");
+  w.end();
+
+  if !materials.is_empty() {
+    write_materials(&mut w, &materials);
+  }
+
+  w.start("library_geometries", &[]);
+
+  for &(ref model_id, model) in &models {
+    write_geometries(&mut w, model_id, model);
+  }
+
+  w.end();
+
+  let controllers: Vec<&(String, &model::Model)> = models.iter().filter(|x| !x.1.blend_shapes.is_empty() || joints(x.1).is_some()).collect();
+
+  if !controllers.is_empty() {
+    w.start("library_controllers", &[]);
+
+    for &&(ref model_id, model) in &controllers {
+      write_controllers(&mut w, model_id, model);
+    }
+
+    w.end();
+  }
+
+  w.start("library_visual_scenes", &[]);
+  w.start("visual_scene", &[("id", "scene"), ("name", "scene")]);
+
+  for &(ref model_id, model) in &models {
+    write_node(&mut w, model_id, model);
+  }
+
+  w.end();
+  w.end();
+
+  w.start("scene", &[]);
+  w.element("instance_visual_scene", &[("url", "#scene")], "");
+  w.end();
+
+  w.end();
+
+  return match w.error {
+    Some(e) => Err(e), None => Ok(())
+  };
+}
+
+// Keeps the first error and goes on, so writing elements does not have to check every one
+struct Writer<W: Write> {
+  events: EventWriter<W>,
+  text: String, // Values that were not written yet
+  separate: bool, // Whether the next value needs a space before it
+  error: Option<io::Error>
+}
+
+impl<W: Write> Writer<W> {
+  fn write(&mut self, event: XmlEvent) {
+    if self.error.is_none() {
+      if let Err(e) = self.events.write(event) {
+        self.error = Some(io::Error::new(io::ErrorKind::Other, format!("{}", e)));
+      }
+    }
+  }
+
+  fn start(&mut self, name: &str, attributes: &[(&str, &str)]) {
+    let mut event = XmlEvent::start_element(name);
+
+    for &(name, value) in attributes {
+      event = event.attr(name, value);
+    }
+
+    self.write(event.into());
+  }
+
+  fn end(&mut self) {
+    self.write(XmlEvent::end_element().into());
+  }
+
+  fn element(&mut self, name: &str, attributes: &[(&str, &str)], text: &str) {
+    self.start(name, attributes);
+
+    if !text.is_empty() {
+      self.write(XmlEvent::characters(text));
+    }
+
+    self.end();
+  }
+
+  // Values separated by spaces, written a chunk at a time as they come
+  fn values<T: fmt::Display, I: IntoIterator<Item = T>>(&mut self, values: I) {
+    for value in values {
+      if self.separate {
+        self.text.push(' ');
+      }
+
+      let _ = write!(self.text, "{}", value);
+
+      self.separate = true;
+
+      if self.text.len() >= 1 << 16 {
+        self.flush_values();
+      }
+    }
+  }
+
+  // The buffer is put back afterwards, to be reused
+  fn flush_values(&mut self) {
+    let mut text = mem::replace(&mut self.text, String::new());
+
+    if !text.is_empty() {
+      self.write(XmlEvent::characters(&text));
+    }
+
+    text.clear();
+
+    self.text = text;
+  }
+
+  fn end_values(&mut self) {
+    self.flush_values();
+    self.separate = false;
+  }
+
+  fn value_element<T: fmt::Display, I: IntoIterator<Item = T>>(&mut self, name: &str, attributes: &[(&str, &str)], values: I) {
+    self.start(name, attributes);
+    self.values(values);
+    self.end_values();
+    self.end();
+  }
+
+  // An array of `count` values with an accessor reading `stride` of them at a time, described by
+  // `params`
+  fn source<T: fmt::Display, I: IntoIterator<Item = T>>(&mut self, id: &str, array: &str, count: usize, values: I, stride: usize, params: &[(&str, &str)]) {
+    let array_id = format!("{}-array", id);
+    let array_url = format!("#{}", array_id);
+    let accessor_count = format!("{}", count / stride);
+    let count = format!("{}", count);
+    let stride = format!("{}", stride);
+
+    self.start("source", &[("id", id)]);
+    self.value_element(array, &[("id", &array_id), ("count", &count)], values);
+    self.start("technique_common", &[]);
+    self.start("accessor", &[("source", &array_url), ("count", &accessor_count), ("stride", &stride)]);
+
+    for &(name, kind) in params {
+      self.element("param", &[("name", name), ("type", kind)], "");
+    }
+
+    self.end();
+    self.end();
+    self.end();
+  }
+}
+
+// There is nothing to go on but the name of the area, so every material is a plain grey
+fn write_materials<W: Write>(w: &mut Writer<W>, materials: &[String]) {
+  w.start("library_effects", &[]);
+
+  for material in materials {
+    w.start("effect", &[("id", &format!("{}-effect", material))]);
+    w.start("profile_COMMON", &[]);
+    w.start("technique", &[("sid", "common")]);
+    w.start("lambert", &[]);
+    w.start("diffuse", &[]);
+    w.element("color", &[("sid", "diffuse")], "0.8 0.8 0.8 1");
+    w.end();
+    w.end();
+    w.end();
+    w.end();
+    w.end();
+  }
+
+  w.end();
+
+  w.start("library_materials", &[]);
+
+  for material in materials {
+    w.start("material", &[("id", &format!("{}-material", material)), ("name", material)]);
+    w.element("instance_effect", &[("url", &format!("#{}-effect", material))], "");
+    w.end();
+  }
+
+  w.end();
+}
+
+// Morph targets are whole meshes in COLLADA, so every blend shape becomes a copy of the mesh with
+// the positions and normals of the vertices it lists replaced
+fn write_geometries<W: Write>(w: &mut Writer<W>, model_id: &str, model: &model::Model) {
+  let mesh = &model.mesh;
+
+  let positions = vectors(mesh, &vertex::AttributeName::Position);
+  let normals = vectors(mesh, &vertex::AttributeName::Normal);
+
+  write_geometry(w, &format!("{}-mesh", model_id), mesh, &positions, &normals);
+
+  for (i, shape) in model.blend_shapes.iter().enumerate() {
+    let mut shape_positions = positions.clone();
+    let mut shape_normals = normals.clone();
+
+    let replaced_positions = vectors(shape, &vertex::AttributeName::Position);
+    let replaced_normals = vectors(shape, &vertex::AttributeName::Normal);
+
+    if let Some(points) = shape.submeshes.first() {
+      let view = points.untyped_view();
+
+      for p in 0 .. points.index_count {
+        let v = view.get_usize(p);
+
+        if v < shape_positions.len() && p < replaced_positions.len() {
+          shape_positions[v] = replaced_positions[p];
+        }
+
+        if v < shape_normals.len() && p < replaced_normals.len() {
+          shape_normals[v] = replaced_normals[p];
+        }
+      }
+    }
+
+    write_geometry(w, &format!("{}-morph{}", model_id, i), mesh, &shape_positions, &shape_normals);
+  }
+}
+
+fn write_geometry<W: Write>(w: &mut Writer<W>, geometry_id: &str, mesh: &Mesh, positions: &[math::Vector3], normals: &[math::Vector3]) {
+  let attributes = &mesh.descriptor.attributes;
+
+  w.start("geometry", &[("id", geometry_id), ("name", &mesh.name)]);
+  w.start("mesh", &[]);
+
+  let positions_id = format!("{}-positions", geometry_id);
+  let normals_id = format!("{}-normals", geometry_id);
+
+  w.source(&positions_id, "float_array", positions.len() * 3, positions.iter().flat_map(|x| x.iter().cloned()).map(number), 3, &[("X", "float"), ("Y", "float"), ("Z", "float")]);
+
+  if !normals.is_empty() {
+    w.source(&normals_id, "float_array", normals.len() * 3, normals.iter().flat_map(|x| x.iter().cloned()).map(number), 3, &[("X", "float"), ("Y", "float"), ("Z", "float")]);
+  }
+
+  // Inputs that share the index of the vertex, with their set number
+  let mut inputs: Vec<(&str, String, Option<usize>)> = Vec::new();
+
+  if !normals.is_empty() {
+    inputs.push(("NORMAL", normals_id, None));
+  }
+
+  for (name, semantic, params) in vec![
+    (vertex::AttributeName::TextureCoordinate, "TEXCOORD", vec![("S", "float"), ("T", "float")]),
+    (vertex::AttributeName::Color, "COLOR", vec![("R", "float"), ("G", "float"), ("B", "float"), ("A", "float")])
+  ] {
+    for (set, attribute) in attributes.iter().filter(|x| x.name == name).enumerate() {
+      let view = &mesh.untyped_view_for_attribute(attribute);
+      let elements = attribute.format.elements().min(params.len());
+
+      // Texture coordinates start at the top in EVE and at the bottom in COLLADA
+      let flip = name == vertex::AttributeName::TextureCoordinate;
+
+      let values = (0 .. mesh.vertex_count).flat_map(|v| (0 .. elements).map(move |c| {
+        let x = view.get_element_f32(v, c);
+
+        number(if flip && c == 1 { 1.0 - x } else { x })
+      }));
+      let source_id = format!("{}-{}{}", geometry_id, semantic.to_lowercase(), set);
+
+      w.source(&source_id, "float_array", mesh.vertex_count * elements, values, elements, &params[.. elements]);
+
+      inputs.push((semantic, source_id, Some(set)));
+    }
+  }
+
+  w.start("vertices", &[("id", &format!("{}-vertices", geometry_id))]);
+  w.element("input", &[("semantic", "POSITION"), ("source", &format!("#{}", positions_id))], "");
+  w.end();
+
+  for submesh in &mesh.submeshes {
+    let primitive = match submesh.geometry {
+      g if g.is_triangles() => "triangles",
+      index::Geometry::Lines | index::Geometry::LineStrips => "lines",
+      _ => continue // Points have no COLLADA primitive
+    };
+
+    let mut faces = submesh.faces();
+
+    w.start(primitive, &[("material", &id(&submesh.name)), ("count", &format!("{}", faces.len()))]);
+    w.element("input", &[("semantic", "VERTEX"), ("source", &format!("#{}-vertices", geometry_id)), ("offset", "0")], "");
+
+    for &(semantic, ref source_id, set) in &inputs {
+      let source = format!("#{}", source_id);
+
+      match set {
+        Some(set) => w.element("input", &[("semantic", semantic), ("source", &source), ("offset", "0"), ("set", &format!("{}", set))], ""),
+        None => w.element("input", &[("semantic", semantic), ("source", &source), ("offset", "0")], "")
+      }
+    }
+
+    w.start("p", &[]);
+
+    while let Some((width, face)) = faces.next_indices() {
+      w.values(face[.. width].iter());
+    }
+
+    w.end_values();
+    w.end();
+    w.end();
+  }
+
+  w.end();
+  w.end();
+}
+
+fn write_controllers<W: Write>(w: &mut Writer<W>, model_id: &str, model: &model::Model) {
+  let mesh = &model.mesh;
+  let mut source = format!("#{}-mesh", model_id);
+
+  if !model.blend_shapes.is_empty() {
+    let morph_id = format!("{}-morph", model_id);
+    let count = model.blend_shapes.len();
+
+    w.start("controller", &[("id", &morph_id), ("name", &format!("{}-morph", model.name))]);
+    w.start("morph", &[("source", &source), ("method", "NORMALIZED")]);
+    w.source(&format!("{}-targets", morph_id), "IDREF_array", count, (0 .. count).map(|i| format!("{}-morph{}", model_id, i)), 1, &[("IDREF", "IDREF")]);
+    w.source(&format!("{}-weights", morph_id), "float_array", count, (0 .. count).map(|_| 0), 1, &[("MORPH_WEIGHT", "float")]);
+    w.start("targets", &[]);
+    w.element("input", &[("semantic", "MORPH_TARGET"), ("source", &format!("#{}-targets", morph_id))], "");
+    w.element("input", &[("semantic", "MORPH_WEIGHT"), ("source", &format!("#{}-weights", morph_id))], "");
+    w.end();
+    w.end();
+    w.end();
+
+    source = format!("#{}", morph_id);
+  }
+
+  let joints = match joints(model) {
+    Some(joints) => joints, None => return
+  };
+
+  let skin_id = format!("{}-skin", model_id);
+  let bones = &model.skeleton.bones;
+
+  // Row major, like math::Matrix4
+  let bind_poses = joints.iter().flat_map(|x| {
+    let inverse = math::inverse_affine(&model.skeleton.world_transform(*x));
+
+    (0 .. 16).map(move |i| number(inverse[i / 4][i % 4]))
+  });
+
+  let indices = mesh.untyped_view_for(&vertex::AttributeName::JointIndices).unwrap();
+  let weights = mesh.untyped_view_for(&vertex::AttributeName::JointWeights);
+
+  let mut weight_values = Vec::new();
+  let mut counts = Vec::new();
+  let mut influences = Vec::new();
+
+  let influence_count = match weights { Some(ref weights) => weights.elements().min(indices.elements()), None => 1 };
+
+  // Only the influences with a weight are listed, as pairs of a joint and a weight
+  for v in 0 .. mesh.vertex_count {
+    let mut count = 0;
+
+    for c in 0 .. influence_count {
+      let weight = match weights { Some(ref weights) => weights.get_element_f32(v, c), None => 1.0 };
+
+      if weight > 0.0 {
+        influences.push(indices.get_element_f32(v, c) as usize);
+        influences.push(weight_values.len());
+        weight_values.push(weight);
+        count += 1;
+      }
+    }
+
+    counts.push(count);
+  }
+
+  w.start("controller", &[("id", &skin_id), ("name", &format!("{}-skin", model.name))]);
+  w.start("skin", &[("source", &source)]);
+  w.value_element("bind_shape_matrix", &[], (0 .. 16).map(|i| if i % 5 == 0 { 1 } else { 0 }));
+  w.source(&format!("{}-joints", skin_id), "Name_array", joints.len(), joints.iter().map(|x| id(&bones[*x].name)), 1, &[("JOINT", "name")]);
+  w.source(&format!("{}-bind_poses", skin_id), "float_array", joints.len() * 16, bind_poses, 16, &[("TRANSFORM", "float4x4")]);
+  w.source(&format!("{}-weights", skin_id), "float_array", weight_values.len(), weight_values.iter(), 1, &[("WEIGHT", "float")]);
+
+  w.start("joints", &[]);
+  w.element("input", &[("semantic", "JOINT"), ("source", &format!("#{}-joints", skin_id))], "");
+  w.element("input", &[("semantic", "INV_BIND_MATRIX"), ("source", &format!("#{}-bind_poses", skin_id))], "");
+  w.end();
+
+  w.start("vertex_weights", &[("count", &format!("{}", mesh.vertex_count))]);
+  w.element("input", &[("semantic", "JOINT"), ("source", &format!("#{}-joints", skin_id)), ("offset", "0")], "");
+  w.element("input", &[("semantic", "WEIGHT"), ("source", &format!("#{}-weights", skin_id)), ("offset", "1")], "");
+  w.value_element("vcount", &[], counts.iter());
+  w.value_element("v", &[], influences.iter());
+  w.end();
+
+  w.end();
+  w.end();
+}
+
+fn write_node<W: Write>(w: &mut Writer<W>, model_id: &str, model: &model::Model) {
+  let bones = &model.skeleton.bones;
+
+  w.start("node", &[("id", model_id), ("name", &model.name), ("type", "NODE")]);
+
+  for (i, bone) in bones.iter().enumerate() {
+    if bone.parent_index.is_none() {
+      write_joint(w, model_id, bones, i);
+    }
+  }
+
+  let roots: Vec<String> = (0 .. bones.len()).filter(|x| bones[*x].parent_index.is_none()).map(|x| format!("#{}-{}", model_id, id(&bones[x].name))).collect();
+
+  let skinned = joints(model).is_some();
+
+  let url = if skinned {
+    format!("#{}-skin", model_id)
+  } else if !model.blend_shapes.is_empty() {
+    format!("#{}-morph", model_id)
+  } else {
+    format!("#{}-mesh", model_id)
+  };
+
+  let instance = if skinned || !model.blend_shapes.is_empty() { "instance_controller" } else { "instance_geometry" };
+
+  w.start(instance, &[("url", &url)]);
+
+  if skinned {
+    for root in &roots {
+      w.element("skeleton", &[], root);
+    }
+  }
+
+  w.start("bind_material", &[]);
+  w.start("technique_common", &[]);
+
+  for submesh in &model.mesh.submeshes {
+    let material = id(&submesh.name);
+
+    w.element("instance_material", &[("symbol", &material), ("target", &format!("#{}-material", material))], "");
+  }
+
+  w.end();
+  w.end();
+  w.end();
+
+  w.end();
+}
+
+fn write_joint<W: Write>(w: &mut Writer<W>, model_id: &str, bones: &[skeleton::Bone], i: usize) {
+  let bone = &bones[i];
+  let sid = id(&bone.name);
+  let transform = bone.transform();
+
+  w.start("node", &[("id", &format!("{}-{}", model_id, sid)), ("name", &bone.name), ("sid", &sid), ("type", "JOINT")]);
+  w.value_element("matrix", &[("sid", "transform")], (0 .. 16).map(|x| number(transform[x / 4][x % 4])));
+
+  for child in 0 .. bones.len() {
+    if bones[child].parent_index == Some(i) {
+      write_joint(w, model_id, bones, child);
+    }
+  }
+
+  w.end();
+}
+
+fn vectors(mesh: &Mesh, name: &vertex::AttributeName) -> Vec<math::Vector3> {
+  let view = match mesh.untyped_view_for(name) {
+    Some(view) => view, None => return Vec::new()
+  };
+
+  let elements = view.elements().min(3);
+
+  return (0 .. mesh.vertex_count).map(|v| {
+    let mut vector = [0.0; 3];
+
+    for c in 0 .. elements {
+      vector[c] = view.get_element_f32(v, c);
+    }
+
+    vector
+  }).collect();
+}
+
+// Ids, sids and names in Name_array cannot hold whitespace and the like
+fn id(name: &str) -> String {
+  let id: String = name.chars().map(|x| if x.is_alphanumeric() || x == '_' || x == '-' { x } else { '_' }).collect();
+
+  return if id.is_empty() { "default".to_owned() } else { id };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::io::Cursor;
+
+  use xml::reader::{EventReader, XmlEvent};
+
+  use index;
+
+  use exporter::fixtures::{asset, bone};
+  use mesh::{Mesh, MeshBuilder};
+
+  // Start elements, with the text each one directly contains
+  fn elements(dae: &[u8]) -> Vec<(String, String)> {
+    let mut elements: Vec<(String, String)> = Vec::new();
+
+    for e in EventReader::new(Cursor::new(dae)) {
+      match e.unwrap() {
+        XmlEvent::StartElement { name, .. } => elements.push((name.local_name, String::new())),
+        XmlEvent::Characters(text) => elements.last_mut().unwrap().1.push_str(&text),
+        _ => ()
+      }
+    }
+
+    return elements;
+  }
+
+  fn text<'a>(elements: &'a [(String, String)], name: &str) -> Vec<&'a str> {
+    return elements.iter().filter(|x| x.0 == name).map(|x| x.1.as_str()).collect();
+  }
+
+  fn builder() -> MeshBuilder {
+    return MeshBuilder::new("hull")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]])
+      .normals(&[[0.0, 0.0, 1.0]; 3])
+      .texture_coordinates(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]])
+      .texture_coordinates(&[[0.5, 0.5]; 3])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2])
+      .submesh("engine glow", index::Geometry::Lines, &[0, 1])
+      .clone();
+  }
+
+  #[test]
+  fn test_export_geometry() {
+    let files = export(&asset(builder().build(), Vec::new(), Vec::new()));
+
+    assert_eq!(files[0].0, "hull.dae");
+
+    let elements = elements(&files[0].1);
+
+    assert_eq!(text(&elements, "material").len(), 2);
+    assert_eq!(text(&elements, "triangles").len(), 1);
+    assert_eq!(text(&elements, "lines").len(), 1);
+    assert_eq!(text(&elements, "instance_geometry").len(), 1);
+
+    // Mirrored along Z, which turns the triangles around, and with V starting at the bottom
+    assert_eq!(text(&elements, "p"), vec!["0 2 1", "0 1"]);
+    assert_eq!(text(&elements, "float_array")[1], "0 0 -1 0 0 -1 0 0 -1");
+    assert_eq!(text(&elements, "float_array")[2], "0 1 1 1 0 0");
+    assert_eq!(text(&elements, "float_array")[3], "0.5 0.5 0.5 0.5 0.5 0.5");
+  }
+
+  #[test]
+  fn test_export_skin_and_morph() {
+    let mesh = builder()
+      .joints(&[[0, 0, 0, 0], [1, 0, 0, 0], [0, 1, 0, 0]], &[[1.0, 0.0, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0], [0.5, 0.5, 0.0, 0.0]])
+      .bone_bindings(&["turret".to_owned(), "root bone".to_owned()])
+      .build();
+
+    let points = builder().submesh("raise", index::Geometry::Points, &[2]).build();
+
+    let raise = Mesh {
+      submeshes: vec![points.submeshes[2].clone()],
+      .. MeshBuilder::new("raise").positions(&[[0.0, 2.0, 0.0]]).build()
+    };
+
+    let bones = vec![bone("root bone", None, [0.0, 0.0, 0.0]), bone("turret", Some(0), [0.0, 1.0, 0.0])];
+
+    let elements = elements(&export_dae(&asset(mesh, vec![raise], bones)));
+
+    assert_eq!(text(&elements, "geometry").len(), 2);
+    assert_eq!(text(&elements, "IDREF_array"), vec!["hull-morph0"]);
+    assert_eq!(text(&elements, "Name_array"), vec!["turret root_bone"]);
+    assert_eq!(text(&elements, "vcount"), vec!["1 1 2"]);
+    assert_eq!(text(&elements, "v"), vec!["0 0 1 1 0 2 1 3"]);
+    assert_eq!(text(&elements, "skeleton"), vec!["#hull-root_bone"]);
+
+    // The turret is one unit up from the root, so its bind pose moves one unit down
+    let bind_poses = text(&elements, "float_array").into_iter().find(|x| x.split(' ').count() == 32).unwrap();
+
+    assert_eq!(bind_poses, "1 0 0 0 0 1 0 -1 0 0 1 0 0 0 0 1 1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1");
+
+    // The morph target is the whole mesh with the raised vertex
+    let target_positions = text(&elements, "float_array").into_iter().filter(|x| x.split(' ').count() == 9).nth(2).unwrap();
+
+    assert_eq!(target_positions, "0 0 0 1 0 0 0 2 0");
+  }
+}
@@ -0,0 +1,191 @@
+use std::{env, fs, io, process};
+
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use asset;
+
+use exporter;
+
+use exporter::{gltf, Output};
+
+const MAGIC: u32 = 0x46546C67; // "glTF"
+const VERSION: u32 = 2;
+
+const JSON_CHUNK: u32 = 0x4E4F534A;
+const BIN_CHUNK: u32 = 0x004E4942;
+
+// Tells apart the temporary buffers of exports running at the same time
+static TEMPORARY_BUFFERS: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Exporter;
+
+impl Exporter {
+  pub fn default() -> Exporter {
+    return Exporter;
+  }
+}
+
+impl exporter::Exporter for Exporter {
+  fn name(&self) -> &'static str {
+    return "glb";
+  }
+
+  fn extensions(&self) -> Vec<&'static str> {
+    return vec!["glb"];
+  }
+
+  fn export(&self, asset: &asset::Asset, output: &mut Output) -> io::Result<()> {
+    return export_to(asset, output);
+  }
+}
+
+pub fn export(asset: &asset::Asset) -> Vec<(String, Vec<u8>)> {
+  return exporter::in_memory(|output| export_to(asset, output));
+}
+
+pub fn export_to(asset: &asset::Asset, output: &mut Output) -> io::Result<()> {
+  try!(output.start_file(&format!("{}.glb", asset.name())));
+
+  return write_glb(asset, output);
+}
+
+pub fn export_glb(asset: &asset::Asset) -> Vec<u8> {
+  let mut result = Vec::new();
+
+  write_glb(asset, &mut result).unwrap();
+
+  return result;
+}
+
+// The header holds the length of the whole file and the JSON goes before the buffer, so the buffer
+// is written to a temporary file and copied into the binary chunk once the JSON is out
+pub fn write_glb<W: Write + ?Sized>(asset: &asset::Asset, w: &mut W) -> io::Result<()> {
+  let temporary = Temporary(env::temp_dir().join(format!("eve_toolbox_glb_{}_{}.bin", process::id(), TEMPORARY_BUFFERS.fetch_add(1, Ordering::SeqCst))));
+
+  let mut bin = try!(fs::OpenOptions::new().read(true).write(true).create_new(true).open(&temporary.0));
+
+  let json = {
+    let mut writer = io::BufWriter::new(&mut bin);
+    let json = try!(gltf::write_document(asset, None, &mut writer));
+
+    try!(writer.flush());
+
+    json
+  };
+
+  let bin_length = json.find("buffers").and_then(|x| x.as_array()).and_then(|x| x.first()).and_then(|x| x.find("byteLength")).and_then(|x| x.as_u64()).unwrap_or(0) as usize;
+
+  // The JSON chunk is padded with spaces and the binary one with zeroes
+  let mut json = format!("{}", json).into_bytes();
+
+  while json.len() % 4 != 0 {
+    json.push(b' ');
+  }
+
+  let bin_padding = (4 - bin_length % 4) % 4;
+
+  let bin_chunk_length = if bin_length == 0 { 0 } else { 8 + bin_length + bin_padding };
+  let length = 12 + 8 + json.len() + bin_chunk_length;
+
+  try!(w.write_u32::<LittleEndian>(MAGIC));
+  try!(w.write_u32::<LittleEndian>(VERSION));
+  try!(w.write_u32::<LittleEndian>(length as u32));
+
+  try!(w.write_u32::<LittleEndian>(json.len() as u32));
+  try!(w.write_u32::<LittleEndian>(JSON_CHUNK));
+  try!(w.write_all(&json));
+
+  // A buffer without any data has no chunk at all
+  if bin_length > 0 {
+    try!(w.write_u32::<LittleEndian>((bin_length + bin_padding) as u32));
+    try!(w.write_u32::<LittleEndian>(BIN_CHUNK));
+    try!(bin.seek(SeekFrom::Start(0)));
+    try!(io::copy(&mut bin, w));
+    try!(w.write_all(&[0; 3][.. bin_padding]));
+  }
+
+  return Ok(());
+}
+
+// Removes the file once it is no longer needed, whether or not writing went through
+struct Temporary(PathBuf);
+
+impl Drop for Temporary {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.0);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::io::Cursor;
+
+  use byteorder::{LittleEndian, ReadBytesExt};
+  use rustc_serialize::json::Json;
+
+  use asset;
+  use index;
+  use model;
+
+  use animation::skeleton;
+  use mesh::MeshBuilder;
+
+  #[test]
+  fn test_export_glb() {
+    let mesh = MeshBuilder::new("hull")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2])
+      .build();
+
+    let model = model::Model {
+      name: "hull".to_owned(),
+      mesh: mesh,
+      blend_shapes: Vec::new(),
+      lods: Vec::new(),
+      skeleton: skeleton::Skeleton { bones: Vec::new() }
+    };
+
+    let asset = asset::Asset { buffers: Vec::new(), objects: vec![asset::Object::Model(model)] };
+    let files = export(&asset);
+
+    assert_eq!(files[0].0, "hull.glb");
+
+    let glb = &files[0].1;
+    let mut cursor = Cursor::new(&glb[..]);
+
+    assert_eq!(cursor.read_u32::<LittleEndian>().unwrap(), MAGIC);
+    assert_eq!(cursor.read_u32::<LittleEndian>().unwrap(), 2);
+    assert_eq!(cursor.read_u32::<LittleEndian>().unwrap() as usize, glb.len());
+
+    let json_length = cursor.read_u32::<LittleEndian>().unwrap() as usize;
+
+    assert_eq!(json_length % 4, 0);
+    assert_eq!(cursor.read_u32::<LittleEndian>().unwrap(), JSON_CHUNK);
+
+    let json = Json::from_str(&String::from_utf8(glb[20 .. 20 + json_length].to_vec()).unwrap()).unwrap();
+
+    cursor.set_position((20 + json_length) as u64);
+
+    let bin_length = cursor.read_u32::<LittleEndian>().unwrap() as usize;
+
+    assert_eq!(cursor.read_u32::<LittleEndian>().unwrap(), BIN_CHUNK);
+    assert_eq!(20 + json_length + 8 + bin_length, glb.len());
+
+    // Embedded buffers have no uri, and may be shorter than their chunk by its padding
+    let buffer = &json.find("buffers").unwrap().as_array().unwrap()[0];
+
+    assert!(buffer.find("uri").is_none());
+    assert!(buffer.find("byteLength").unwrap().as_u64().unwrap() as usize <= bin_length);
+
+    // The chunk holds the same buffer a .bin would
+    let (_, bin) = gltf::document(&asset, None);
+
+    assert_eq!(&glb[28 + json_length .. 28 + json_length + bin.len()], &bin[..]);
+  }
+}
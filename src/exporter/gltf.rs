@@ -0,0 +1,1136 @@
+use std;
+use std::io;
+
+use std::io::Write;
+
+use rustc_serialize::json::Json;
+
+use asset;
+use index;
+use math;
+use model;
+use vertex;
+use exporter;
+
+use exporter::{Output, joints};
+use animation::{animation, sampler, skeleton};
+use buffer::{BufferWriter, ScalarValue};
+use mesh::{Mesh, Stream, Submesh};
+use mesh::{convert, transform};
+
+const ARRAY_BUFFER: u64 = 34962;
+const ELEMENT_ARRAY_BUFFER: u64 = 34963;
+
+pub struct Exporter;
+
+impl Exporter {
+  pub fn default() -> Exporter {
+    return Exporter;
+  }
+}
+
+impl exporter::Exporter for Exporter {
+  fn name(&self) -> &'static str {
+    return "gltf";
+  }
+
+  fn extensions(&self) -> Vec<&'static str> {
+    return vec!["gltf", "bin"];
+  }
+
+  fn export(&self, asset: &asset::Asset, output: &mut Output) -> io::Result<()> {
+    return export_to(asset, output);
+  }
+}
+
+pub fn export(asset: &asset::Asset) -> Vec<(String, Vec<u8>)> {
+  return exporter::in_memory(|output| export_to(asset, output));
+}
+
+// The JSON needs the offset and length of every buffer view, so the .bin is written first and
+// the .gltf from what was recorded while writing it
+pub fn export_to(asset: &asset::Asset, output: &mut Output) -> io::Result<()> {
+  let name = asset.name();
+  let bin_name = format!("{}.bin", name);
+
+  try!(output.start_file(&bin_name));
+
+  let json = try!(write_document(asset, Some(&bin_name), output));
+
+  try!(output.start_file(&format!("{}.gltf", name)));
+
+  return write!(output, "{}", json.pretty());
+}
+
+// The JSON part of the asset and the contents of its single buffer
+pub fn document(asset: &asset::Asset, uri: Option<&str>) -> (Json, Vec<u8>) {
+  let mut bin = Vec::new();
+  let json = write_document(asset, uri, &mut bin).unwrap();
+
+  return (json, bin);
+}
+
+// Writes the contents of the single buffer of the asset to `w` and returns the JSON part, which
+// refers to the buffer by `uri` or, without one, expects it to be embedded like in a .glb. EVE is
+// left handed and glTF right handed, which mirrors along Z. Skeletons and animations are converted
+// up front, mesh data is mirrored as it is written
+pub fn write_document<W: Write + ?Sized>(asset: &asset::Asset, uri: Option<&str>, w: &mut W) -> io::Result<Json> {
+  let mut document = Document::new(w);
+  let mut roots = Vec::new();
+
+  for object in &asset.objects {
+    match object {
+      &asset::Object::Model(ref m) => roots.extend(export_model(&mut document, m).into_iter()),
+      _ => ()
+    }
+  }
+
+  // Animations refer to the bones of every model, so they come last
+  for object in &asset.objects {
+    match object {
+      &asset::Object::Animation(ref a) => export_animation(&mut document, &transform::convert_animation(a, &transform::EVE, &transform::GLTF)),
+      _ => ()
+    }
+  }
+
+  document.align(4);
+
+  if let Some(error) = document.error.take() {
+    return Err(error);
+  }
+
+  let length = document.length;
+
+  let mut members = vec![
+    ("asset", object(vec![("version", string("2.0")), ("generator", string("eve-toolbox"))])),
+    ("scene", Json::U64(0)),
+    ("scenes", Json::Array(vec![object(vec![("nodes", indices(&roots))])]))
+  ];
+
+  let arrays = vec![
+    ("nodes", document.nodes),
+    ("meshes", document.meshes),
+    ("skins", document.skins),
+    ("animations", document.animations),
+    ("accessors", document.accessors),
+    ("bufferViews", document.buffer_views)
+  ];
+
+  for (name, array) in arrays {
+    if !array.is_empty() {
+      members.push((name, Json::Array(array)));
+    }
+  }
+
+  if length > 0 {
+    let mut buffer = vec![("byteLength", Json::U64(length as u64))];
+
+    if let Some(uri) = uri {
+      buffer.push(("uri", string(uri)));
+    }
+
+    members.push(("buffers", Json::Array(vec![object(buffer)])));
+  }
+
+  return Ok(object(members));
+}
+
+// The buffer goes straight to the sink, keeping the first error and going on so writing views does
+// not have to check every one
+struct Document<'a, W: Write + ?Sized + 'a> {
+  sink: &'a mut W,
+  length: usize,
+  error: Option<io::Error>,
+  buffer_views: Vec<Json>,
+  accessors: Vec<Json>,
+  nodes: Vec<Json>,
+  meshes: Vec<Json>,
+  skins: Vec<Json>,
+  animations: Vec<Json>,
+  bones: Vec<(String, usize)> // Node of each bone, for animation channels
+}
+
+impl<'a, W: Write + ?Sized> Document<'a, W> {
+  fn new(sink: &'a mut W) -> Document<'a, W> {
+    return Document {
+      sink: sink,
+      length: 0,
+      error: None,
+      buffer_views: Vec::new(),
+      accessors: Vec::new(),
+      nodes: Vec::new(),
+      meshes: Vec::new(),
+      skins: Vec::new(),
+      animations: Vec::new(),
+      bones: Vec::new()
+    };
+  }
+
+  fn write(&mut self, bytes: &[u8]) {
+    if self.error.is_none() {
+      if let Err(error) = self.sink.write_all(bytes) {
+        self.error = Some(error);
+      }
+    }
+
+    self.length += bytes.len();
+  }
+
+  fn align(&mut self, alignment: usize) {
+    let padding = (alignment - self.length % alignment) % alignment;
+
+    self.write(&[0; 4][.. padding]);
+  }
+
+  // Views start on a 4 byte boundary, and vertex views pad every element to one as well. A view is
+  // put together before it is written out
+  fn buffer_view(&mut self, values: &[ScalarValue], format: vertex::Format, target: Option<u64>) -> usize {
+    let stride = if target == Some(ARRAY_BUFFER) { Some((format.byte_size() + 3) / 4 * 4) } else { None };
+
+    let mut writer = BufferWriter::new();
+
+    for element in values.chunks(format.elements()) {
+      let start = writer.len();
+
+      for value in element {
+        writer.write_scalar_value(value);
+      }
+
+      if let Some(stride) = stride {
+        writer.pad_to(start + stride);
+      }
+    }
+
+    return self.bytes_view(writer.into_buffer(None, None).as_slice(), stride, target);
+  }
+
+  // Data that is already laid out the way glTF wants it is copied without decoding it
+  fn bytes_view(&mut self, bytes: &[u8], stride: Option<usize>, target: Option<u64>) -> usize {
+    self.align(4);
+
+    let offset = self.length;
+
+    self.write(bytes);
+
+    return self.push_buffer_view(offset, stride, target);
+  }
+
+  fn push_buffer_view(&mut self, offset: usize, stride: Option<usize>, target: Option<u64>) -> usize {
+    let mut members = vec![
+      ("buffer", Json::U64(0)),
+      ("byteOffset", Json::U64(offset as u64)),
+      ("byteLength", Json::U64((self.length - offset) as u64))
+    ];
+
+    if let Some(stride) = stride {
+      members.push(("byteStride", Json::U64(stride as u64)));
+    }
+
+    if let Some(target) = target {
+      members.push(("target", Json::U64(target)));
+    }
+
+    self.buffer_views.push(object(members));
+
+    return self.buffer_views.len() - 1;
+  }
+
+  // Bounds are only written for float accessors, which is all glTF asks them for
+  fn accessor(&mut self, values: &[ScalarValue], format: vertex::Format, target: Option<u64>, bounds: bool) -> usize {
+    let view = self.buffer_view(values, format, target);
+
+    let bounds = if bounds {
+      Some(bounds_of(format.elements(), &values.iter().map(|x| x.to_f32()).collect::<Vec<f32>>()))
+    } else {
+      None
+    };
+
+    return self.push_accessor(view, 0, format, values.len() / format.elements(), bounds);
+  }
+
+  fn push_accessor(&mut self, view: usize, offset: usize, format: vertex::Format, count: usize, bounds: Option<(Vec<f32>, Vec<f32>)>) -> usize {
+    let (component_type, normalized) = component_type(format.scalar()).unwrap();
+
+    let mut members = vec![
+      ("bufferView", Json::U64(view as u64)),
+      ("componentType", Json::U64(component_type)),
+      ("count", Json::U64(count as u64)),
+      ("type", string(accessor_type(format.width())))
+    ];
+
+    if offset != 0 {
+      members.push(("byteOffset", Json::U64(offset as u64)));
+    }
+
+    if normalized {
+      members.push(("normalized", Json::Boolean(true)));
+    }
+
+    if let Some((min, max)) = bounds {
+      members.push(("min", Json::Array(min.into_iter().map(number).collect())));
+      members.push(("max", Json::Array(max.into_iter().map(number).collect())));
+    }
+
+    self.accessors.push(object(members));
+
+    return self.accessors.len() - 1;
+  }
+
+  fn float_accessor(&mut self, values: &[f32], width: vertex::Width, target: Option<u64>, bounds: bool) -> usize {
+    let values: Vec<ScalarValue> = values.iter().map(|x| ScalarValue::f32(*x)).collect();
+
+    return self.accessor(&values, vertex::Format(vertex::Scalar::f32, width), target, bounds);
+  }
+}
+
+// Returns the nodes of the model that belong in the scene
+fn export_model<W: Write + ?Sized>(document: &mut Document<W>, model: &model::Model) -> Vec<usize> {
+  let mesh = &model.mesh;
+  let skeleton = transform::convert_skeleton(&model.skeleton, &transform::EVE, &transform::GLTF);
+  let bones = &skeleton.bones;
+
+  let mut roots = Vec::new();
+  let first_bone = document.nodes.len();
+
+  for (i, bone) in bones.iter().enumerate() {
+    let children: Vec<usize> = (0 .. bones.len()).filter(|x| bones[*x].parent_index == Some(i)).map(|x| first_bone + x).collect();
+
+    let mut members = vec![
+      ("name", string(&bone.name)),
+      ("translation", numbers(&bone.position)),
+      ("rotation", numbers(&math::normalize_quaternion(bone.orientation))),
+      ("scale", numbers(&scale(bone)))
+    ];
+
+    if !children.is_empty() {
+      members.push(("children", indices(&children)));
+    }
+
+    document.nodes.push(object(members));
+    document.bones.push((bone.name.clone(), first_bone + i));
+
+    if bone.parent_index.is_none() {
+      roots.push(first_bone + i);
+    }
+  }
+
+  // A skin needs every bound bone in the skeleton, without one the joints are left out
+  let skin = match joints(model) {
+    Some(ref joints) => Some(export_skin(document, &skeleton, joints, first_bone)),
+    None => None
+  };
+
+  let mut members = vec![("name", string(&model.name))];
+
+  if let Some(m) = export_mesh(document, mesh, &model.blend_shapes, skin.is_some()) {
+    members.push(("mesh", Json::U64(m as u64)));
+
+    if let Some(skin) = skin {
+      members.push(("skin", Json::U64(skin as u64)));
+    }
+  }
+
+  document.nodes.push(object(members));
+  roots.insert(0, document.nodes.len() - 1);
+
+  return roots;
+}
+
+fn export_skin<W: Write + ?Sized>(document: &mut Document<W>, skeleton: &skeleton::Skeleton, joints: &[usize], first_bone: usize) -> usize {
+  let mut matrices = Vec::new();
+
+  // Column major, unlike math::Matrix4
+  for joint in joints {
+    let inverse = math::inverse_affine(&skeleton.world_transform(*joint));
+
+    for c in 0 .. 4 {
+      for r in 0 .. 4 {
+        matrices.push(inverse[r][c]);
+      }
+    }
+  }
+
+  let inverse_bind_matrices = document.float_accessor(&matrices, vertex::Width::Matrix4x4, None, false);
+
+  let nodes: Vec<usize> = joints.iter().map(|x| first_bone + x).collect();
+
+  document.skins.push(object(vec![
+    ("joints", indices(&nodes)),
+    ("inverseBindMatrices", Json::U64(inverse_bind_matrices as u64))
+  ]));
+
+  return document.skins.len() - 1;
+}
+
+fn export_mesh<W: Write + ?Sized>(document: &mut Document<W>, mesh: &Mesh, blend_shapes: &[Mesh], skinned: bool) -> Option<usize> {
+  let mut exported = Vec::new();
+
+  for (i, attribute) in mesh.descriptor.attributes.iter().enumerate() {
+    let set = mesh.descriptor.attributes[.. i].iter().filter(|x| x.name == attribute.name).count();
+
+    let semantic = match semantic(&attribute.name, set) {
+      Some(semantic) => semantic, None => continue
+    };
+
+    let joints = attribute.name == vertex::AttributeName::JointIndices || attribute.name == vertex::AttributeName::JointWeights;
+
+    if joints && !skinned {
+      continue;
+    }
+
+    let format = attribute_format(&attribute.name, attribute.format);
+
+    exported.push((semantic, attribute, format, shareable(mesh, attribute, format)));
+  }
+
+  // Every vertex buffer with an attribute to share is copied once
+  let mut shared_views = vec![None; mesh.buffers.len()];
+
+  for b in 0 .. mesh.buffers.len() {
+    let shared: Vec<&vertex::Attribute> = exported.iter().filter(|x| x.3 && x.1.buffer_index == b).map(|x| x.1).collect();
+
+    if !shared.is_empty() {
+      shared_views[b] = Some(vertex_buffer_view(document, mesh, b, &shared));
+    }
+  }
+
+  let mut attributes = Vec::new();
+
+  for &(ref semantic, attribute, format, shared) in &exported {
+    let bounds = semantic == "POSITION";
+
+    let accessor = if shared {
+      let bounds = if bounds {
+        let view = mesh.untyped_view_for_attribute(attribute);
+        let mut values: Vec<f32> = (0 .. mesh.vertex_count).flat_map(|i| view.get_f32(i).into_iter()).collect();
+
+        mirror(&attribute.name, format.elements(), &mut values);
+
+        Some(bounds_of(format.elements(), &values))
+      } else {
+        None
+      };
+
+      document.push_accessor(shared_views[attribute.buffer_index].unwrap(), attribute.offset, format, mesh.vertex_count, bounds)
+    } else {
+      let converted = convert::convert_stream(&mirrored_stream(mesh, attribute), format).0;
+
+      document.accessor(&converted.values, format, Some(ARRAY_BUFFER), bounds)
+    };
+
+    attributes.push((semantic.clone(), Json::U64(accessor as u64)));
+  }
+
+  let (targets, target_names) = export_morph_targets(document, mesh, blend_shapes);
+
+  let mut primitives = Vec::new();
+
+  for submesh in &mesh.submeshes {
+    let (mode, accessor) = match export_indices(document, mesh, submesh) {
+      Some(indices) => indices, None => continue
+    };
+
+    let mut members = vec![
+      ("attributes", Json::Object(attributes.iter().cloned().collect())),
+      ("indices", Json::U64(accessor as u64)),
+      ("mode", Json::U64(mode))
+    ];
+
+    if !targets.is_empty() {
+      members.push(("targets", Json::Array(targets.clone())));
+    }
+
+    primitives.push(object(members));
+  }
+
+  if primitives.is_empty() {
+    return None;
+  }
+
+  let mut members = vec![("name", string(&mesh.name)), ("primitives", Json::Array(primitives))];
+
+  if !targets.is_empty() {
+    members.push(("weights", Json::Array(targets.iter().map(|_| number(0.0)).collect())));
+    members.push(("extras", object(vec![("targetNames", Json::Array(target_names.iter().map(|x| string(x)).collect()))])));
+  }
+
+  document.meshes.push(object(members));
+
+  return Some(document.meshes.len() - 1);
+}
+
+// Attributes already in the format glTF takes are not converted, they point into a copy of the
+// vertex buffer they are in. Mirrored ones have to be floats, which change sign with one bit
+fn shareable(mesh: &Mesh, attribute: &vertex::Attribute, format: vertex::Format) -> bool {
+  let source = &mesh.buffers[attribute.buffer_index];
+  let stride = mesh.descriptor.layouts[attribute.buffer_index].stride;
+
+  let length = source.length.min(mesh.vertex_count * stride);
+  let byte_size = format.byte_size();
+
+  return attribute.format == format && mesh.vertex_count > 0
+    && (mirrored_components(&attribute.name).is_empty() || format.scalar() == vertex::Scalar::f32)
+    && stride % 4 == 0 && stride >= 4 && stride <= 252
+    && attribute.offset % format.scalar().byte_size() == 0 && attribute.offset + byte_size <= stride
+    && (mesh.vertex_count - 1) * stride + attribute.offset + byte_size <= length;
+}
+
+// The buffer is copied a chunk of vertices at a time, flipping the sign bit of the mirrored
+// components of the shared attributes on the way
+fn vertex_buffer_view<W: Write + ?Sized>(document: &mut Document<W>, mesh: &Mesh, buffer_index: usize, shared: &[&vertex::Attribute]) -> usize {
+  let source = &mesh.buffers[buffer_index];
+  let stride = mesh.descriptor.layouts[buffer_index].stride;
+
+  let length = source.length.min(mesh.vertex_count * stride);
+
+  // Floats are little endian, the sign is in their last byte
+  let signs: Vec<usize> = shared.iter().flat_map(|attribute| {
+    mirrored_components(&attribute.name).iter()
+      .filter(|c| **c < attribute.format.elements())
+      .map(|c| attribute.offset + c * 4 + 3)
+      .collect::<Vec<usize>>()
+      .into_iter()
+  }).collect();
+
+  document.align(4);
+
+  let offset = document.length;
+
+  for chunk in source[0 .. length].chunks(stride * 1024) {
+    let mut chunk = chunk.to_vec();
+
+    for vertex in chunk.chunks_mut(stride) {
+      for sign in &signs {
+        if *sign < vertex.len() {
+          vertex[*sign] ^= 0x80;
+        }
+      }
+    }
+
+    document.write(&chunk);
+  }
+
+  return document.push_buffer_view(offset, Some(stride), Some(ARRAY_BUFFER));
+}
+
+// The attribute as floats, mirrored
+fn mirrored_stream(mesh: &Mesh, attribute: &vertex::Attribute) -> Stream {
+  let view = mesh.untyped_view_for_attribute(attribute);
+  let elements = attribute.format.elements();
+
+  let mut values: Vec<f32> = (0 .. mesh.vertex_count).flat_map(|i| view.get_f32(i).into_iter()).collect();
+
+  mirror(&attribute.name, elements, &mut values);
+
+  let mut stream = Stream { name: attribute.name.clone(), format: vertex::Format(vertex::Scalar::f32, attribute.format.width()), values: values.into_iter().map(ScalarValue::f32).collect() };
+
+  // Tangents without a handedness are taken to be right handed
+  if attribute.name == vertex::AttributeName::Tangent && elements < 4 {
+    let values: Vec<ScalarValue> = stream.values.chunks(elements).flat_map(|x| {
+      let mut tangent = x.to_vec();
+
+      tangent.resize(3, ScalarValue::f32(0.0));
+      tangent.push(ScalarValue::f32(1.0));
+
+      tangent.into_iter()
+    }).collect();
+
+    stream = Stream { name: stream.name, format: vertex::Format(vertex::Scalar::f32, vertex::Width::Vector4), values: values };
+  }
+
+  return stream;
+}
+
+// The components that change sign going from EVE to glTF. The handedness of a tangent flips along
+// with its direction
+fn mirrored_components(name: &vertex::AttributeName) -> &'static [usize] {
+  return match *name {
+    vertex::AttributeName::Position | vertex::AttributeName::Normal => &[2],
+    vertex::AttributeName::Bitangent | vertex::AttributeName::Binormal => &[2],
+    vertex::AttributeName::Tangent => &[2, 3],
+    _ => &[]
+  };
+}
+
+fn mirror(name: &vertex::AttributeName, elements: usize, values: &mut [f32]) {
+  for element in values.chunks_mut(elements) {
+    for c in mirrored_components(name) {
+      if *c < elements {
+        element[*c] = -element[*c];
+      }
+    }
+  }
+}
+
+// Mirroring turns triangles around, which swapping their last two corners undoes. Without restarts
+// lists are copied that way, and points and lines as they are, unless a u16 index could be the
+// reserved 65535. Anything else goes through its faces, as glTF has no primitive restart and a
+// mirrored strip would start with the wrong winding
+fn export_indices<W: Write + ?Sized>(document: &mut Document<W>, mesh: &Mesh, submesh: &Submesh) -> Option<(u64, usize)> {
+  let copyable = !submesh.primitive_restart
+    && (submesh.index_format == index::Format::u32 || mesh.vertex_count < std::u16::MAX as usize)
+    && submesh.geometry != index::Geometry::TriangleStrips && submesh.geometry != index::Geometry::TriangleFans;
+
+  if copyable {
+    let size = submesh.index_format.byte_size();
+    let triangles = submesh.geometry == index::Geometry::Triangles;
+    let count = if triangles { submesh.index_count / 3 * 3 } else { submesh.index_count };
+
+    if count == 0 {
+      return None;
+    }
+
+    document.align(4);
+
+    let offset = document.length;
+
+    for chunk in submesh.view[0 .. count * size].chunks(3 * size * 1024) {
+      let mut chunk = chunk.to_vec();
+
+      if triangles {
+        for triangle in chunk.chunks_mut(3 * size) {
+          for b in 0 .. size {
+            triangle.swap(size + b, 2 * size + b);
+          }
+        }
+      }
+
+      document.write(&chunk);
+    }
+
+    let view = document.push_buffer_view(offset, None, Some(ELEMENT_ARRAY_BUFFER));
+    let scalar = match submesh.index_format { index::Format::u16 => vertex::Scalar::u16, index::Format::u32 => vertex::Scalar::u32 };
+
+    return Some((mode(submesh.geometry), document.push_accessor(view, 0, vertex::Format(scalar, vertex::Width::Scalar), count, None)));
+  }
+
+  let geometry = match submesh.geometry {
+    index::Geometry::LineStrips => index::Geometry::Lines,
+    g if g.is_triangles() => index::Geometry::Triangles,
+    g => g
+  };
+
+  let indices: Vec<usize> = submesh.faces().flat_map(|x| {
+    if x.len() == 3 { vec![x[0], x[2], x[1]] } else { x }.into_iter()
+  }).collect();
+
+  if indices.is_empty() {
+    return None;
+  }
+
+  let scalar = if mesh.vertex_count < std::u16::MAX as usize { vertex::Scalar::u16 } else { vertex::Scalar::u32 };
+
+  let values: Vec<ScalarValue> = indices.iter().map(|x| match scalar {
+    vertex::Scalar::u16 => ScalarValue::u16(*x as u16),
+    _ => ScalarValue::u32(*x as u32)
+  }).collect();
+
+  return Some((mode(geometry), document.accessor(&values, vertex::Format(scalar, vertex::Width::Scalar), Some(ELEMENT_ARRAY_BUFFER), false)));
+}
+
+// Blend shapes hold the final positions and normals of the vertices their points list, which
+// become dense deltas over the whole mesh
+fn export_morph_targets<W: Write + ?Sized>(document: &mut Document<W>, mesh: &Mesh, blend_shapes: &[Mesh]) -> (Vec<Json>, Vec<String>) {
+  let mut targets = Vec::new();
+  let mut names = Vec::new();
+
+  let positions = match mesh.positions() {
+    Some(positions) => positions, None => return (targets, names)
+  };
+
+  let normals = mesh.untyped_view_for(&vertex::AttributeName::Normal);
+
+  for shape in blend_shapes {
+    let (shape_positions, points) = match (shape.positions(), shape.submeshes.first()) {
+      (Some(p), Some(s)) => (p, s), _ => continue
+    };
+
+    let shape_normals = shape.untyped_view_for(&vertex::AttributeName::Normal);
+    let view = points.untyped_view();
+
+    let mut position_deltas = vec![0.0; mesh.vertex_count * 3];
+    let mut normal_deltas = vec![0.0; mesh.vertex_count * 3];
+
+    for i in 0 .. points.index_count.min(shape.vertex_count) {
+      let v = view.get_usize(i);
+
+      if v >= mesh.vertex_count {
+        continue;
+      }
+
+      let delta = math::sub(shape_positions[i], positions[v]);
+
+      for c in 0 .. 3 {
+        position_deltas[v * 3 + c] = delta[c];
+      }
+
+      if let (Some(ref normals), Some(ref shape_normals)) = (normals.as_ref(), shape_normals.as_ref()) {
+        let delta = math::sub(math::to_vector3(&shape_normals.get_f32(i)), math::to_vector3(&normals.get_f32(v)));
+
+        for c in 0 .. 3 {
+          normal_deltas[v * 3 + c] = delta[c];
+        }
+      }
+    }
+
+    mirror(&vertex::AttributeName::Position, 3, &mut position_deltas);
+    mirror(&vertex::AttributeName::Normal, 3, &mut normal_deltas);
+
+    let mut attributes = vec![("POSITION", Json::U64(document.float_accessor(&position_deltas, vertex::Width::Vector3, Some(ARRAY_BUFFER), true) as u64))];
+
+    if normals.is_some() && shape_normals.is_some() {
+      attributes.push(("NORMAL", Json::U64(document.float_accessor(&normal_deltas, vertex::Width::Vector3, Some(ARRAY_BUFFER), false) as u64)));
+    }
+
+    targets.push(object(attributes));
+    names.push(shape.name.clone());
+  }
+
+  return (targets, names);
+}
+
+fn export_animation<W: Write + ?Sized>(document: &mut Document<W>, animation: &animation::Animation) {
+  let mut samplers = Vec::new();
+  let mut channels = Vec::new();
+
+  for track in &animation.tracks {
+    let node = match document.bones.iter().find(|x| x.0 == track.bone) {
+      Some(&(_, node)) => node, None => continue
+    };
+
+    let curves = [("rotation", &track.orientation), ("translation", &track.position), ("scale", &track.scale_shear)];
+
+    for &(path, curve) in &curves {
+      let (times, values, width) = match curve.as_ref().and_then(|x| keyframes(path, x)) {
+        Some(keyframes) => keyframes, None => continue
+      };
+
+      let input = document.float_accessor(&times, vertex::Width::Scalar, None, true);
+      let output = document.float_accessor(&values, width, None, false);
+
+      // Curves of a higher degree are approximated by interpolating their control points linearly
+      let interpolation = if curve.as_ref().unwrap().degree == 0 { "STEP" } else { "LINEAR" };
+
+      samplers.push(object(vec![
+        ("input", Json::U64(input as u64)),
+        ("output", Json::U64(output as u64)),
+        ("interpolation", string(interpolation))
+      ]));
+
+      channels.push(object(vec![
+        ("sampler", Json::U64((samplers.len() - 1) as u64)),
+        ("target", object(vec![("node", Json::U64(node as u64)), ("path", string(path))]))
+      ]));
+    }
+  }
+
+  if channels.is_empty() {
+    return;
+  }
+
+  document.animations.push(object(vec![
+    ("name", string(&animation.name)),
+    ("samplers", Json::Array(samplers)),
+    ("channels", Json::Array(channels))
+  ]));
+}
+
+// Key times have to increase strictly, keys that do not are dropped. Returns None for samplers
+// with outputs that do not fit the path
+fn keyframes(path: &str, sampler: &sampler::Sampler) -> Option<(Vec<f32>, Vec<f32>, vertex::Width)> {
+  let (components, width) = match (path, sampler.outputs.len()) {
+    ("rotation", 4) => (vec![0, 1, 2, 3], vertex::Width::Vector4),
+    ("translation", 3) | ("scale", 3) => (vec![0, 1, 2], vertex::Width::Vector3),
+    ("scale", 9) => (vec![0, 4, 8], vertex::Width::Vector3),
+    _ => return None
+  };
+
+  if sampler.outputs.iter().any(|x| x.len() != sampler.input.len()) {
+    return None;
+  }
+
+  let mut times: Vec<f32> = Vec::new();
+  let mut values = Vec::new();
+
+  for (k, time) in sampler.input.iter().enumerate() {
+    if *time < 0.0 || times.last().map_or(false, |x| *time <= *x) {
+      continue;
+    }
+
+    let value: Vec<f32> = components.iter().map(|c| sampler.outputs[*c][k]).collect();
+
+    times.push(*time);
+
+    if path == "rotation" {
+      values.extend(math::normalize_quaternion([value[0], value[1], value[2], value[3]]).iter().cloned());
+    } else {
+      values.extend(value.into_iter());
+    }
+  }
+
+  if times.is_empty() {
+    return None;
+  }
+
+  return Some((times, values, width));
+}
+
+fn bounds_of(elements: usize, values: &[f32]) -> (Vec<f32>, Vec<f32>) {
+  let mut min = vec![std::f32::MAX; elements];
+  let mut max = vec![std::f32::MIN; elements];
+
+  for element in values.chunks(elements) {
+    for (i, value) in element.iter().enumerate() {
+      min[i] = min[i].min(*value);
+      max[i] = max[i].max(*value);
+    }
+  }
+
+  return (min, max);
+}
+
+// glTF nodes cannot shear, so only the diagonal of the scale/shear matrix is kept
+fn scale(bone: &skeleton::Bone) -> [f32; 3] {
+  return [bone.scale_shear[0], bone.scale_shear[4], bone.scale_shear[8]];
+}
+
+fn semantic(name: &vertex::AttributeName, set: usize) -> Option<String> {
+  return match *name {
+    vertex::AttributeName::Position if set == 0 => Some("POSITION".to_owned()),
+    vertex::AttributeName::Normal if set == 0 => Some("NORMAL".to_owned()),
+    vertex::AttributeName::Tangent if set == 0 => Some("TANGENT".to_owned()),
+    vertex::AttributeName::Position | vertex::AttributeName::Normal | vertex::AttributeName::Tangent => None,
+    vertex::AttributeName::TextureCoordinate => Some(format!("TEXCOORD_{}", set)),
+    vertex::AttributeName::Color => Some(format!("COLOR_{}", set)),
+    vertex::AttributeName::JointIndices => Some(format!("JOINTS_{}", set)),
+    vertex::AttributeName::JointWeights => Some(format!("WEIGHTS_{}", set)),
+
+    // Application specific attributes start with an underscore
+    ref other => {
+      let name = match *other {
+        vertex::AttributeName::Other(ref name) => name.to_uppercase(),
+        ref other => format!("{:?}", other).to_uppercase()
+      };
+
+      Some(if set == 0 { format!("_{}", name) } else { format!("_{}_{}", name, set) })
+    }
+  };
+}
+
+// The closest format glTF allows for an attribute, normalized integers are kept where they can be
+fn attribute_format(name: &vertex::AttributeName, format: vertex::Format) -> vertex::Format {
+  let vertex::Format(scalar, width) = format;
+
+  let unsigned_normalized_or_float = match scalar {
+    vertex::Scalar::u8_normalized | vertex::Scalar::u16_normalized => scalar,
+    _ => vertex::Scalar::f32
+  };
+
+  return match *name {
+    vertex::AttributeName::Position | vertex::AttributeName::Normal => vertex::Format(vertex::Scalar::f32, vertex::Width::Vector3),
+    vertex::AttributeName::Tangent => vertex::Format(vertex::Scalar::f32, vertex::Width::Vector4),
+    vertex::AttributeName::TextureCoordinate => vertex::Format(unsigned_normalized_or_float, vertex::Width::Vector2),
+    vertex::AttributeName::Color if width == vertex::Width::Vector3 => vertex::Format(unsigned_normalized_or_float, width),
+    vertex::AttributeName::Color => vertex::Format(unsigned_normalized_or_float, vertex::Width::Vector4),
+    vertex::AttributeName::JointIndices if scalar == vertex::Scalar::u8 => vertex::Format(scalar, vertex::Width::Vector4),
+    vertex::AttributeName::JointIndices => vertex::Format(vertex::Scalar::u16, vertex::Width::Vector4),
+    vertex::AttributeName::JointWeights => vertex::Format(unsigned_normalized_or_float, vertex::Width::Vector4),
+
+    // Matrices of small components would need their columns padded, so they are written as floats
+    _ => match width {
+      vertex::Width::Matrix3x3 | vertex::Width::Matrix4x4 => vertex::Format(vertex::Scalar::f32, width),
+      _ if scalar != vertex::Scalar::u32 && component_type(scalar).is_some() => format,
+      _ => vertex::Format(vertex::Scalar::f32, width)
+    }
+  };
+}
+
+fn component_type(scalar: vertex::Scalar) -> Option<(u64, bool)> {
+  return match scalar {
+    vertex::Scalar::i8 => Some((5120, false)),
+    vertex::Scalar::i8_normalized => Some((5120, true)),
+    vertex::Scalar::u8 => Some((5121, false)),
+    vertex::Scalar::u8_normalized => Some((5121, true)),
+    vertex::Scalar::i16 => Some((5122, false)),
+    vertex::Scalar::i16_normalized => Some((5122, true)),
+    vertex::Scalar::u16 => Some((5123, false)),
+    vertex::Scalar::u16_normalized => Some((5123, true)),
+    vertex::Scalar::u32 => Some((5125, false)),
+    vertex::Scalar::f32 => Some((5126, false)),
+    _ => None
+  };
+}
+
+fn accessor_type(width: vertex::Width) -> &'static str {
+  return match width {
+    vertex::Width::Scalar => "SCALAR",
+    vertex::Width::Vector2 => "VEC2",
+    vertex::Width::Vector3 => "VEC3",
+    vertex::Width::Vector4 => "VEC4",
+    vertex::Width::Matrix3x3 => "MAT3",
+    vertex::Width::Matrix4x4 => "MAT4"
+  };
+}
+
+fn mode(geometry: index::Geometry) -> u64 {
+  return match geometry {
+    index::Geometry::Points => 0,
+    index::Geometry::Lines => 1,
+    index::Geometry::LineStrips => 3,
+    index::Geometry::Triangles => 4,
+    index::Geometry::TriangleStrips => 5,
+    index::Geometry::TriangleFans => 6
+  };
+}
+
+fn object(members: Vec<(&str, Json)>) -> Json {
+  return Json::Object(members.into_iter().map(|(name, value)| (name.to_owned(), value)).collect());
+}
+
+fn string(s: &str) -> Json {
+  return Json::String(s.to_owned());
+}
+
+// Goes through the shortest representation of the f32, so 0.1 is not written as 0.10000000149011612
+fn number(x: f32) -> Json {
+  return Json::F64(format!("{}", x).parse().unwrap_or(0.0));
+}
+
+fn numbers(xs: &[f32]) -> Json {
+  return Json::Array(xs.iter().map(|x| number(*x)).collect());
+}
+
+fn indices(xs: &[usize]) -> Json {
+  return Json::Array(xs.iter().map(|x| Json::U64(*x as u64)).collect());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use rustc_serialize::json::Json;
+
+  use asset;
+  use index;
+  use vertex;
+
+  use animation::{animation, sampler};
+  use exporter::fixtures::{bone, model};
+  use mesh::{Mesh, MeshBuilder};
+
+  fn builder() -> MeshBuilder {
+    return MeshBuilder::new("hull")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 2.0]])
+      .normals(&[[0.0, 0.0, 1.0]; 4])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2, 2, 1, 3])
+      .clone();
+  }
+
+  fn u64_at(json: &Json, path: &[&str]) -> u64 {
+    return json.find_path(path).unwrap().as_u64().unwrap();
+  }
+
+  fn at<'a>(json: &'a Json, name: &str, i: u64) -> &'a Json {
+    return &json.find(name).unwrap().as_array().unwrap()[i as usize];
+  }
+
+  fn accessor(json: &Json, i: u64) -> &Json {
+    return at(json, "accessors", i);
+  }
+
+  fn floats(json: &Json, name: &str) -> Vec<f64> {
+    return json.find(name).unwrap().as_array().unwrap().iter().map(|x| x.as_f64().unwrap()).collect();
+  }
+
+  fn primitive(json: &Json) -> &Json {
+    return at(at(json, "meshes", 0), "primitives", 0);
+  }
+
+  // The offline subset of the validator rules that concern the buffer
+  fn check_buffer_views(json: &Json, bin: &[u8]) {
+    assert_eq!(u64_at(at(json, "buffers", 0), &["byteLength"]) as usize, bin.len());
+
+    for accessor in json.find("accessors").unwrap().as_array().unwrap() {
+      let view = at(json, "bufferViews", u64_at(accessor, &["bufferView"]));
+
+      let offset = u64_at(view, &["byteOffset"]) as usize;
+      let length = u64_at(view, &["byteLength"]) as usize;
+      let accessor_offset = accessor.find("byteOffset").map_or(0, |x| x.as_u64().unwrap() as usize);
+
+      let component_size = match u64_at(accessor, &["componentType"]) { 5120 | 5121 => 1, 5122 | 5123 => 2, _ => 4 };
+      let elements = match accessor.find("type").unwrap().as_string().unwrap() {
+        "SCALAR" => 1, "VEC2" => 2, "VEC3" => 3, "VEC4" => 4, "MAT3" => 9, _ => 16
+      };
+
+      let element_size = component_size * elements;
+      let stride = view.find("byteStride").map_or(element_size, |x| x.as_u64().unwrap() as usize);
+
+      assert_eq!(offset % 4, 0);
+      assert_eq!(stride % 4, if view.find("byteStride").is_some() { 0 } else { stride % 4 });
+      assert!(stride >= element_size);
+      assert!(offset + length <= bin.len());
+      assert_eq!(accessor_offset % component_size, 0);
+      assert!(accessor_offset + (u64_at(accessor, &["count"]) as usize - 1) * stride + element_size <= length);
+    }
+  }
+
+  #[test]
+  fn test_export() {
+    let asset = asset::Asset { buffers: Vec::new(), objects: vec![model(builder().build(), Vec::new(), Vec::new())] };
+
+    let files = export(&asset);
+
+    // The buffer is written first, the JSON needs to know where everything in it went
+    assert_eq!(files[0].0, "hull.bin");
+    assert_eq!(files[1].0, "hull.gltf");
+
+    let json = Json::from_str(&String::from_utf8(files[1].1.clone()).unwrap()).unwrap();
+
+    check_buffer_views(&json, &files[0].1);
+
+    assert_eq!(json.find_path(&["asset", "version"]).unwrap().as_string(), Some("2.0"));
+    assert_eq!(at(&json, "buffers", 0).find("uri").unwrap().as_string(), Some("hull.bin"));
+
+    let positions = accessor(&json, u64_at(primitive(&json), &["attributes", "POSITION"]));
+    let indices = accessor(&json, u64_at(primitive(&json), &["indices"]));
+
+    // Z points the other way in glTF
+    assert_eq!(floats(positions, "min"), vec![0.0, 0.0, -2.0]);
+    assert_eq!(floats(positions, "max"), vec![1.0, 1.0, 0.0]);
+    assert_eq!(u64_at(indices, &["componentType"]), 5123);
+    assert_eq!(u64_at(indices, &["count"]), 6);
+    assert_eq!(u64_at(primitive(&json), &["mode"]), 4);
+
+    // The mirror is made while copying, swapping the last two corners of every triangle and
+    // flipping the sign of Z
+    let bin = &files[0].1;
+    let index_offset = u64_at(at(&json, "bufferViews", u64_at(indices, &["bufferView"])), &["byteOffset"]) as usize;
+    let position_offset = u64_at(at(&json, "bufferViews", u64_at(positions, &["bufferView"])), &["byteOffset"]) as usize;
+
+    assert_eq!(&bin[index_offset .. index_offset + 12], &[0, 0, 2, 0, 1, 0, 2, 0, 3, 0, 1, 0]);
+    assert_eq!(&bin[position_offset + 3 * 24 + 8 .. position_offset + 3 * 24 + 12], &[0, 0, 0, 192]);
+    assert_eq!(&bin[position_offset + 3 * 24 + 20 .. position_offset + 3 * 24 + 24], &[0, 0, 128, 191]);
+
+    // Positions and normals are interleaved in the mesh and stay that way
+    let normals = accessor(&json, u64_at(primitive(&json), &["attributes", "NORMAL"]));
+
+    assert_eq!(u64_at(normals, &["bufferView"]), u64_at(positions, &["bufferView"]));
+    assert_eq!(u64_at(normals, &["byteOffset"]), 12);
+    assert_eq!(u64_at(at(&json, "bufferViews", u64_at(normals, &["bufferView"])), &["byteStride"]), 24);
+  }
+
+  #[test]
+  fn test_normalized_attributes() {
+    let packed = vertex::Format(vertex::Scalar::u8_normalized, vertex::Width::Vector4);
+    let mesh = builder().attribute(vertex::AttributeName::Color, packed, &[1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]).build();
+
+    let (json, bin) = document(&asset::Asset { buffers: Vec::new(), objects: vec![model(mesh, Vec::new(), Vec::new())] }, None);
+
+    check_buffer_views(&json, &bin);
+
+    let colors = accessor(&json, u64_at(primitive(&json), &["attributes", "COLOR_0"]));
+
+    assert_eq!(u64_at(colors, &["componentType"]), 5121);
+    assert_eq!(colors.find("normalized").unwrap().as_boolean(), Some(true));
+    assert!(at(&json, "buffers", 0).find("uri").is_none());
+  }
+
+  #[test]
+  fn test_primitive_restart() {
+    let mesh = MeshBuilder::new("strip")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0]])
+      .submesh("strip", index::Geometry::TriangleStrips, &[0, 1, 2, index::RESTART_INDEX, 1, 2, 3])
+      .build();
+
+    let (json, _) = document(&asset::Asset { buffers: Vec::new(), objects: vec![model(mesh, Vec::new(), Vec::new())] }, None);
+
+    assert_eq!(u64_at(primitive(&json), &["mode"]), 4);
+    assert_eq!(u64_at(accessor(&json, u64_at(primitive(&json), &["indices"])), &["count"]), 6);
+  }
+
+  #[test]
+  fn test_skin_and_animation() {
+    let mesh = builder()
+      .joints(&[[0, 0, 0, 0], [1, 0, 0, 0], [0, 1, 0, 0], [1, 0, 0, 0]], &[[1.0, 0.0, 0.0, 0.0]; 4])
+      .bone_bindings(&["turret".to_owned(), "root".to_owned()])
+      .build();
+
+    let bones = vec![bone("root", None, [0.0, 0.0, 0.0]), bone("turret", Some(0), [0.0, 1.0, 0.0])];
+
+    let curve = |outputs: Vec<Vec<f32>>| sampler::Sampler {
+      pre_behavior: sampler::Behavior::Undefined,
+      post_behavior: sampler::Behavior::Undefined,
+      degree: 1,
+      input: vec![0.0, 1.0],
+      outputs: outputs
+    };
+
+    let track = |bone: &str| animation::TransformTrack {
+      group: "turret".to_owned(),
+      bone: bone.to_owned(),
+      orientation: Some(curve(vec![vec![0.0, 0.0], vec![0.0, 0.0], vec![0.0, 0.0]])),
+      position: Some(curve(vec![vec![0.0, 0.0], vec![1.0, 2.0], vec![0.0, 0.0]])),
+      scale_shear: None
+    };
+
+    let rotate = animation::Animation { name: "rotate".to_owned(), duration: 1.0, tracks: vec![track("turret"), track("missing")] };
+
+    let objects = vec![model(mesh.clone(), Vec::new(), bones), asset::Object::Animation(rotate)];
+    let (json, bin) = document(&asset::Asset { buffers: Vec::new(), objects: objects }, None);
+
+    check_buffer_views(&json, &bin);
+
+    // Bones come before the node of the mesh
+    let skin = at(&json, "skins", 0);
+    let joints: Vec<u64> = skin.find("joints").unwrap().as_array().unwrap().iter().map(|x| x.as_u64().unwrap()).collect();
+
+    assert_eq!(joints, vec![1, 0]);
+    assert_eq!(u64_at(at(&json, "nodes", 2), &["skin"]), 0);
+    assert_eq!(at(at(&json, "nodes", 0), "children", 0).as_u64(), Some(1));
+    assert!(primitive(&json).find_path(&["attributes", "JOINTS_0"]).is_some());
+
+    let inverse_bind_matrices = accessor(&json, u64_at(skin, &["inverseBindMatrices"]));
+
+    assert_eq!(inverse_bind_matrices.find("type").unwrap().as_string(), Some("MAT4"));
+    assert_eq!(&bin[52 .. 56], &[0, 0, 128, 191]); // Translation of the turret, -1.0
+
+    let animation = at(&json, "animations", 0);
+    let channel = at(animation, "channels", 0);
+
+    assert_eq!(animation.find("channels").unwrap().as_array().unwrap().len(), 1);
+    assert_eq!(u64_at(channel, &["target", "node"]), 1);
+    assert_eq!(channel.find_path(&["target", "path"]).unwrap().as_string(), Some("translation"));
+
+    let input = accessor(&json, u64_at(at(animation, "samplers", 0), &["input"]));
+
+    assert_eq!(floats(input, "max"), vec![1.0]);
+
+    // Without the bones in the skeleton there is no skin, and no joints either
+    let (json, _) = document(&asset::Asset { buffers: Vec::new(), objects: vec![model(mesh, Vec::new(), Vec::new())] }, None);
+
+    assert!(json.find("skins").is_none());
+    assert!(primitive(&json).find_path(&["attributes", "JOINTS_0"]).is_none());
+  }
+
+  #[test]
+  fn test_morph_targets() {
+    let points = builder().submesh("raise", index::Geometry::Points, &[3]).build();
+
+    // The builder keeps indices within its own vertices, blend shapes point into the base mesh
+    let raise = Mesh {
+      submeshes: vec![points.submeshes[1].clone()],
+      .. MeshBuilder::new("raise").positions(&[[1.0, 1.0, 3.0]]).normals(&[[0.0, 0.0, 1.0]]).build()
+    };
+
+    let (json, bin) = document(&asset::Asset { buffers: Vec::new(), objects: vec![model(builder().build(), vec![raise], Vec::new())] }, None);
+
+    check_buffer_views(&json, &bin);
+
+    let mesh = at(&json, "meshes", 0);
+    let target = at(primitive(&json), "targets", 0);
+
+    assert_eq!(floats(accessor(&json, u64_at(target, &["POSITION"])), "min"), vec![0.0, 0.0, -1.0]);
+    assert_eq!(floats(mesh, "weights"), vec![0.0]);
+    assert_eq!(at(mesh.find("extras").unwrap(), "targetNames", 0).as_string(), Some("raise"));
+  }
+}
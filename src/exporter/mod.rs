@@ -0,0 +1,205 @@
+pub mod collada;
+pub mod glb;
+pub mod gltf;
+pub mod obj;
+pub mod output;
+pub mod ply;
+pub mod stl;
+pub mod usd;
+
+use std::io;
+
+use std::collections::HashMap;
+
+use asset;
+use model;
+use vertex;
+
+pub use self::output::Output;
+
+pub trait Exporter : Send + Sync {
+  fn name(&self) -> &'static str;
+
+  // Of the files it writes, the first one being the main one
+  fn extensions(&self) -> Vec<&'static str>;
+
+  // Names and current values of the options
+  fn options(&self) -> Vec<(&'static str, String)> {
+    return Vec::new();
+  }
+
+  fn set_option(&mut self, name: &str, _: &str) -> Result<(), String> {
+    return Err(format!("{} has no option {}", self.name(), name));
+  }
+
+  fn export(&self, asset: &asset::Asset, output: &mut Output) -> io::Result<()>;
+}
+
+pub struct Exporters {
+  exporters: HashMap<&'static str, Box<Exporter>>
+}
+
+impl Exporters {
+  pub fn new() -> Exporters {
+    return Exporters {
+      exporters: HashMap::new()
+    };
+  }
+
+  pub fn default() -> Exporters {
+    let mut exporters = Exporters::new();
+
+    exporters.insert(Box::new(collada::Exporter::default()));
+    exporters.insert(Box::new(glb::Exporter::default()));
+    exporters.insert(Box::new(gltf::Exporter::default()));
+    exporters.insert(Box::new(obj::Exporter::default()));
+    exporters.insert(Box::new(ply::Exporter::default()));
+    exporters.insert(Box::new(stl::Exporter::default()));
+    exporters.insert(Box::new(usd::Exporter::default()));
+
+    return exporters;
+  }
+
+  // Replaces any exporter that has the same name
+  pub fn insert(&mut self, exporter: Box<Exporter>) {
+    self.exporters.insert(exporter.name(), exporter);
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Exporter> {
+    return self.exporters.get(name).map(|x| &**x);
+  }
+
+  pub fn get_mut(&mut self, name: &str) -> Option<&mut (Exporter + 'static)> {
+    return self.exporters.get_mut(name).map(|x| &mut **x);
+  }
+
+  pub fn for_extension(&self, extension: &str) -> Option<&Exporter> {
+    let extension = extension.to_lowercase();
+
+    return self.exporters.values().find(|x| x.extensions().first() == Some(&extension.as_str())).map(|x| &**x);
+  }
+
+  pub fn names(&self) -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = self.exporters.keys().cloned().collect();
+
+    names.sort();
+
+    return names;
+  }
+}
+
+// Appends a number to names that were already used, for exporters writing a file per model
+pub fn unique_name(name: &str, names: &mut Vec<String>) -> String {
+  let mut unique = name.to_owned();
+  let mut n = 1;
+
+  while names.contains(&unique) {
+    n += 1;
+    unique = format!("{}_{}", name, n);
+  }
+
+  names.push(unique.clone());
+
+  return unique;
+}
+
+// For callers that want the files rather than a stream of them
+fn in_memory<F>(export: F) -> Vec<(String, Vec<u8>)> where F: FnOnce(&mut Output) -> io::Result<()> {
+  let mut output = output::Memory::new();
+
+  export(&mut output).unwrap(); // Writing to memory does not fail
+
+  return output.files;
+}
+
+// The bones the joint indices refer to, when every one of them is in the skeleton
+fn joints(model: &model::Model) -> Option<Vec<usize>> {
+  let mesh = &model.mesh;
+
+  if mesh.attribute_for(&vertex::AttributeName::JointIndices).is_none() || mesh.bone_bindings.is_empty() {
+    return None;
+  }
+
+  return mesh.bone_bindings.iter().map(|name| model.skeleton.bones.iter().position(|x| x.name == *name)).collect();
+}
+
+// Adding zero turns -0 into 0
+fn number(x: f32) -> f32 {
+  return x + 0.0;
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+  return match value {
+    "true" | "yes" | "1" => Ok(true),
+    "false" | "no" | "0" => Ok(false),
+    _ => Err(format!("{} must be true or false, not {}", name, value))
+  };
+}
+
+// Models the exporters are tested with
+#[cfg(test)]
+mod fixtures {
+  use asset;
+  use model;
+
+  use animation::skeleton;
+  use mesh::Mesh;
+
+  pub fn bone(name: &str, parent_index: Option<usize>, position: [f32; 3]) -> skeleton::Bone {
+    return skeleton::Bone {
+      name: name.to_owned(),
+      parent_index: parent_index,
+      position: position,
+      orientation: [0.0, 0.0, 0.0, 1.0],
+      scale_shear: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]
+    };
+  }
+
+  pub fn model(mesh: Mesh, blend_shapes: Vec<Mesh>, bones: Vec<skeleton::Bone>) -> asset::Object {
+    return asset::Object::Model(model::Model {
+      name: "hull".to_owned(),
+      mesh: mesh,
+      blend_shapes: blend_shapes,
+      lods: Vec::new(),
+      skeleton: skeleton::Skeleton { bones: bones }
+    });
+  }
+
+  // An asset with nothing but that model
+  pub fn asset(mesh: Mesh, blend_shapes: Vec<Mesh>, bones: Vec<skeleton::Bone>) -> asset::Asset {
+    return asset::Asset { buffers: Vec::new(), objects: vec![model(mesh, blend_shapes, bones)] };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use asset;
+
+  use exporter::output::Memory;
+
+  #[test]
+  fn test_registry() {
+    let mut exporters = Exporters::default();
+
+    assert_eq!(exporters.names(), vec!["collada", "glb", "gltf", "obj", "ply", "stl", "usd"]);
+    assert_eq!(exporters.for_extension("DAE").map(|x| x.name()), Some("collada"));
+    assert!(exporters.for_extension("bin").is_none());
+
+    let obj = exporters.get_mut("obj").unwrap();
+
+    assert!(obj.set_option("single_file", "yes").is_ok());
+    assert!(obj.set_option("single_file", "maybe").is_err());
+    assert!(obj.set_option("colour", "red").is_err());
+    assert_eq!(obj.options(), vec![("single_file", "true".to_owned())]);
+
+    let mut output = Memory::new();
+
+    obj.export(&asset::Asset { buffers: Vec::new(), objects: Vec::new() }, &mut output).unwrap();
+
+    let names: Vec<&str> = output.files.iter().map(|x| x.0.as_str()).collect();
+
+    assert_eq!(names, vec!["asset.obj", "asset.mtl"]);
+  }
+}
@@ -1,78 +1,310 @@
+use std::io;
+
 use mesh;
 use asset;
 use model;
 
 use vertex;
 
+use exporter;
+
+use exporter::{Output, unique_name};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Options {
+  pub single_file: bool // Every model in one .obj instead of one .obj per model
+}
+
+impl Options {
+  pub fn default() -> Options {
+    return Options { single_file: false };
+  }
+}
+
+pub struct Exporter {
+  pub options: Options
+}
+
+impl Exporter {
+  pub fn default() -> Exporter {
+    return Exporter { options: Options::default() };
+  }
+}
+
+impl exporter::Exporter for Exporter {
+  fn name(&self) -> &'static str {
+    return "obj";
+  }
+
+  fn extensions(&self) -> Vec<&'static str> {
+    return vec!["obj", "mtl"];
+  }
+
+  fn options(&self) -> Vec<(&'static str, String)> {
+    return vec![("single_file", format!("{}", self.options.single_file))];
+  }
+
+  fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+    match name {
+      "single_file" => self.options.single_file = try!(exporter::parse_bool(name, value)),
+      _ => return Err(format!("obj has no option {}", name))
+    }
+
+    return Ok(());
+  }
+
+  fn export(&self, asset: &asset::Asset, output: &mut Output) -> io::Result<()> {
+    return export_to(asset, &self.options, output);
+  }
+}
+
 pub fn export(asset: &asset::Asset) -> Vec<(String, Vec<u8>)> {
-  let mut files = Vec::new();
+  return export_with_options(asset, &Options::default());
+}
+
+pub fn export_with_options(asset: &asset::Asset, options: &Options) -> Vec<(String, Vec<u8>)> {
+  return exporter::in_memory(|output| export_to(asset, options, output));
+}
+
+// Every .obj comes with a .mtl that has a material for each of its areas. Files are named after
+// the model they hold, or after the first one when they hold them all
+pub fn export_to(asset: &asset::Asset, options: &Options, output: &mut Output) -> io::Result<()> {
+  let mut models = Vec::new();
 
   for object in &asset.objects {
     match object {
-      &asset::Object::Model(ref m) => files.push(export_model(m))
+      &asset::Object::Model(ref m) => models.push(m),
+      _ => ()
     }
   }
 
-  return files;
+  if options.single_file {
+    return write_models(&asset.name(), &models, output);
+  }
+
+  let mut names = Vec::new();
+
+  for model in models {
+    let name = unique_name(&identifier(&model.name), &mut names);
+
+    try!(write_models(&name, &[model], output));
+  }
+
+  return Ok(());
+}
+
+pub fn export_model(model: &model::Model) -> Vec<(String, Vec<u8>)> {
+  return exporter::in_memory(|output| write_models(&model.name, &[model], output));
 }
 
-pub fn export_model(model: &model::Model) -> (String, Vec<u8>) {
-  let mut result = String::new();
+// The file name is in the mtllib statement, so it is an identifier as well
+fn write_models(name: &str, models: &[&model::Model], output: &mut Output) -> io::Result<()> {
+  let name = identifier(name);
+  let mut materials = Vec::new();
+
+  try!(output.start_file(&format!("{}.obj", name)));
+  try!(write!(output, "mtllib {}.mtl\n", name));
+
+  // Each attribute is numbered across the whole file
+  let mut offsets = [1, 1, 1];
+
+  for model in models {
+    let mesh = &model.mesh;
+
+    try!(write!(output, "o {}\n", identifier(&model.name)));
+
+    if !try!(write_attribute("v", vertex::AttributeName::Position, 4, mesh, output)) {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no positions to export to .obj", model.name)));
+    }
+
+    let textures_written = try!(write_attribute("vt", vertex::AttributeName::TextureCoordinate, 3, mesh, output));
+    let normals_written = try!(write_attribute("vn", vertex::AttributeName::Normal, 3, mesh, output));
 
-  let mesh = &model.mesh;
+    let parts = [Some(offsets[0]), if textures_written { Some(offsets[1]) } else { None }, if normals_written { Some(offsets[2]) } else { None }];
 
-  if !write("v", vertex::AttributeName::Position, 4, mesh, &mut result) {
-    panic!("No positions when exporting to .obj");
+    for submesh in &mesh.submeshes {
+      let material = identifier(&submesh.name);
+
+      try!(write_faces(&material, parts, &submesh, output));
+
+      if !materials.contains(&material) {
+        materials.push(material);
+      }
+    }
+
+    offsets[0] += mesh.vertex_count;
+
+    if textures_written { offsets[1] += mesh.vertex_count };
+    if normals_written { offsets[2] += mesh.vertex_count };
   }
 
-  let textures_written = write("vt", vertex::AttributeName::TextureCoordinate, 3, mesh, &mut result);
-  let normals_written = write("vn", vertex::AttributeName::Normal, 3, mesh, &mut result);
+  try!(output.start_file(&format!("{}.mtl", name)));
+
+  return write_materials(&materials, output);
+}
 
-  for submesh in &mesh.submeshes {
-    write_faces(textures_written, normals_written, &submesh, &mut result);
+// There is nothing to go on but the name of the area, so every material is a plain grey
+fn write_materials(materials: &[String], output: &mut Output) -> io::Result<()> {
+  for material in materials {
+    try!(write!(output, "newmtl {}\n", material));
+    try!(output.write_all(b"Ka 0 0 0\nKd 0.8 0.8 0.8\nKs 0 0 0\nd 1\nillum 1\n\n"));
   }
 
-  return (mesh.name.clone() + ".obj", result.into_bytes());
+  return Ok(());
 }
 
-fn write(prefix: &str, name: vertex::AttributeName, max_elements: usize, mesh: &mesh::Mesh, result: &mut String) -> bool {
+fn write_attribute(prefix: &str, name: vertex::AttributeName, max_elements: usize, mesh: &mesh::Mesh, output: &mut Output) -> io::Result<bool> {
   if let Some(view) = mesh.untyped_view_for(&name) {
+    let elements = view.elements().min(max_elements);
+
     for i in 0 .. view.len() {
-      let value: Vec<String> = view.get_f32(i).iter().take(max_elements).map(|x| format!("{}", x)).collect();
+      try!(output.write_all(prefix.as_bytes()));
+
+      for c in 0 .. elements {
+        try!(write!(output, " {}", view.get_element_f32(i, c)));
+      }
 
-      result.push_str(format!("{} {}\n", prefix, value.join(" ")).as_str());
+      try!(output.write_all(b"\n"));
     }
 
-    return true;
+    return Ok(true);
   }
 
-  return false;
+  return Ok(false);
 }
 
-fn write_faces(textures_written: bool, normals_written: bool, submesh: &mesh::Submesh, result: &mut String) {
-  result.push_str(format!("o {}\n", submesh.name).as_str());
+// `offsets` holds the number of the first vertex of the model for positions, texture coordinates
+// and normals, for each one that was written
+fn write_faces(material: &str, offsets: [Option<usize>; 3], submesh: &mesh::Submesh, output: &mut Output) -> io::Result<()> {
+  try!(write!(output, "g {}\n", material));
+  try!(write!(output, "usemtl {}\n", material));
 
-  for face in submesh.faces() {
-    result.push_str("  f");
+  let mut faces = submesh.faces();
 
-    for vertex in face {
-      write_face_part(textures_written, normals_written, vertex + 1, result);
+  while let Some((width, face)) = faces.next_indices() {
+    try!(output.write_all(b"  f"));
+
+    for vertex in &face[.. width] {
+      try!(write_face_part(offsets, *vertex, output));
     }
 
-    result.push_str("\n")
+    try!(output.write_all(b"\n"));
   }
+
+  return Ok(());
 }
 
-fn write_face_part(textures_written: bool, normals_written: bool, value: usize, result: &mut String) {
-  result.push_str(format!(" {}", value).as_str());
+fn write_face_part(offsets: [Option<usize>; 3], vertex: usize, output: &mut Output) -> io::Result<()> {
+  try!(write!(output, " {}", vertex + offsets[0].unwrap()));
 
-  if textures_written {
-    result.push_str(format!("/{}", value).as_str());
+  if let Some(offset) = offsets[1] {
+    try!(write!(output, "/{}", vertex + offset));
   }
 
-  if normals_written {
-    if !textures_written { result.push_str("/") };
+  if let Some(offset) = offsets[2] {
+    if offsets[1].is_none() { try!(output.write_all(b"/")) };
+
+    try!(write!(output, "/{}", vertex + offset));
+  }
+
+  return Ok(());
+}
+
+// Names in .obj and .mtl statements end at the first whitespace
+fn identifier(name: &str) -> String {
+  let name: String = name.chars().map(|x| if x.is_whitespace() { '_' } else { x }).collect();
+
+  return if name.is_empty() { "default".to_owned() } else { name };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use asset;
+  use index;
+  use model;
+
+  use animation::skeleton;
+  use exporter::output::Memory;
+  use mesh::MeshBuilder;
+
+  fn model(name: &str, x: f32) -> asset::Object {
+    let mesh = MeshBuilder::new("hull")
+      .positions(&[[x, 0.0, 0.0], [x + 1.0, 0.0, 0.0], [x, 1.0, 0.0], [x + 1.0, 1.0, 0.0]])
+      .normals(&[[0.0, 0.0, 1.0]; 4])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2])
+      .submesh("glass window", index::Geometry::Triangles, &[2, 1, 3])
+      .build();
+
+    return asset::Object::Model(model::Model {
+      name: name.to_owned(),
+      mesh: mesh,
+      blend_shapes: Vec::new(),
+      lods: Vec::new(),
+      skeleton: skeleton::Skeleton { bones: Vec::new() }
+    });
+  }
+
+  fn text(file: &(String, Vec<u8>)) -> String {
+    return String::from_utf8(file.1.clone()).unwrap();
+  }
+
+  #[test]
+  fn test_export() {
+    let asset = asset::Asset { buffers: Vec::new(), objects: vec![model("frigate", 0.0), model("cruiser", 5.0)] };
+
+    let files = export(&asset);
+    let names: Vec<&str> = files.iter().map(|x| x.0.as_str()).collect();
+
+    assert_eq!(names, vec!["frigate.obj", "frigate.mtl", "cruiser.obj", "cruiser.mtl"]);
+
+    let obj = text(&files[0]);
+
+    assert!(obj.starts_with("mtllib frigate.mtl\no frigate\n"));
+    assert!(obj.contains("g glass_window\nusemtl glass_window\n  f 3//3 2//2 4//4\n"));
+    assert_eq!(text(&files[1]).matches("newmtl").count(), 2);
+
+    let asset = asset::Asset { buffers: Vec::new(), objects: vec![model("frigate", 0.0), model("frigate", 5.0)] };
+    let names: Vec<String> = export(&asset).into_iter().map(|x| x.0).collect();
+
+    assert_eq!(names, vec!["frigate.obj", "frigate.mtl", "frigate_2.obj", "frigate_2.mtl"]);
+
+    let asset = asset::Asset { buffers: Vec::new(), objects: vec![model("heavy frigate", 0.0), model("heavy_frigate", 5.0)] };
+    let files = export(&asset);
+
+    assert_eq!(files[0].0, "heavy_frigate.obj");
+    assert_eq!(files[2].0, "heavy_frigate_2.obj");
+    assert!(text(&files[0]).starts_with("mtllib heavy_frigate.mtl
+"));
+  }
+
+  #[test]
+  fn test_export_without_positions() {
+    let mesh = MeshBuilder::new("hull").normals(&[[0.0, 0.0, 1.0]; 3]).submesh("hull", index::Geometry::Triangles, &[0, 1, 2]).build();
+    let model = model::Model { name: "frigate".to_owned(), mesh: mesh, blend_shapes: Vec::new(), lods: Vec::new(), skeleton: skeleton::Skeleton { bones: Vec::new() } };
+
+    let asset = asset::Asset { buffers: Vec::new(), objects: vec![asset::Object::Model(model)] };
+
+    let mut output = Memory::new();
+
+    assert_eq!(export_to(&asset, &Options::default(), &mut output).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+  }
+
+  #[test]
+  fn test_export_single_file() {
+    let asset = asset::Asset { buffers: Vec::new(), objects: vec![model("frigate", 0.0), model("cruiser", 5.0)] };
+
+    let files = export_with_options(&asset, &Options { single_file: true });
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].0, "frigate.obj");
+
+    let obj = text(&files[0]);
 
-    result.push_str(format!("/{}", value).as_str());
+    // Vertices of the second model are numbered after the ones of the first
+    assert!(obj.contains("o cruiser\n"));
+    assert!(obj.ends_with("usemtl glass_window\n  f 7//7 6//6 8//8\n"));
+    assert_eq!(text(&files[1]).matches("newmtl").count(), 2);
   }
 }
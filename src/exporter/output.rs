@@ -0,0 +1,403 @@
+use std::fs;
+use std::io;
+use std::mem;
+
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+// Where exporters write their files, one after the other. Whatever is written goes to the file
+// started last, and starting a file ends the one before it. The caller finishes the output once
+// every export is done, so several assets can go in one
+pub trait Output : Write {
+  fn start_file(&mut self, name: &str) -> io::Result<()>;
+
+  fn finish(&mut self) -> io::Result<()>;
+}
+
+fn no_file() -> io::Error {
+  return io::Error::new(io::ErrorKind::Other, "No file was started in the output");
+}
+
+// Files go in a directory, which is created when needed
+pub struct Directory {
+  pub path: PathBuf,
+  file: Option<BufWriter<fs::File>>
+}
+
+impl Directory {
+  pub fn new<P: Into<PathBuf>>(path: P) -> Directory {
+    return Directory { path: path.into(), file: None };
+  }
+}
+
+impl Write for Directory {
+  fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+    return match self.file {
+      Some(ref mut file) => file.write(data), None => Err(no_file())
+    };
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    return match self.file {
+      Some(ref mut file) => file.flush(), None => Ok(())
+    };
+  }
+}
+
+impl Output for Directory {
+  fn start_file(&mut self, name: &str) -> io::Result<()> {
+    try!(self.finish());
+    try!(fs::create_dir_all(&self.path));
+
+    self.file = Some(BufWriter::new(try!(fs::File::create(self.path.join(name)))));
+
+    return Ok(());
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    if let Some(mut file) = self.file.take() {
+      try!(file.flush());
+    }
+
+    return Ok(());
+  }
+}
+
+pub struct Memory {
+  pub files: Vec<(String, Vec<u8>)>
+}
+
+impl Memory {
+  pub fn new() -> Memory {
+    return Memory { files: Vec::new() };
+  }
+}
+
+impl Write for Memory {
+  fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+    return match self.files.last_mut() {
+      Some(file) => file.1.write(data), None => Err(no_file())
+    };
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    return Ok(());
+  }
+}
+
+impl Output for Memory {
+  fn start_file(&mut self, name: &str) -> io::Result<()> {
+    self.files.push((name.to_owned(), Vec::new()));
+
+    return Ok(());
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    return Ok(());
+  }
+}
+
+struct Entry {
+  name: String,
+  offset: u32,
+  crc: u32,
+  size: u32
+}
+
+// MS-DOS date of 1980-01-01, exports are meant to be reproducible
+const DATE: u16 = 0x21;
+
+// 11 for names in UTF-8
+const FLAGS: u16 = 1 << 11;
+
+// A zip archive of uncompressed files, written as it goes along. Readers that stream stored files
+// need their sizes up front, so the checksum and sizes are put in the local header once the data
+// is written. Without zip64, archives end at 4 GiB and 65535 files
+pub struct Zip<W: Write + Seek> {
+  writer: BufWriter<W>,
+  crc_table: [u32; 256],
+  offset: u64,
+  entries: Vec<Entry>,
+  current: Option<Entry>,
+  finished: bool
+}
+
+impl<W: Write + Seek> Zip<W> {
+  pub fn new(writer: W) -> Zip<W> {
+    let mut crc_table = [0; 256];
+
+    for i in 0 .. 256 {
+      let mut c = i as u32;
+
+      for _ in 0 .. 8 {
+        c = if c & 1 == 1 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+      }
+
+      crc_table[i] = c;
+    }
+
+    return Zip {
+      writer: BufWriter::new(writer),
+      crc_table: crc_table,
+      offset: 0,
+      entries: Vec::new(),
+      current: None,
+      finished: false
+    };
+  }
+
+  // The underlying writer, once the archive is finished
+  pub fn into_inner(mut self) -> io::Result<W> {
+    try!(self.finish());
+
+    return self.writer.into_inner().map_err(|e| e.into());
+  }
+
+  fn offset(&self) -> io::Result<u32> {
+    if self.offset > u32::max_value() as u64 {
+      return Err(io::Error::new(io::ErrorKind::Other, "Zip archive is larger than 4 GiB"));
+    }
+
+    return Ok(self.offset as u32);
+  }
+
+  fn put(&mut self, data: &[u8]) -> io::Result<()> {
+    try!(self.writer.write_all(data));
+
+    self.offset += data.len() as u64;
+
+    return Ok(());
+  }
+
+  // Signature, version needed to extract, flags, method, time and date, shared by both headers
+  fn put_header_start(&mut self, signature: u32) -> io::Result<()> {
+    let mut header = Vec::with_capacity(14);
+
+    try!(header.write_u32::<LittleEndian>(signature));
+
+    if signature == 0x02014B50 {
+      try!(header.write_u16::<LittleEndian>(20)); // Version made by
+    }
+
+    try!(header.write_u16::<LittleEndian>(20));
+    try!(header.write_u16::<LittleEndian>(FLAGS));
+    try!(header.write_u16::<LittleEndian>(0)); // Stored
+    try!(header.write_u16::<LittleEndian>(0));
+    try!(header.write_u16::<LittleEndian>(DATE));
+
+    return self.put(&header);
+  }
+
+  fn end_file(&mut self) -> io::Result<()> {
+    let mut entry = match self.current.take() {
+      Some(entry) => entry, None => return Ok(())
+    };
+
+    entry.crc = !entry.crc;
+
+    let mut fields = Vec::with_capacity(12);
+
+    try!(fields.write_u32::<LittleEndian>(entry.crc));
+    try!(fields.write_u32::<LittleEndian>(entry.size));
+    try!(fields.write_u32::<LittleEndian>(entry.size));
+
+    // They are 14 bytes into the local header
+    let back = (self.offset - (entry.offset as u64 + 14)) as i64;
+
+    try!(self.writer.seek(SeekFrom::Current(-back)));
+    try!(self.writer.write_all(&fields));
+    try!(self.writer.seek(SeekFrom::Current(back - 12)));
+
+    self.entries.push(entry);
+
+    return Ok(());
+  }
+}
+
+impl<W: Write + Seek> Write for Zip<W> {
+  fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+    if self.current.is_none() {
+      return Err(no_file());
+    }
+
+    try!(self.put(data));
+
+    let table = &self.crc_table;
+    let entry = self.current.as_mut().unwrap();
+
+    for b in data {
+      entry.crc = table[((entry.crc ^ *b as u32) & 0xFF) as usize] ^ (entry.crc >> 8);
+    }
+
+    if entry.size as u64 + data.len() as u64 > u32::max_value() as u64 {
+      return Err(io::Error::new(io::ErrorKind::Other, format!("{} is larger than 4 GiB", entry.name)));
+    }
+
+    entry.size += data.len() as u32;
+
+    return Ok(data.len());
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    return self.writer.flush();
+  }
+}
+
+impl<W: Write + Seek> Output for Zip<W> {
+  fn start_file(&mut self, name: &str) -> io::Result<()> {
+    if self.finished {
+      return Err(io::Error::new(io::ErrorKind::Other, "Zip archive is already finished"));
+    }
+
+    try!(self.end_file());
+
+    if name.len() > u16::max_value() as usize {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "Zip file name is longer than 65535 bytes"));
+    }
+
+    if self.entries.len() >= u16::max_value() as usize {
+      return Err(io::Error::new(io::ErrorKind::Other, "Zip archive has more than 65535 files"));
+    }
+
+    let offset = try!(self.offset());
+
+    try!(self.put_header_start(0x04034B50));
+
+    let mut header = Vec::with_capacity(16);
+
+    try!(header.write_u32::<LittleEndian>(0)); // Checksum and sizes are filled in after the data
+    try!(header.write_u32::<LittleEndian>(0));
+    try!(header.write_u32::<LittleEndian>(0));
+    try!(header.write_u16::<LittleEndian>(name.len() as u16));
+    try!(header.write_u16::<LittleEndian>(0));
+    try!(self.put(&header));
+    try!(self.put(name.as_bytes()));
+
+    self.current = Some(Entry { name: name.to_owned(), offset: offset, crc: !0, size: 0 });
+
+    return Ok(());
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    if self.finished {
+      return Ok(());
+    }
+
+    try!(self.end_file());
+
+    let start = try!(self.offset());
+    let entries = mem::replace(&mut self.entries, Vec::new());
+
+    for entry in &entries {
+      try!(self.put_header_start(0x02014B50));
+
+      let mut header = Vec::with_capacity(30);
+
+      try!(header.write_u32::<LittleEndian>(entry.crc));
+      try!(header.write_u32::<LittleEndian>(entry.size));
+      try!(header.write_u32::<LittleEndian>(entry.size));
+      try!(header.write_u16::<LittleEndian>(entry.name.len() as u16));
+      try!(header.write_u16::<LittleEndian>(0)); // Extra field
+      try!(header.write_u16::<LittleEndian>(0)); // Comment
+      try!(header.write_u16::<LittleEndian>(0)); // Disk
+      try!(header.write_u16::<LittleEndian>(0)); // Internal attributes
+      try!(header.write_u32::<LittleEndian>(0)); // External attributes
+      try!(header.write_u32::<LittleEndian>(entry.offset));
+      try!(self.put(&header));
+      try!(self.put(entry.name.as_bytes()));
+    }
+
+    let end = try!(self.offset());
+
+    let mut footer = Vec::with_capacity(22);
+
+    try!(footer.write_u32::<LittleEndian>(0x06054B50));
+    try!(footer.write_u16::<LittleEndian>(0));
+    try!(footer.write_u16::<LittleEndian>(0));
+    try!(footer.write_u16::<LittleEndian>(entries.len() as u16));
+    try!(footer.write_u16::<LittleEndian>(entries.len() as u16));
+    try!(footer.write_u32::<LittleEndian>(end - start));
+    try!(footer.write_u32::<LittleEndian>(start));
+    try!(footer.write_u16::<LittleEndian>(0));
+    try!(self.put(&footer));
+
+    self.entries = entries;
+    self.finished = true;
+
+    return self.writer.flush();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::io::{Cursor, Write};
+
+  use byteorder::{LittleEndian, ReadBytesExt};
+
+  #[test]
+  fn test_memory() {
+    let mut output = Memory::new();
+
+    assert!(output.write_all(b"lost").is_err());
+
+    output.start_file("hull.obj").unwrap();
+    write!(output, "v {} {} {}\n", 1, 2, 3).unwrap();
+    output.start_file("hull.mtl").unwrap();
+    output.finish().unwrap();
+
+    assert_eq!(output.files, vec![("hull.obj".to_owned(), b"v 1 2 3\n".to_vec()), ("hull.mtl".to_owned(), Vec::new())]);
+  }
+
+  #[test]
+  fn test_zip() {
+    let mut zip = Zip::new(Cursor::new(Vec::new()));
+
+    zip.start_file("a.txt").unwrap();
+    zip.write_all(b"123456789").unwrap();
+    zip.start_file("b.txt").unwrap();
+
+    let data = zip.into_inner().unwrap().into_inner();
+
+    // Local header, name and data of a.txt, then of the empty b.txt
+    let a = 30 + 5 + 9;
+    let b = 30 + 5;
+
+    assert_eq!(&data[0 .. 4], &[0x50, 0x4B, 0x03, 0x04]);
+    assert_eq!((&data[6 .. 8]).read_u16::<LittleEndian>().unwrap(), 1 << 11);
+    assert_eq!((&data[14 .. 18]).read_u32::<LittleEndian>().unwrap(), 0xCBF43926); // CRC-32 of "123456789"
+    assert_eq!((&data[18 .. 22]).read_u32::<LittleEndian>().unwrap(), 9);
+    assert_eq!((&data[22 .. 26]).read_u32::<LittleEndian>().unwrap(), 9);
+    assert_eq!(&data[35 .. 44], b"123456789");
+    assert_eq!((&data[a .. a + 4]).read_u32::<LittleEndian>().unwrap(), 0x04034B50);
+    assert_eq!((&data[a + 14 .. a + 18]).read_u32::<LittleEndian>().unwrap(), 0); // CRC-32 of nothing
+    assert_eq!(data.len(), a + b + 2 * (46 + 5) + 22);
+
+    // The end of the central directory holds the number of entries, its size and its offset
+    let end = &data[data.len() - 22 ..];
+
+    assert_eq!((&end[10 .. 12]).read_u16::<LittleEndian>().unwrap(), 2);
+    assert_eq!((&end[12 .. 16]).read_u32::<LittleEndian>().unwrap(), 2 * (46 + 5));
+    assert_eq!((&end[16 .. 20]).read_u32::<LittleEndian>().unwrap(), (a + b) as u32);
+  }
+
+  #[test]
+  fn test_zip_limits() {
+    let mut zip = Zip::new(Cursor::new(Vec::new()));
+
+    assert!(zip.start_file(&"a".repeat(65536)).is_err());
+
+    for i in 0 .. 65535 {
+      zip.start_file(&format!("{}", i)).unwrap();
+    }
+
+    assert!(zip.start_file("65535").is_err());
+
+    let data = zip.into_inner().unwrap().into_inner();
+
+    assert_eq!((&data[data.len() - 12 .. data.len() - 10]).read_u16::<LittleEndian>().unwrap(), 65535);
+  }
+}
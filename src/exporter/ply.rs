@@ -0,0 +1,317 @@
+use std::io;
+
+use std::io::Write;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use asset;
+use mesh;
+use vertex;
+
+use exporter;
+
+use buffer::ScalarValue;
+use exporter::{Output, unique_name};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+  Ascii,
+  BinaryLittleEndian
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Options {
+  pub encoding: Encoding
+}
+
+impl Options {
+  pub fn default() -> Options {
+    return Options { encoding: Encoding::BinaryLittleEndian };
+  }
+}
+
+pub struct Exporter {
+  pub options: Options
+}
+
+impl Exporter {
+  pub fn default() -> Exporter {
+    return Exporter { options: Options::default() };
+  }
+}
+
+impl exporter::Exporter for Exporter {
+  fn name(&self) -> &'static str {
+    return "ply";
+  }
+
+  fn extensions(&self) -> Vec<&'static str> {
+    return vec!["ply"];
+  }
+
+  fn options(&self) -> Vec<(&'static str, String)> {
+    let encoding = match self.options.encoding { Encoding::Ascii => "ascii", Encoding::BinaryLittleEndian => "binary" };
+
+    return vec![("encoding", encoding.to_owned())];
+  }
+
+  fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+    match (name, value) {
+      ("encoding", "ascii") => self.options.encoding = Encoding::Ascii,
+      ("encoding", "binary") => self.options.encoding = Encoding::BinaryLittleEndian,
+      ("encoding", _) => return Err(format!("encoding must be ascii or binary, not {}", value)),
+      _ => return Err(format!("ply has no option {}", name))
+    }
+
+    return Ok(());
+  }
+
+  fn export(&self, asset: &asset::Asset, output: &mut Output) -> io::Result<()> {
+    return export_to(asset, &self.options, output);
+  }
+}
+
+pub fn export(asset: &asset::Asset) -> Vec<(String, Vec<u8>)> {
+  return export_with_options(asset, &Options::default());
+}
+
+pub fn export_with_options(asset: &asset::Asset, options: &Options) -> Vec<(String, Vec<u8>)> {
+  return exporter::in_memory(|output| export_to(asset, options, output));
+}
+
+pub fn export_to(asset: &asset::Asset, options: &Options, output: &mut Output) -> io::Result<()> {
+  let mut names = Vec::new();
+
+  for object in &asset.objects {
+    match object {
+      &asset::Object::Model(ref m) => {
+        let name = unique_name(&m.mesh.name, &mut names);
+
+        try!(output.start_file(&format!("{}.ply", name)));
+        try!(write_mesh(&m.mesh, options, output));
+      }
+      _ => ()
+    }
+  }
+
+  return Ok(());
+}
+
+pub fn export_mesh(mesh: &mesh::Mesh, options: &Options) -> Vec<u8> {
+  let mut result = Vec::new();
+
+  write_mesh(mesh, options, &mut result).unwrap();
+
+  return result;
+}
+
+// Every attribute is written with the type it is stored as, normalized integers keep their
+// integer values. The faces of triangle submeshes carry the index of their area, whose names are
+// listed in the comments
+pub fn write_mesh<W: Write + ?Sized>(mesh: &mesh::Mesh, options: &Options, w: &mut W) -> io::Result<()> {
+  let attributes = &mesh.descriptor.attributes;
+  let face_count: usize = mesh.submeshes.iter().filter(|x| x.geometry.is_triangles()).map(|x| x.faces().len()).sum();
+
+  try!(w.write_all(b"ply\n"));
+  try!(w.write_all(match options.encoding {
+    Encoding::Ascii => b"format ascii 1.0\n",
+    Encoding::BinaryLittleEndian => b"format binary_little_endian 1.0\n"
+  }));
+
+  for (i, submesh) in mesh.submeshes.iter().enumerate() {
+    try!(write!(w, "comment area {} {}\n", i, submesh.name));
+  }
+
+  try!(write!(w, "element vertex {}\n", mesh.vertex_count));
+
+  for (i, attribute) in attributes.iter().enumerate() {
+    let set = attributes[.. i].iter().filter(|x| x.name == attribute.name).count();
+    let scalar = property_type(attribute.format.scalar());
+
+    for name in property_names(&attribute.name, set, attribute.format.elements()) {
+      try!(write!(w, "property {} {}\n", scalar, name));
+    }
+  }
+
+  try!(write!(w, "element face {}\n", face_count));
+  try!(w.write_all(b"property list uchar uint vertex_indices\nproperty ushort area\nend_header\n"));
+
+  let views: Vec<_> = attributes.iter().map(|x| mesh.untyped_view_for_attribute(x)).collect();
+
+  for v in 0 .. mesh.vertex_count {
+    let mut first = true;
+
+    for view in &views {
+      for c in 0 .. view.elements() {
+        try!(write_value(options.encoding, view.get_element_value(v, c), first, w));
+        first = false;
+      }
+    }
+
+    try!(end_row(options.encoding, w));
+  }
+
+  for (area, submesh) in mesh.submeshes.iter().enumerate().filter(|x| x.1.geometry.is_triangles()) {
+    let mut faces = submesh.faces();
+
+    while let Some((width, face)) = faces.next_indices() {
+      try!(write_value(options.encoding, ScalarValue::u8(width as u8), true, w));
+
+      for index in &face[.. width] {
+        try!(write_value(options.encoding, ScalarValue::u32(*index as u32), false, w));
+      }
+
+      try!(write_value(options.encoding, ScalarValue::u16(area as u16), false, w));
+      try!(end_row(options.encoding, w));
+    }
+  }
+
+  return Ok(());
+}
+
+// Values in ASCII rows are separated by spaces, binary ones follow each other
+fn write_value<W: Write + ?Sized>(encoding: Encoding, value: ScalarValue, first: bool, w: &mut W) -> io::Result<()> {
+  return match encoding {
+    Encoding::Ascii => {
+      if !first {
+        try!(w.write_all(b" "));
+      }
+
+      write_ascii_value(value, w)
+    }
+    Encoding::BinaryLittleEndian => write_binary_value(value, w)
+  };
+}
+
+fn end_row<W: Write + ?Sized>(encoding: Encoding, w: &mut W) -> io::Result<()> {
+  return match encoding {
+    Encoding::Ascii => w.write_all(b"\n"),
+    Encoding::BinaryLittleEndian => Ok(())
+  };
+}
+
+fn write_ascii_value<W: Write + ?Sized>(value: ScalarValue, w: &mut W) -> io::Result<()> {
+  return match value {
+    ScalarValue::u8(x) | ScalarValue::u8_normalized(x) => write!(w, "{}", x),
+    ScalarValue::u16(x) | ScalarValue::u16_normalized(x) => write!(w, "{}", x),
+    ScalarValue::u32(x) | ScalarValue::u32_normalized(x) => write!(w, "{}", x),
+    ScalarValue::i8(x) | ScalarValue::i8_normalized(x) => write!(w, "{}", x),
+    ScalarValue::i16(x) | ScalarValue::i16_normalized(x) => write!(w, "{}", x),
+    ScalarValue::i32(x) | ScalarValue::i32_normalized(x) => write!(w, "{}", x),
+    other => write!(w, "{}", other.to_f32())
+  };
+}
+
+fn write_binary_value<W: Write + ?Sized>(value: ScalarValue, w: &mut W) -> io::Result<()> {
+  return match value {
+    ScalarValue::u8(x) | ScalarValue::u8_normalized(x) => w.write_u8(x),
+    ScalarValue::u16(x) | ScalarValue::u16_normalized(x) => w.write_u16::<LittleEndian>(x),
+    ScalarValue::u32(x) | ScalarValue::u32_normalized(x) => w.write_u32::<LittleEndian>(x),
+    ScalarValue::i8(x) | ScalarValue::i8_normalized(x) => w.write_i8(x),
+    ScalarValue::i16(x) | ScalarValue::i16_normalized(x) => w.write_i16::<LittleEndian>(x),
+    ScalarValue::i32(x) | ScalarValue::i32_normalized(x) => w.write_i32::<LittleEndian>(x),
+    other => w.write_f32::<LittleEndian>(other.to_f32())
+  };
+}
+
+// Half floats and packed vectors have no PLY type, they are written as floats
+fn property_type(scalar: vertex::Scalar) -> &'static str {
+  return match scalar {
+    vertex::Scalar::i8 | vertex::Scalar::i8_normalized => "char",
+    vertex::Scalar::u8 | vertex::Scalar::u8_normalized => "uchar",
+    vertex::Scalar::i16 | vertex::Scalar::i16_normalized => "short",
+    vertex::Scalar::u16 | vertex::Scalar::u16_normalized => "ushort",
+    vertex::Scalar::i32 | vertex::Scalar::i32_normalized => "int",
+    vertex::Scalar::u32 | vertex::Scalar::u32_normalized => "uint",
+    _ => "float"
+  };
+}
+
+// The usual names for positions, normals, colors and texture coordinates, the rest are numbered
+fn property_names(name: &vertex::AttributeName, set: usize, elements: usize) -> Vec<String> {
+  let suffix = if set == 0 { String::new() } else { format!("{}", set) };
+
+  let known: &[&str] = match *name {
+    vertex::AttributeName::Position if set == 0 => &["x", "y", "z", "w"],
+    vertex::AttributeName::Normal if set == 0 => &["nx", "ny", "nz", "nw"],
+    vertex::AttributeName::Color => &["red", "green", "blue", "alpha"],
+    vertex::AttributeName::TextureCoordinate => &["s", "t", "r", "q"],
+    _ => &[]
+  };
+
+  if elements <= known.len() {
+    return known[.. elements].iter().map(|x| format!("{}{}", x, suffix)).collect();
+  }
+
+  let base = match *name {
+    vertex::AttributeName::Other(ref name) => name.to_lowercase().replace(" ", "_"),
+    ref other => snake_case(&format!("{:?}", other))
+  };
+
+  let base = if set == 0 { base } else { format!("{}{}", base, set) };
+
+  return (0 .. elements).map(|i| format!("{}_{}", base, i)).collect();
+}
+
+fn snake_case(name: &str) -> String {
+  let mut result = String::new();
+
+  for (i, c) in name.chars().enumerate() {
+    if c.is_uppercase() && i > 0 {
+      result.push('_');
+    }
+
+    result.extend(c.to_lowercase());
+  }
+
+  return result;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use index;
+  use vertex;
+
+  use mesh::MeshBuilder;
+
+  fn mesh() -> mesh::Mesh {
+    let colors = vertex::Format(vertex::Scalar::u8_normalized, vertex::Width::Vector4);
+    let tangents = vertex::Format(vertex::Scalar::i16_normalized, vertex::Width::Vector4);
+
+    return MeshBuilder::new("hull")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]])
+      .attribute(vertex::AttributeName::Color, colors, &[1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0])
+      .attribute(vertex::AttributeName::Tangent, tangents, &[1.0, 0.0, 0.0, -1.0, 1.0, 0.0, 0.0, -1.0, 1.0, 0.0, 0.0, -1.0])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2])
+      .build();
+  }
+
+  #[test]
+  fn test_export_ascii() {
+    let ply = String::from_utf8(export_mesh(&mesh(), &Options { encoding: Encoding::Ascii })).unwrap();
+
+    assert_eq!(ply, "ply\nformat ascii 1.0\ncomment area 0 hull\nelement vertex 3\n\
+      property float x\nproperty float y\nproperty float z\n\
+      property uchar red\nproperty uchar green\nproperty uchar blue\nproperty uchar alpha\n\
+      property short tangent_0\nproperty short tangent_1\nproperty short tangent_2\nproperty short tangent_3\n\
+      element face 1\nproperty list uchar uint vertex_indices\nproperty ushort area\nend_header\n\
+      0 0 0 255 0 0 255 32767 0 0 -32767\n\
+      1 0 0 0 255 0 255 32767 0 0 -32767\n\
+      0 1 0 0 0 255 255 32767 0 0 -32767\n\
+      3 0 1 2 0\n");
+  }
+
+  #[test]
+  fn test_export_binary() {
+    let ply = export_mesh(&mesh(), &Options::default());
+    let end = "end_header\n";
+
+    let body = ply.len() - ply.windows(end.len()).position(|x| x == end.as_bytes()).unwrap() - end.len();
+
+    // Vertices of three floats, four bytes and four shorts, then a face of 1 + 3 * 4 + 2 bytes
+    assert_eq!(body, 3 * (12 + 4 + 8) + 15);
+    assert_eq!(property_names(&vertex::AttributeName::JointIndices, 1, 4), vec!["joint_indices1_0", "joint_indices1_1", "joint_indices1_2", "joint_indices1_3"]);
+  }
+}
@@ -0,0 +1,358 @@
+use std::io;
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use asset;
+use math;
+use mesh;
+use vertex;
+
+use exporter;
+
+use exporter::{Output, unique_name};
+use mesh::transform;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+  Ascii,
+  Binary
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Options {
+  pub encoding: Encoding,
+  pub excluded_areas: Vec<String>, // Areas whose name contains any of these are left out, ignoring case
+  pub millimetres_per_unit: f32 // EVE units are metres
+}
+
+impl Options {
+  pub fn default() -> Options {
+    return Options {
+      encoding: Encoding::Binary,
+      excluded_areas: vec!["decal".to_owned(), "glass".to_owned(), "light".to_owned()],
+      millimetres_per_unit: 1000.0
+    };
+  }
+}
+
+// Edges are matched by position, so seams in texture coordinates or normals do not open a mesh
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Report {
+  pub triangles: usize,
+  pub open_edges: usize, // Used by a single triangle
+  pub non_manifold_edges: usize // Used by more than two triangles, or twice in the same direction
+}
+
+impl Report {
+  pub fn is_watertight(&self) -> bool {
+    return self.triangles > 0 && self.open_edges == 0 && self.non_manifold_edges == 0;
+  }
+}
+
+pub struct Exporter {
+  pub options: Options
+}
+
+impl Exporter {
+  pub fn default() -> Exporter {
+    return Exporter { options: Options::default() };
+  }
+}
+
+impl exporter::Exporter for Exporter {
+  fn name(&self) -> &'static str {
+    return "stl";
+  }
+
+  fn extensions(&self) -> Vec<&'static str> {
+    return vec!["stl", "txt"];
+  }
+
+  fn options(&self) -> Vec<(&'static str, String)> {
+    let encoding = match self.options.encoding { Encoding::Ascii => "ascii", Encoding::Binary => "binary" };
+
+    return vec![
+      ("encoding", encoding.to_owned()),
+      ("excluded_areas", self.options.excluded_areas.join(",")),
+      ("millimetres_per_unit", format!("{}", self.options.millimetres_per_unit))
+    ];
+  }
+
+  // Excluded areas are separated by commas
+  fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+    match (name, value) {
+      ("encoding", "ascii") => self.options.encoding = Encoding::Ascii,
+      ("encoding", "binary") => self.options.encoding = Encoding::Binary,
+      ("encoding", _) => return Err(format!("encoding must be ascii or binary, not {}", value)),
+      ("excluded_areas", _) => self.options.excluded_areas = value.split(',').filter(|x| !x.is_empty()).map(|x| x.to_owned()).collect(),
+      ("millimetres_per_unit", _) => match value.parse::<f32>() {
+        Ok(x) if x > 0.0 => self.options.millimetres_per_unit = x,
+        _ => return Err(format!("millimetres_per_unit must be a positive number, not {}", value))
+      },
+      _ => return Err(format!("stl has no option {}", name))
+    }
+
+    return Ok(());
+  }
+
+  fn export(&self, asset: &asset::Asset, output: &mut Output) -> io::Result<()> {
+    return export_to(asset, &self.options, output);
+  }
+}
+
+pub fn export(asset: &asset::Asset) -> Vec<(String, Vec<u8>)> {
+  let options = Options::default();
+
+  return exporter::in_memory(|output| export_to(asset, &options, output));
+}
+
+pub fn export_with_options(asset: &asset::Asset, options: &Options) -> io::Result<Vec<(String, Vec<u8>, Report)>> {
+  let mut names = Vec::new();
+  let mut files = Vec::new();
+
+  for object in &asset.objects {
+    match object {
+      &asset::Object::Model(ref m) => {
+        let name = unique_name(&m.mesh.name, &mut names);
+        let mesh = convert(&m.mesh);
+
+        let mut stl = Vec::new();
+
+        try!(write_converted_mesh(&mesh, options, &mut stl));
+
+        files.push((format!("{}.stl", name), stl, report(&mesh, options)));
+      }
+      _ => ()
+    }
+  }
+
+  return Ok(files);
+}
+
+// Each .stl comes with a .txt that says whether it can be printed as it is
+pub fn export_to(asset: &asset::Asset, options: &Options, output: &mut Output) -> io::Result<()> {
+  let mut names = Vec::new();
+
+  for object in &asset.objects {
+    match object {
+      &asset::Object::Model(ref m) => {
+        let name = unique_name(&m.mesh.name, &mut names);
+        let mesh = convert(&m.mesh);
+
+        try!(output.start_file(&format!("{}.stl", name)));
+        try!(write_converted_mesh(&mesh, options, output));
+
+        try!(output.start_file(&format!("{}.txt", name)));
+        try!(write_report(&report(&mesh, options), output));
+      }
+      _ => ()
+    }
+  }
+
+  return Ok(());
+}
+
+pub fn export_mesh(mesh: &mesh::Mesh, options: &Options) -> io::Result<Vec<u8>> {
+  let mut result = Vec::new();
+
+  try!(write_mesh(mesh, options, &mut result));
+
+  return Ok(result);
+}
+
+pub fn write_mesh<W: Write + ?Sized>(mesh: &mesh::Mesh, options: &Options, w: &mut W) -> io::Result<()> {
+  return write_converted_mesh(&convert(mesh), options, w);
+}
+
+// Slicers take Z to be up, so meshes go into the right handed, Z up space of Blender first. That
+// turns their triangles around as well, keeping the facet normals pointing out
+fn convert(mesh: &mesh::Mesh) -> mesh::Mesh {
+  return transform::convert(mesh, &transform::EVE, &transform::BLENDER);
+}
+
+// The header of binary files must not start with "solid", or readers take them for ASCII ones
+fn write_converted_mesh<W: Write + ?Sized>(mesh: &mesh::Mesh, options: &Options, w: &mut W) -> io::Result<()> {
+  let positions = match mesh.untyped_view_for(&vertex::AttributeName::Position) {
+    Some(positions) => positions,
+    None => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no positions to export to .stl", mesh.name)))
+  };
+
+  let point = |v: usize| -> math::Vector3 {
+    let p = [positions.get_element_f32(v, 0), positions.get_element_f32(v, 1), positions.get_element_f32(v, 2)];
+
+    math::scale(p, options.millimetres_per_unit)
+  };
+
+  let submeshes: Vec<&mesh::Submesh> = mesh.submeshes.iter().filter(|x| x.geometry.is_triangles() && !is_excluded(x, options)).collect();
+  let name: String = mesh.name.chars().map(|x| if x.is_whitespace() { '_' } else { x }).collect();
+
+  match options.encoding {
+    Encoding::Ascii => try!(write!(w, "solid {}\n", name)),
+    Encoding::Binary => {
+      let mut header = format!("Binary STL of {}", mesh.name).into_bytes();
+
+      header.resize(80, 0);
+
+      try!(w.write_all(&header));
+      try!(w.write_u32::<LittleEndian>(submeshes.iter().map(|x| x.faces().len()).sum::<usize>() as u32));
+    }
+  }
+
+  for submesh in submeshes {
+    let mut faces = submesh.faces();
+
+    while let Some((_, face)) = faces.next_indices() {
+      let p = [point(face[0]), point(face[1]), point(face[2])];
+      let normal = math::normalize(math::cross(math::sub(p[1], p[0]), math::sub(p[2], p[0])));
+
+      match options.encoding {
+        Encoding::Ascii => {
+          try!(write!(w, "  facet normal {} {} {}\n    outer loop\n", normal[0], normal[1], normal[2]));
+
+          for v in &p {
+            try!(write!(w, "      vertex {} {} {}\n", v[0], v[1], v[2]));
+          }
+
+          try!(w.write_all(b"    endloop\n  endfacet\n"));
+        }
+        Encoding::Binary => {
+          for x in normal.iter().chain(p.iter().flat_map(|v| v.iter())) {
+            try!(w.write_f32::<LittleEndian>(*x));
+          }
+
+          try!(w.write_u16::<LittleEndian>(0));
+        }
+      }
+    }
+  }
+
+  return match options.encoding {
+    Encoding::Ascii => write!(w, "endsolid {}\n", name),
+    Encoding::Binary => Ok(())
+  };
+}
+
+pub fn report(mesh: &mesh::Mesh, options: &Options) -> Report {
+  let positions = mesh.positions().unwrap_or(Vec::new());
+  let triangles = triangles(mesh, options);
+
+  let key = |v: usize| math::position_key(positions[v]);
+
+  // Uses of each edge in either direction
+  let mut edges: HashMap<([u32; 3], [u32; 3]), (usize, usize)> = HashMap::new();
+
+  for face in &triangles {
+    for c in 0 .. 3 {
+      let (from, to) = (key(face[c]), key(face[(c + 1) % 3]));
+
+      if from < to {
+        edges.entry((from, to)).or_insert((0, 0)).0 += 1;
+      } else {
+        edges.entry((to, from)).or_insert((0, 0)).1 += 1;
+      }
+    }
+  }
+
+  let open_edges = edges.values().filter(|&&(a, b)| a + b == 1).count();
+  let non_manifold_edges = edges.values().filter(|&&(a, b)| a + b > 1 && (a != 1 || b != 1)).count();
+
+  return Report { triangles: triangles.len(), open_edges: open_edges, non_manifold_edges: non_manifold_edges };
+}
+
+pub fn write_report<W: Write + ?Sized>(report: &Report, w: &mut W) -> io::Result<()> {
+  try!(write!(w, "triangles: {}\nopen edges: {}\nnon-manifold edges: {}\n", report.triangles, report.open_edges, report.non_manifold_edges));
+
+  return write!(w, "watertight: {}\n", if report.is_watertight() { "yes" } else { "no" });
+}
+
+fn is_excluded(submesh: &mesh::Submesh, options: &Options) -> bool {
+  let name = submesh.name.to_lowercase();
+
+  return options.excluded_areas.iter().any(|x| name.contains(&x.to_lowercase()));
+}
+
+fn triangles(mesh: &mesh::Mesh, options: &Options) -> Vec<[usize; 3]> {
+  return mesh.triangles().into_iter().filter(|x| !is_excluded(&mesh.submeshes[x.0], options)).map(|x| x.1).collect();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use asset;
+  use index;
+  use model;
+
+  use animation::skeleton;
+  use mesh::MeshBuilder;
+
+  // A tetrahedron whose last face is split off into a glass area, with a seam on one vertex that
+  // only differs in the sign of zero
+  fn mesh() -> mesh::Mesh {
+    return MeshBuilder::new("hull")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [-0.0, 0.0, -0.0]])
+      .submesh("hull", index::Geometry::Triangles, &[0, 2, 1, 0, 1, 3, 4, 3, 2])
+      .submesh("Cockpit Glass", index::Geometry::Triangles, &[1, 2, 3])
+      .build();
+  }
+
+  #[test]
+  fn test_report() {
+    let all = Options { excluded_areas: Vec::new(), .. Options::default() };
+
+    assert!(report(&mesh(), &all).is_watertight());
+
+    let hull = report(&mesh(), &Options::default());
+
+    assert_eq!(hull.triangles, 3);
+    assert_eq!(hull.open_edges, 3);
+    assert!(!hull.is_watertight());
+  }
+
+  #[test]
+  fn test_export() {
+    let asset = asset::Asset { buffers: Vec::new(), objects: vec![asset::Object::Model(model::Model {
+      name: "frigate".to_owned(),
+      mesh: mesh(),
+      blend_shapes: Vec::new(),
+      lods: Vec::new(),
+      skeleton: skeleton::Skeleton { bones: Vec::new() }
+    })] };
+
+    let files = export(&asset);
+
+    assert_eq!(files.iter().map(|x| x.0.as_str()).collect::<Vec<_>>(), vec!["hull.stl", "hull.txt"]);
+    assert_eq!(String::from_utf8(files[1].1.clone()).unwrap(), "triangles: 3\nopen edges: 3\nnon-manifold edges: 0\nwatertight: no\n");
+  }
+
+  #[test]
+  fn test_export_binary() {
+    let stl = export_mesh(&mesh(), &Options { millimetres_per_unit: 10.0, .. Options::default() }).unwrap();
+
+    // The first triangle faces -Z in EVE, which is -Y once Z is up
+    assert_eq!(stl.len(), 84 + 3 * 50);
+    assert_eq!(&stl[80 .. 84], &[3, 0, 0, 0]);
+    assert_eq!(&stl[84 .. 96], &[0, 0, 0, 0, 0, 0, 128, 191, 0, 0, 0, 0]); // Normal of (0, -1, 0)
+    assert_eq!(&stl[108 .. 112], &[0, 0, 32, 65]); // 10.0, the x of the second vertex
+    assert_eq!(&stl[128 .. 132], &[0, 0, 32, 65]); // 10.0, the z of the third vertex
+  }
+
+  #[test]
+  fn test_export_without_positions() {
+    let mesh = MeshBuilder::new("hull").normals(&[[0.0, 0.0, 1.0]; 3]).submesh("hull", index::Geometry::Triangles, &[0, 1, 2]).build();
+
+    assert_eq!(export_mesh(&mesh, &Options::default()).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+  }
+
+  #[test]
+  fn test_export_ascii() {
+    let stl = String::from_utf8(export_mesh(&mesh(), &Options { encoding: Encoding::Ascii, .. Options::default() }).unwrap()).unwrap();
+
+    assert!(stl.starts_with("solid hull\n  facet normal 0 -1 0\n    outer loop\n      vertex 0 0 0\n      vertex 1000 0 0\n"));
+    assert!(stl.ends_with("  endfacet\nendsolid hull\n"));
+    assert_eq!(stl.matches("facet normal").count(), 3);
+  }
+}
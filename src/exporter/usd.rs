@@ -0,0 +1,570 @@
+use std::fmt;
+use std::io;
+
+use std::io::Write;
+
+use asset;
+use math;
+use model;
+use vertex;
+
+use animation::skeleton;
+use buffer::UntypedView;
+use mesh::{Mesh, transform};
+
+use exporter;
+
+use exporter::{Output, joints, number, unique_name};
+
+pub struct Exporter;
+
+impl Exporter {
+  pub fn default() -> Exporter {
+    return Exporter;
+  }
+}
+
+impl exporter::Exporter for Exporter {
+  fn name(&self) -> &'static str {
+    return "usd";
+  }
+
+  fn extensions(&self) -> Vec<&'static str> {
+    return vec!["usda"];
+  }
+
+  fn export(&self, asset: &asset::Asset, output: &mut Output) -> io::Result<()> {
+    return export_to(asset, output);
+  }
+}
+
+pub fn export(asset: &asset::Asset) -> Vec<(String, Vec<u8>)> {
+  return exporter::in_memory(|output| export_to(asset, output));
+}
+
+pub fn export_to(asset: &asset::Asset, output: &mut Output) -> io::Result<()> {
+  try!(output.start_file(&format!("{}.usda", asset.name())));
+
+  return write_usda(asset, output);
+}
+
+pub fn export_usda(asset: &asset::Asset) -> String {
+  let mut result = Vec::new();
+
+  write_usda(asset, &mut result).unwrap();
+
+  return String::from_utf8(result).unwrap();
+}
+
+// A stage with an Xform for the asset and a prim for every model under it. Rigged models and
+// models with blend shapes are SkelRoots, with their bones in a Skeleton next to the Mesh. The
+// stage is right handed, so the asset is mirrored into it first
+pub fn write_usda<W: Write + ?Sized>(asset: &asset::Asset, w: &mut W) -> io::Result<()> {
+  let asset = &transform::convert_asset(asset, &transform::EVE, &transform::USD);
+  let root = identifier(&asset.name());
+
+  try!(write!(w, "#usda 1.0\n(\n    defaultPrim = \"{}\"\n    metersPerUnit = 1\n    upAxis = \"Y\"\n)\n\n", root));
+  try!(write!(w, "def Xform \"{}\"\n{{\n", root));
+
+  let mut names = Vec::new();
+  let mut first = true;
+
+  for object in &asset.objects {
+    match object {
+      &asset::Object::Model(ref m) => {
+        if !first { try!(w.write_all(b"\n")) };
+
+        let name = unique_name(&identifier(&m.name), &mut names);
+
+        try!(write_model(&format!("/{}/{}", root, name), &name, m, w));
+        first = false;
+      }
+      _ => ()
+    }
+  }
+
+  return w.write_all(b"}\n");
+}
+
+fn write_model<W: Write + ?Sized>(path: &str, name: &str, model: &model::Model, w: &mut W) -> io::Result<()> {
+  let mesh = &model.mesh;
+  let joints = joints(model);
+
+  // Blend shapes are only applied to meshes bound to a skeleton, even one without any joints
+  let bound = joints.is_some() || !model.blend_shapes.is_empty();
+  let kind = if bound { "SkelRoot" } else { "Xform" };
+
+  try!(line(w, 1, format_args!("def {} \"{}\"", kind, name)));
+  try!(line(w, 1, format_args!("{{")));
+
+  let mesh_name = identifier(&mesh.name);
+  let mesh_path = format!("{}/{}", path, mesh_name);
+
+  if bound {
+    try!(line(w, 2, format_args!("def Mesh \"{}\" (", mesh_name)));
+    try!(line(w, 3, format_args!("prepend apiSchemas = [\"SkelBindingAPI\"]")));
+    try!(line(w, 2, format_args!(")")));
+  } else {
+    try!(line(w, 2, format_args!("def Mesh \"{}\"", mesh_name)));
+  }
+
+  try!(line(w, 2, format_args!("{{")));
+
+  // Only triangles make faces, line and point areas are left out
+  let triangles = mesh.triangles();
+
+  try!(property(w, 3, "int[] faceVertexCounts", triangles.iter().map(|_| 3)));
+  try!(property(w, 3, "int[] faceVertexIndices", triangles.iter().flat_map(|x| x.1.iter())));
+
+  try!(write_attributes(mesh, joints.is_some(), w));
+
+  try!(line(w, 3, format_args!("uniform token subdivisionScheme = \"none\"")));
+
+  if !triangles.is_empty() {
+    try!(line(w, 3, format_args!("uniform token subsetFamily:materialBind:familyType = \"nonOverlapping\"")));
+  }
+
+  // Blend shapes and subsets are children of the mesh, so they share one set of names
+  let mut used = Vec::new();
+
+  let shape_names: Vec<String> = model.blend_shapes.iter().map(|x| unique_name(&identifier(&x.name), &mut used)).collect();
+  let subset_names: Vec<String> = mesh.submeshes.iter().map(|x| unique_name(&identifier(&x.name), &mut used)).collect();
+
+  if !shape_names.is_empty() {
+    try!(property(w, 3, "uniform token[] skel:blendShapes", shape_names.iter().map(|x| format!("\"{}\"", x))));
+    try!(property(w, 3, "rel skel:blendShapeTargets", shape_names.iter().map(|x| format!("<{}/{}>", mesh_path, x))));
+  }
+
+  if let Some(ref joints) = joints {
+    let paths = joint_paths(&model.skeleton.bones);
+
+    try!(property(w, 3, "uniform token[] skel:joints", joints.iter().map(|x| format!("\"{}\"", paths[*x]))));
+  }
+
+  if bound {
+    try!(line(w, 3, format_args!("rel skel:skeleton = <{}/skeleton>", path)));
+  }
+
+  for (area, submesh) in mesh.submeshes.iter().enumerate() {
+    let faces = (0 .. triangles.len()).filter(|x| triangles[*x].0 == area);
+
+    if faces.clone().next().is_none() {
+      continue;
+    }
+
+    try!(w.write_all(b"\n"));
+    try!(line(w, 3, format_args!("def GeomSubset \"{}\" (", subset_names[area])));
+    try!(line(w, 4, format_args!("customData = {{ string area = \"{}\" }}", escape(&submesh.name))));
+    try!(line(w, 3, format_args!(")")));
+    try!(line(w, 3, format_args!("{{")));
+    try!(line(w, 4, format_args!("uniform token elementType = \"face\"")));
+    try!(line(w, 4, format_args!("uniform token familyName = \"materialBind\"")));
+    try!(property(w, 4, "int[] indices", faces));
+    try!(line(w, 3, format_args!("}}")));
+  }
+
+  for (shape, shape_name) in model.blend_shapes.iter().zip(shape_names.iter()) {
+    try!(w.write_all(b"\n"));
+    try!(write_blend_shape(mesh, shape, shape_name, w));
+  }
+
+  try!(line(w, 2, format_args!("}}")));
+
+  if bound {
+    try!(w.write_all(b"\n"));
+    try!(write_skeleton(&model.skeleton, w));
+  }
+
+  return line(w, 1, format_args!("}}"));
+}
+
+// Positions, normals, texture coordinates and colors go where USD expects them, every other
+// attribute becomes a float primvar named after it
+fn write_attributes<W: Write + ?Sized>(mesh: &Mesh, skinned: bool, w: &mut W) -> io::Result<()> {
+  let attributes = &mesh.descriptor.attributes;
+
+  for (i, attribute) in attributes.iter().enumerate() {
+    let set = attributes[.. i].iter().filter(|x| x.name == attribute.name).count();
+    let suffix = if set == 0 { String::new() } else { format!("{}", set) };
+
+    let view = &mesh.untyped_view_for_attribute(attribute);
+    let elements = attribute.format.elements();
+
+    let tuples = |n: usize| (0 .. mesh.vertex_count).map(move |v| Tuple::from_view(view, v, n));
+    let scalars = |c: usize| (0 .. mesh.vertex_count).map(move |v| number(view.get_element_f32(v, c)));
+    let flat = (0 .. mesh.vertex_count).flat_map(|v| (0 .. elements).map(move |c| number(view.get_element_f32(v, c))));
+
+    try!(match attribute.name {
+      vertex::AttributeName::Position if set == 0 => property(w, 3, "point3f[] points", tuples(3)),
+      vertex::AttributeName::Normal if set == 0 => primvar(w, "normal3f[] normals", tuples(3), 1),
+      vertex::AttributeName::TextureCoordinate => {
+        // Texture coordinates start at the top in EVE and at the bottom in USD
+        let st = tuples(2).map(|mut x| {
+          x.0[1] = 1.0 - x.0[1];
+
+          x
+        });
+
+        primvar(w, &format!("texCoord2f[] primvars:st{}", suffix), st, 1)
+      }
+      vertex::AttributeName::Color => {
+        try!(primvar(w, &format!("color3f[] primvars:displayColor{}", suffix), tuples(3), 1));
+
+        if elements > 3 {
+          try!(primvar(w, &format!("float[] primvars:displayOpacity{}", suffix), scalars(3), 1));
+        }
+
+        Ok(())
+      }
+      vertex::AttributeName::JointIndices if set == 0 && skinned => {
+        try!(primvar(w, "int[] primvars:skel:jointIndices", flat.map(|x| x as usize), elements));
+
+        // Missing weights leave everything to the first joint
+        match mesh.untyped_view_for(&vertex::AttributeName::JointWeights) {
+          Some(ref weights) => {
+            let available = weights.elements();
+            let values = (0 .. mesh.vertex_count).flat_map(|v| (0 .. elements).map(move |c| if c < available { number(weights.get_element_f32(v, c)) } else { 0.0 }));
+
+            primvar(w, "float[] primvars:skel:jointWeights", values, elements)
+          }
+          None => primvar(w, "float[] primvars:skel:jointWeights", (0 .. mesh.vertex_count * elements).map(|x| if x % elements == 0 { 1 } else { 0 }), elements)
+        }
+      }
+      vertex::AttributeName::JointWeights if set == 0 && skinned => Ok(()), // Written with the joint indices
+      ref other => {
+        let name = match *other {
+          vertex::AttributeName::Other(ref name) => identifier(name),
+          ref other => {
+            let name = format!("{:?}", other);
+
+            format!("{}{}", name[.. 1].to_lowercase(), &name[1 ..])
+          }
+        };
+
+        match elements {
+          1 => primvar(w, &format!("float[] primvars:{}{}", name, suffix), scalars(0), 1),
+          2 | 3 | 4 => primvar(w, &format!("float{}[] primvars:{}{}", elements, name, suffix), tuples(elements), 1),
+          _ => primvar(w, &format!("float[] primvars:{}{}", name, suffix), flat, elements)
+        }
+      }
+    });
+  }
+
+  return Ok(());
+}
+
+fn primvar<W: Write + ?Sized, T: fmt::Display, I: Iterator<Item = T>>(w: &mut W, declaration: &str, values: I, element_size: usize) -> io::Result<()> {
+  try!(indent(w, 3));
+  try!(write!(w, "{} = ", declaration));
+  try!(write_list(w, values));
+  try!(w.write_all(b" (\n"));
+
+  if element_size > 1 {
+    try!(line(w, 4, format_args!("elementSize = {}", element_size)));
+  }
+
+  try!(line(w, 4, format_args!("interpolation = \"vertex\"")));
+
+  return line(w, 3, format_args!(")"));
+}
+
+// Blend shapes hold the new positions of the vertices they list, USD wants offsets from the mesh
+fn write_blend_shape<W: Write + ?Sized>(mesh: &Mesh, shape: &Mesh, name: &str, w: &mut W) -> io::Result<()> {
+  let positions = mesh.positions().unwrap_or(Vec::new());
+  let normals = mesh.untyped_view_for(&vertex::AttributeName::Normal);
+
+  let shape_positions = shape.positions().unwrap_or(Vec::new());
+  let shape_normals = shape.untyped_view_for(&vertex::AttributeName::Normal);
+
+  let mut indices = Vec::new();
+  let mut offsets = Vec::new();
+  let mut normal_offsets = Vec::new();
+
+  if let Some(points) = shape.submeshes.first() {
+    let view = points.untyped_view();
+
+    for i in 0 .. points.index_count.min(shape_positions.len()) {
+      let v = view.get_usize(i);
+
+      if v >= positions.len() {
+        continue;
+      }
+
+      indices.push(v);
+      offsets.push(Tuple::from_vector(math::sub(shape_positions[i], positions[v])));
+
+      if let (Some(ref normals), Some(ref shape_normals)) = (normals.as_ref(), shape_normals.as_ref()) {
+        normal_offsets.push(Tuple::from_vector(math::sub(math::to_vector3(&shape_normals.get_f32(i)), math::to_vector3(&normals.get_f32(v)))));
+      }
+    }
+  }
+
+  try!(line(w, 3, format_args!("def BlendShape \"{}\"", name)));
+  try!(line(w, 3, format_args!("{{")));
+  try!(property(w, 4, "uniform vector3f[] offsets", offsets.into_iter()));
+
+  if !normal_offsets.is_empty() {
+    try!(property(w, 4, "uniform vector3f[] normalOffsets", normal_offsets.into_iter()));
+  }
+
+  try!(property(w, 4, "uniform int[] pointIndices", indices.into_iter()));
+
+  return line(w, 3, format_args!("}}"));
+}
+
+// Bind transforms are in the space of the skeleton, rest transforms in the space of the parent
+fn write_skeleton<W: Write + ?Sized>(skeleton: &skeleton::Skeleton, w: &mut W) -> io::Result<()> {
+  try!(line(w, 2, format_args!("def Skeleton \"skeleton\"")));
+  try!(line(w, 2, format_args!("{{")));
+  try!(property(w, 3, "uniform matrix4d[] bindTransforms", (0 .. skeleton.bones.len()).map(|x| Matrix(skeleton.world_transform(x)))));
+  try!(property(w, 3, "uniform token[] joints", joint_paths(&skeleton.bones).into_iter().map(|x| format!("\"{}\"", x))));
+  try!(property(w, 3, "uniform matrix4d[] restTransforms", skeleton.bones.iter().map(|x| Matrix(x.transform()))));
+
+  return line(w, 2, format_args!("}}"));
+}
+
+// Joints are named by their path from the root of the skeleton
+fn joint_paths(bones: &[skeleton::Bone]) -> Vec<String> {
+  return (0 .. bones.len()).map(|i| {
+    let mut path = identifier(&bones[i].name);
+    let mut parent = bones[i].parent_index;
+
+    while let Some(p) = parent {
+      path = format!("{}/{}", identifier(&bones[p].name), path);
+      parent = bones[p].parent_index;
+    }
+
+    path
+  }).collect();
+}
+
+fn indent<W: Write + ?Sized>(w: &mut W, depth: usize) -> io::Result<()> {
+  for _ in 0 .. depth {
+    try!(w.write_all(b"    "));
+  }
+
+  return Ok(());
+}
+
+fn line<W: Write + ?Sized>(w: &mut W, depth: usize, text: fmt::Arguments) -> io::Result<()> {
+  try!(indent(w, depth));
+  try!(w.write_fmt(text));
+
+  return w.write_all(b"\n");
+}
+
+fn property<W: Write + ?Sized, T: fmt::Display, I: Iterator<Item = T>>(w: &mut W, depth: usize, declaration: &str, values: I) -> io::Result<()> {
+  try!(indent(w, depth));
+  try!(write!(w, "{} = ", declaration));
+  try!(write_list(w, values));
+
+  return w.write_all(b"\n");
+}
+
+fn write_list<W: Write + ?Sized, T: fmt::Display, I: Iterator<Item = T>>(w: &mut W, values: I) -> io::Result<()> {
+  try!(w.write_all(b"["));
+
+  for (i, value) in values.enumerate() {
+    if i > 0 {
+      try!(w.write_all(b", "));
+    }
+
+    try!(write!(w, "{}", value));
+  }
+
+  return w.write_all(b"]");
+}
+
+// Up to four numbers in parentheses
+struct Tuple([f32; 4], usize);
+
+impl Tuple {
+  fn from_view(view: &UntypedView, v: usize, elements: usize) -> Tuple {
+    let mut values = [0.0; 4];
+
+    for c in 0 .. elements.min(view.elements()) {
+      values[c] = view.get_element_f32(v, c);
+    }
+
+    return Tuple(values, elements);
+  }
+
+  fn from_vector(v: math::Vector3) -> Tuple {
+    return Tuple([v[0], v[1], v[2], 0.0], 3);
+  }
+}
+
+impl fmt::Display for Tuple {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    try!(write!(f, "("));
+
+    for c in 0 .. self.1 {
+      try!(write!(f, "{}{}", if c > 0 { ", " } else { "" }, number(self.0[c])));
+    }
+
+    return write!(f, ")");
+  }
+}
+
+// USD multiplies row vectors, so the translation goes in the last row
+struct Matrix(math::Matrix4);
+
+impl fmt::Display for Matrix {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    try!(write!(f, "( "));
+
+    for r in 0 .. 4 {
+      let m = &self.0;
+
+      try!(write!(f, "{}({}, {}, {}, {})", if r > 0 { ", " } else { "" }, number(m[0][r]), number(m[1][r]), number(m[2][r]), number(m[3][r])));
+    }
+
+    return write!(f, " )");
+  }
+}
+
+fn escape(text: &str) -> String {
+  return text.replace("\\", "\\\\").replace("\"", "\\\"");
+}
+
+// Prim names start with a letter or an underscore and hold nothing but those and digits
+fn identifier(name: &str) -> String {
+  let mut identifier: String = name.chars().map(|x| if x.is_ascii() && (x.is_alphanumeric() || x == '_') { x } else { '_' }).collect();
+
+  if identifier.is_empty() {
+    return "default".to_owned();
+  }
+
+  if identifier.chars().next().unwrap().is_numeric() {
+    identifier.insert(0, '_');
+  }
+
+  return identifier;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use index;
+
+  use exporter::fixtures::{asset, bone};
+  use mesh::{Mesh, MeshBuilder};
+
+  fn builder() -> MeshBuilder {
+    return MeshBuilder::new("hull")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0]])
+      .normals(&[[0.0, 0.0, 1.0]; 4])
+      .texture_coordinates(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2])
+      .submesh("glass window", index::Geometry::Triangles, &[2, 1, 3])
+      .clone();
+  }
+
+  // Mirroring into USD flips the winding and the normals
+  #[test]
+  fn test_export() {
+    let files = export(&asset(builder().build(), Vec::new(), Vec::new()));
+
+    assert_eq!(files[0].0, "hull.usda");
+    assert_eq!(String::from_utf8(files[0].1.clone()).unwrap(), r#"#usda 1.0
+(
+    defaultPrim = "hull"
+    metersPerUnit = 1
+    upAxis = "Y"
+)
+
+def Xform "hull"
+{
+    def Xform "hull"
+    {
+        def Mesh "hull"
+        {
+            int[] faceVertexCounts = [3, 3]
+            int[] faceVertexIndices = [0, 2, 1, 2, 3, 1]
+            point3f[] points = [(0, 0, 0), (1, 0, 0), (0, 1, 0), (1, 1, 0)]
+            normal3f[] normals = [(0, 0, -1), (0, 0, -1), (0, 0, -1), (0, 0, -1)] (
+                interpolation = "vertex"
+            )
+            texCoord2f[] primvars:st = [(0, 1), (1, 1), (0, 0), (1, 0)] (
+                interpolation = "vertex"
+            )
+            uniform token subdivisionScheme = "none"
+            uniform token subsetFamily:materialBind:familyType = "nonOverlapping"
+
+            def GeomSubset "hull" (
+                customData = { string area = "hull" }
+            )
+            {
+                uniform token elementType = "face"
+                uniform token familyName = "materialBind"
+                int[] indices = [0]
+            }
+
+            def GeomSubset "glass_window" (
+                customData = { string area = "glass window" }
+            )
+            {
+                uniform token elementType = "face"
+                uniform token familyName = "materialBind"
+                int[] indices = [1]
+            }
+        }
+    }
+}
+"#);
+  }
+
+  #[test]
+  fn test_export_skeleton_and_blend_shapes() {
+    let mesh = builder()
+      .joints(&[[0, 0, 0, 0], [1, 0, 0, 0], [0, 1, 0, 0], [1, 0, 0, 0]], &[[1.0, 0.0, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0], [0.5, 0.5, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0]])
+      .bone_bindings(&["turret".to_owned(), "root bone".to_owned()])
+      .build();
+
+    let points = builder().submesh("raise", index::Geometry::Points, &[2]).build();
+
+    let raise = Mesh {
+      submeshes: vec![points.submeshes[2].clone()],
+      .. MeshBuilder::new("raise").positions(&[[0.0, 2.0, 0.0]]).normals(&[[0.0, 1.0, 0.0]]).build()
+    };
+
+    let bones = vec![bone("root bone", None, [0.0, 0.0, 0.0]), bone("turret", Some(0), [0.0, 1.0, 0.0])];
+
+    let usda = export_usda(&asset(mesh, vec![raise], bones));
+
+    assert!(usda.contains("    def SkelRoot \"hull\"\n"));
+    assert!(usda.contains("        def Mesh \"hull\" (\n            prepend apiSchemas = [\"SkelBindingAPI\"]\n        )\n"));
+    assert!(usda.contains("            int[] primvars:skel:jointIndices = [0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0] (\n                elementSize = 4\n"));
+    assert!(usda.contains("            float[] primvars:skel:jointWeights = [1, 0, 0, 0, 1, 0, 0, 0, 0.5, 0.5, 0, 0, 1, 0, 0, 0] (\n"));
+    assert!(usda.contains("            uniform token[] skel:blendShapes = [\"raise\"]\n"));
+    assert!(usda.contains("            rel skel:blendShapeTargets = [</hull/hull/hull/raise>]\n"));
+    assert!(usda.contains("            uniform token[] skel:joints = [\"root_bone/turret\", \"root_bone\"]\n"));
+    assert!(usda.contains("            rel skel:skeleton = </hull/hull/skeleton>\n"));
+
+    // Offsets from the mesh, not the new positions
+    assert!(usda.contains("                uniform vector3f[] offsets = [(0, 1, 0)]\n"));
+    assert!(usda.contains("                uniform vector3f[] normalOffsets = [(0, 1, 1)]\n"));
+    assert!(usda.contains("                uniform int[] pointIndices = [2]\n"));
+
+    assert!(usda.contains("            uniform token[] joints = [\"root_bone\", \"root_bone/turret\"]\n"));
+    assert!(usda.contains("( (1, 0, 0, 0), (0, 1, 0, 0), (0, 0, 1, 0), (0, 1, 0, 1) )]\n"));
+  }
+
+  #[test]
+  fn test_export_blend_shapes_without_skeleton() {
+    let points = builder().submesh("raise", index::Geometry::Points, &[2]).build();
+
+    let raise = Mesh {
+      submeshes: vec![points.submeshes[2].clone()],
+      .. MeshBuilder::new("raise").positions(&[[0.0, 2.0, 0.0]]).build()
+    };
+
+    let usda = export_usda(&asset(builder().build(), vec![raise], Vec::new()));
+
+    assert!(usda.contains("        def Mesh \"hull\" (\n            prepend apiSchemas = [\"SkelBindingAPI\"]\n        )\n"));
+    assert!(usda.contains("            rel skel:skeleton = </hull/hull/skeleton>\n"));
+    assert!(usda.contains("        def Skeleton \"skeleton\"\n        {\n            uniform matrix4d[] bindTransforms = []\n            uniform token[] joints = []\n"));
+    assert!(!usda.contains("skel:joints"));
+  }
+}
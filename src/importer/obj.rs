@@ -0,0 +1,306 @@
+use std;
+use std::path;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use asset;
+use index;
+use model;
+
+use animation::skeleton;
+use buffer::Buffer;
+use mesh::{Mesh, MeshBuilder};
+
+// Positions, texture coordinates and normals of a face corner, as indices into the whole file
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct Object {
+  name: String,
+  areas: Vec<(String, Vec<[Corner; 3]>)>
+}
+
+// Every `o` becomes a model. Areas are named after their material, or their group without one,
+// and polygons are split into fans of triangles. Lines that cannot be read are skipped, along with
+// the faces using a vertex from one, without changing how the vertices after it are numbered
+pub fn import(input: Arc<Buffer>) -> Option<asset::Asset> {
+  let text = match std::str::from_utf8(&input[..]) {
+    Ok(text) => text, Err(_) => return None
+  };
+
+  let default_name = input.uri.as_ref()
+    .and_then(|x| path::Path::new(x).file_stem().and_then(|x| x.to_str()).map(|x| x.to_owned()))
+    .unwrap_or("obj".to_owned());
+
+  let mut positions: Vec<Option<[f32; 3]>> = Vec::new();
+  let mut texture_coordinates: Vec<Option<[f32; 2]>> = Vec::new();
+  let mut normals: Vec<Option<[f32; 3]>> = Vec::new();
+
+  let mut objects = vec![Object { name: default_name, areas: Vec::new() }];
+
+  let mut group = "default".to_owned();
+  let mut material: Option<String> = None;
+
+  for line in text.lines() {
+    let line = match line.find('#') { Some(i) => &line[.. i], None => line };
+    let mut parts = line.split_whitespace();
+
+    let keyword = match parts.next() {
+      Some(keyword) => keyword, None => continue
+    };
+
+    let arguments: Vec<&str> = parts.collect();
+
+    match keyword {
+      "v" => positions.push(floats(&arguments, 3).map(|v| [v[0], v[1], v[2]])),
+      "vt" => texture_coordinates.push(floats(&arguments, 1).map(|v| [v[0], if v.len() > 1 { v[1] } else { 0.0 }])),
+      "vn" => normals.push(floats(&arguments, 3).map(|v| [v[0], v[1], v[2]])),
+      "o" => {
+        let name = arguments.join(" ");
+
+        if objects.last().unwrap().areas.is_empty() {
+          objects.last_mut().unwrap().name = name;
+        } else {
+          objects.push(Object { name: name, areas: Vec::new() });
+        }
+      },
+      "g" => group = if arguments.is_empty() { "default".to_owned() } else { arguments.join(" ") },
+      "usemtl" => material = if arguments.is_empty() { None } else { Some(arguments.join(" ")) },
+      "f" => {
+        let corners = match arguments.iter().map(|x| corner(x, &positions, &texture_coordinates, &normals)).collect::<Option<Vec<Corner>>>() {
+          Some(corners) => corners, None => continue
+        };
+
+        if corners.len() < 3 {
+          continue;
+        }
+
+        let area_name = material.clone().unwrap_or(group.clone());
+        let areas = &mut objects.last_mut().unwrap().areas;
+
+        let area = match areas.iter().position(|x| x.0 == area_name) {
+          Some(area) => area,
+          None => { areas.push((area_name, Vec::new())); areas.len() - 1 }
+        };
+
+        for i in 1 .. corners.len() - 1 {
+          areas[area].1.push([corners[0], corners[i], corners[i + 1]]);
+        }
+      },
+      _ => () // Lines, curves, smoothing groups and the like are not supported
+    }
+  }
+
+  let objects = objects.iter().filter(|x| !x.areas.is_empty()).map(|object| {
+    let mesh = build_mesh(object, &positions, &texture_coordinates, &normals);
+
+    asset::Object::Model(model::Model {
+      name: object.name.clone(),
+      mesh: mesh,
+      blend_shapes: Vec::new(),
+      lods: Vec::new(),
+      skeleton: skeleton::Skeleton { bones: Vec::new() }
+    })
+  }).collect();
+
+  return Some(asset::Asset { buffers: Vec::new(), objects: objects });
+}
+
+// Every distinct combination of position, texture coordinate and normal becomes a vertex. Corners
+// without a texture coordinate or normal get zeroes when others in the object have one. Corners only
+// use vertices that were read
+fn build_mesh(object: &Object, positions: &[Option<[f32; 3]>], texture_coordinates: &[Option<[f32; 2]>], normals: &[Option<[f32; 3]>]) -> Mesh {
+  let mut vertices: Vec<Corner> = Vec::new();
+  let mut lookup: HashMap<Corner, u32> = HashMap::new();
+
+  let mut builder = MeshBuilder::new(&object.name);
+  let mut areas = Vec::new();
+
+  for &(ref name, ref triangles) in &object.areas {
+    let mut indices = Vec::new();
+
+    for triangle in triangles {
+      for corner in triangle {
+        let next = vertices.len() as u32;
+        let index = *lookup.entry(*corner).or_insert(next);
+
+        if index == next {
+          vertices.push(*corner);
+        }
+
+        indices.push(index);
+      }
+    }
+
+    areas.push((name.clone(), indices));
+  }
+
+  builder.positions(&vertices.iter().map(|x| positions[x.0].unwrap()).collect::<Vec<_>>());
+
+  if vertices.iter().any(|x| x.1.is_some()) {
+    builder.texture_coordinates(&vertices.iter().map(|x| x.1.map_or([0.0, 0.0], |t| texture_coordinates[t].unwrap())).collect::<Vec<_>>());
+  }
+
+  if vertices.iter().any(|x| x.2.is_some()) {
+    builder.normals(&vertices.iter().map(|x| x.2.map_or([0.0, 0.0, 0.0], |n| normals[n].unwrap())).collect::<Vec<_>>());
+  }
+
+  for (name, indices) in areas {
+    builder.submesh(&name, index::Geometry::Triangles, &indices);
+  }
+
+  return builder.build();
+}
+
+fn floats(arguments: &[&str], minimum: usize) -> Option<Vec<f32>> {
+  if arguments.len() < minimum {
+    return None;
+  }
+
+  let mut result = Vec::new();
+
+  for argument in arguments {
+    match argument.parse::<f32>() {
+      Ok(x) => result.push(x), Err(_) => return None
+    }
+  }
+
+  return Some(result);
+}
+
+// v, v/vt, v//vn or v/vt/vn, each one counting from 1, or backwards from the last one when negative.
+// None when the corner is invalid or uses a vertex that could not be read
+fn corner(argument: &str, positions: &[Option<[f32; 3]>], texture_coordinates: &[Option<[f32; 2]>], normals: &[Option<[f32; 3]>]) -> Option<Corner> {
+  let parts: Vec<&str> = argument.split('/').collect();
+
+  if parts.len() > 3 {
+    return None;
+  }
+
+  let position = match resolve(parts[0], positions.len()) {
+    Some(Some(position)) if positions[position].is_some() => position, _ => return None
+  };
+
+  let texture_coordinate = if parts.len() > 1 { resolve(parts[1], texture_coordinates.len()) } else { Some(None) };
+  let normal = if parts.len() > 2 { resolve(parts[2], normals.len()) } else { Some(None) };
+
+  return match (texture_coordinate, normal) {
+    (Some(t), Some(n)) if t.map_or(true, |t| texture_coordinates[t].is_some()) && n.map_or(true, |n| normals[n].is_some()) => Some((position, t, n)),
+    _ => None
+  };
+}
+
+// None when the index is invalid, Some(None) when it is left out
+fn resolve(part: &str, count: usize) -> Option<Option<usize>> {
+  if part.is_empty() {
+    return Some(None);
+  }
+
+  let i = match part.parse::<i64>() {
+    Ok(i) => i, Err(_) => return None
+  };
+
+  let resolved = if i < 0 { count as i64 + i } else { i - 1 };
+
+  if resolved < 0 || resolved >= count as i64 {
+    return None;
+  }
+
+  return Some(Some(resolved as usize));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use asset;
+  use vertex;
+
+  use buffer::Buffer;
+
+  fn import_str(text: &str) -> Option<asset::Asset> {
+    return import(Buffer::new(Some("res:/dx9/model/hull.obj".to_owned()), None, text.as_bytes().to_vec()));
+  }
+
+  fn model(asset: &asset::Asset, i: usize) -> &model::Model {
+    return match asset.objects[i] {
+      asset::Object::Model(ref m) => m,
+      _ => panic!("Not a model")
+    };
+  }
+
+  #[test]
+  fn test_import() {
+    let asset = import_str("
+      # A quad and a triangle
+      v 0 0 0
+      v 1 0 0
+      v 1 1 0
+      v 0 1 0
+      vt 0 0
+      vt 1 1
+      vn 0 0 1
+      g hull
+      f 1/1/1 2/1/1 3/2/1 4/2/1
+      usemtl glass
+      f -4//1 -3//1 -1//1
+    ").unwrap();
+
+    assert_eq!(asset.objects.len(), 1);
+
+    let mesh = &model(&asset, 0).mesh;
+
+    assert_eq!(mesh.name, "hull");
+    assert_eq!(mesh.submeshes.len(), 2);
+    assert_eq!(mesh.submeshes[0].name, "hull");
+    assert_eq!(mesh.submeshes[1].name, "glass");
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 1, 2], vec![0, 2, 3]]);
+
+    // The glass corners have no texture coordinates, so they are separate vertices
+    assert_eq!(mesh.vertex_count, 7);
+    assert_eq!(mesh.positions().unwrap()[6], [0.0, 1.0, 0.0]);
+    assert_eq!(mesh.untyped_view_for(&vertex::AttributeName::TextureCoordinate).unwrap().get_f32(2), &[1.0, 1.0]);
+  }
+
+  #[test]
+  fn test_import_objects() {
+    let asset = import_str("
+      v 0 0 0
+      v 1 0 0
+      v 0 1 0
+      o frigate
+      f 1 2 3
+      o cruiser
+      f 3 2 1
+    ").unwrap();
+
+    assert_eq!(asset.objects.len(), 2);
+    assert_eq!(model(&asset, 1).name, "cruiser");
+    assert_eq!(model(&asset, 1).mesh.submeshes[0].name, "default");
+    assert!(model(&asset, 1).mesh.attribute_for(&vertex::AttributeName::Normal).is_none());
+  }
+
+  #[test]
+  fn test_import_malformed() {
+    let asset = import_str("
+      v 0 0 0
+      v 1 0 0
+      v 0 zero 0
+      v 0 1 0
+      f 1 2 4
+      f 1 2 3
+      f 1 2
+      f 1 2 9
+      f 1 2 four
+    ").unwrap();
+
+    // The unreadable position still counts, so the one after it is the fourth
+    let mesh = &model(&asset, 0).mesh;
+
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 1, 2]]);
+    assert_eq!(mesh.positions().unwrap()[2], [0.0, 1.0, 0.0]);
+
+    assert_eq!(import_str("v 0 0 0\nf 1 2 3\n").unwrap().objects.len(), 0);
+    assert!(import(Buffer::new(None, None, vec![0xFF, 0xFE])).is_none());
+  }
+}
@@ -2,29 +2,31 @@ use std::io::Read;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
-use animation::sampler;
+use animation::{animation, sampler};
 
 use importer::wbg::read_string;
 
-pub fn read_animation(cursor: &mut Read) {
-  let _ = read_string(cursor);
-  let _ = cursor.read_f32::<LittleEndian>().unwrap();
+pub fn read_animation(cursor: &mut Read) -> animation::Animation {
+  let name = read_string(cursor);
+  let duration = cursor.read_f32::<LittleEndian>().unwrap();
 
   let group_count = cursor.read_u8().unwrap();
 
-  (0 .. group_count).map(|_| { read_group(cursor) }).last();
+  let tracks = (0 .. group_count).fold(vec![], |mut sum, _| { sum.extend(read_group(cursor).into_iter()); sum });
+
+  return animation::Animation { name: name, duration: duration, tracks: tracks };
 }
 
-fn read_group(cursor: &mut Read) -> Vec<(String, (Option<sampler::Sampler>, Option<sampler::Sampler>, Option<sampler::Sampler>))> {
+fn read_group(cursor: &mut Read) -> Vec<animation::TransformTrack> {
   let name = read_string(cursor);
 
   let transform_track = cursor.read_u8().unwrap();
 
-  return (0 .. transform_track).map(|_| { (name.clone(), read_transform_track(cursor)) }).collect();
+  return (0 .. transform_track).map(|_| { read_transform_track(cursor, &name) }).collect();
 }
 
-fn read_transform_track(cursor: &mut Read) -> (Option<sampler::Sampler>, Option<sampler::Sampler>, Option<sampler::Sampler>) {
-  let _ = read_string(cursor);
+fn read_transform_track(cursor: &mut Read, group: &str) -> animation::TransformTrack {
+  let bone = read_string(cursor);
 
   let mut orientation = read_curves(cursor);
   let position = read_curves(cursor);
@@ -40,7 +42,14 @@ fn read_transform_track(cursor: &mut Read) -> (Option<sampler::Sampler>, Option<
     }
   }
 
-  return (orientation, position, scale_shear);
+  return animation::TransformTrack {
+    group: group.to_owned(),
+    bone: bone,
+
+    orientation: orientation,
+    position: position,
+    scale_shear: scale_shear
+  };
 }
 
 fn read_curves(cursor: &mut Read) -> Option<sampler::Sampler> {
@@ -31,7 +31,9 @@ fn read_blend_shape(cursor: &mut Cursor<&[u8]>, buffer: Arc<buffer::Buffer>) ->
       view: index_buffer,
       index_count: index_count,
       index_format: index_format,
-      geometry: index::Geometry::Points
-    }]
+      geometry: index::Geometry::Points,
+      primitive_restart: false
+    }],
+    bone_bindings: Vec::new()
   };
 }
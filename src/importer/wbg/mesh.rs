@@ -26,7 +26,8 @@ pub fn read_mesh(cursor: &mut Cursor<&[u8]>, buffer: Arc<buffer::Buffer>) -> mes
     vertex_count: vertex_count,
     descriptor: descriptor,
     buffers: vec![vertex_buffer],
-    submeshes: submeshes
+    submeshes: submeshes,
+    bone_bindings: Vec::new()
   };
 }
 
@@ -63,6 +64,7 @@ fn read_mesh_area(cursor: &mut Cursor<&[u8]>, index_buffer: &Arc<buffer::BufferV
     view: view,
     index_count: index_count,
     index_format: index_format,
-    geometry: index::Geometry::Triangles
+    geometry: index::Geometry::Triangles,
+    primitive_restart: false
   };
 }
@@ -34,8 +34,8 @@ pub fn import(input: Arc<Buffer>) -> Option<asset::Asset> {
   let mesh_count = cursor.read_u8().unwrap();
 
   let meshes: Vec<(Mesh, Vec<Mesh>)> = (0 .. mesh_count).map(|_| {
-    let mesh = mesh::read_mesh(&mut cursor, input.clone());
-    let _ = bone_bindings::read_bone_bindings(&mut cursor);
+    let mut mesh = mesh::read_mesh(&mut cursor, input.clone());
+    mesh.bone_bindings = bone_bindings::read_bone_bindings(&mut cursor);
     let blend_shapes = blend_shape::read_blend_shapes(&mut cursor, input.clone());
 
     (mesh, blend_shapes)
@@ -56,6 +56,7 @@ pub fn import(input: Arc<Buffer>) -> Option<asset::Asset> {
         name: name.clone(),
         mesh: mesh.clone(),
         blend_shapes: blend_shapes.clone(),
+        lods: Vec::new(),
         skeleton: skeleton.clone()
       })
     }).collect()
@@ -63,11 +64,13 @@ pub fn import(input: Arc<Buffer>) -> Option<asset::Asset> {
 
   let animation_count = cursor.read_u8().unwrap();
 
-  (0 .. animation_count).map(|_| {
-    animation::read_animation(&mut cursor)
-  }).last();
+  let animations: Vec<asset::Object> = (0 .. animation_count).map(|_| {
+    asset::Object::Animation(animation::read_animation(&mut cursor))
+  }).collect();
+
+  let mut objects = models.iter().fold(vec![], |mut sum, x| { sum.push_all(&*x); sum });
 
-  let objects = models.iter().fold(vec![], |mut sum, x| { sum.push_all(&*x); sum });
+  objects.push_all(&animations);
 
   return Some(asset::Asset { buffers: vec![input.clone()], objects: objects });
 }
@@ -1,5 +1,10 @@
 #![allow(non_camel_case_types)]
 
+use std;
+
+// Marks a primitive restart in index lists handed to mesh::MeshBuilder
+pub const RESTART_INDEX: u32 = 0xFFFFFFFF;
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Format {
   u16, u32
@@ -12,12 +17,30 @@ impl Format {
       Format::u32 => 4
     };
   }
+
+  pub fn restart_index(&self) -> usize {
+    return match *self {
+      Format::u16 => std::u16::MAX as usize,
+      Format::u32 => std::u32::MAX as usize
+    };
+  }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Geometry {
   Points,
   Lines,
+  LineStrips,
   Triangles,
-  TriangleStrips
+  TriangleStrips,
+  TriangleFans
+}
+
+impl Geometry {
+  pub fn is_triangles(&self) -> bool {
+    return match *self {
+      Geometry::Triangles | Geometry::TriangleStrips | Geometry::TriangleFans => true,
+      _ => false
+    };
+  }
 }
@@ -5,6 +5,7 @@
 #![feature(link_llvm_intrinsics)]
 
 extern crate xml;
+extern crate memmap;
 extern crate hyper;
 extern crate eventual;
 extern crate byteorder;
@@ -14,6 +15,7 @@ pub mod resource_manager;
 pub mod resource_loaders;
 
 pub mod buffer;
+pub mod math;
 
 pub mod index;
 pub mod vertex;
@@ -23,14 +25,14 @@ pub mod asset;
 pub mod model;
 
 pub mod animation {
+  pub mod animation;
   pub mod sampler;
   pub mod skeleton;
 }
 
 pub mod importer {
+  pub mod obj;
   pub mod wbg;
 }
 
-pub mod exporter {
-  pub mod obj;
-}
+pub mod exporter;
@@ -3,12 +3,14 @@
 extern crate engine;
 extern crate getopts;
 
-use std::fs;
 use std::env;
+use std::fs;
 
-use std::io::Write;
+use std::path::Path;
 use std::sync::Arc;
 
+use engine::exporter::{Exporter, Output};
+
 fn main() {
   let resource_manager = engine::resource_manager::ResourceManager::default();
 
@@ -20,16 +22,59 @@ fn main() {
     "hulls" => {
       println!("{}", sof.hulls().join("\n"));
     },
+    "formats" => {
+      let exporters = engine::exporter::Exporters::default();
+
+      for name in exporters.names() {
+        let exporter = exporters.get(name).unwrap();
+        let options: Vec<String> = exporter.options().iter().map(|x| format!("{}={}", x.0, x.1)).collect();
+
+        println!("{}", format!("{} (.{}) {}", name, exporter.extensions().join(", ."), options.join(" ")).trim_right());
+      }
+    },
     "export" => {
-      for i in 3 .. args.len() {
-        export(&mut sof, resource_manager.clone(), &args[i], &args[2]);
+      let mut options = getopts::Options::new();
+
+      options.optopt("f", "format", "Format to export to, obj when left out", "FORMAT");
+      options.optmulti("o", "option", "Option of the exporter", "NAME=VALUE");
+      options.optflag("z", "zip", "Write a zip archive for every hull rather than a directory");
+
+      let matches = match options.parse(&args[2 ..]) {
+        Ok(m) => m, Err(e) => panic!("{}", e)
+      };
+
+      let mut exporters = engine::exporter::Exporters::default();
+
+      let names = exporters.names().join(", ");
+      let format = matches.opt_str("f").unwrap_or("obj".to_owned());
+
+      let exporter = match exporters.get_mut(&format) {
+        Some(exporter) => exporter, None => panic!("Unknown format {}, expected one of {}", format, names)
+      };
+
+      for option in matches.opt_strs("o") {
+        let mut split = option.splitn(2, '=');
+        let (name, value) = (split.next().unwrap(), split.next().unwrap_or(""));
+
+        if let Err(e) = exporter.set_option(name, value) {
+          panic!("{}", e);
+        }
+      }
+
+      for i in 1 .. matches.free.len() {
+        export(&mut sof, resource_manager.clone(), &matches.free[i], &matches.free[0], exporter, matches.opt_present("z"));
+      }
+    }
+    "validate" => {
+      for i in 2 .. args.len() {
+        validate(&mut sof, resource_manager.clone(), &args[i]);
       }
     }
     cmd => panic!("Unknown command {}", cmd)
   }
 }
 
-fn export(sof: &mut engine::resource_manager::sof::Manager, resource_manager: Arc<engine::resource_manager::ResourceManager>, arg: &String, destination: &String) {
+fn load(sof: &mut engine::resource_manager::sof::Manager, resource_manager: Arc<engine::resource_manager::ResourceManager>, arg: &String) -> Option<Arc<engine::resource_manager::Resource>> {
   let geometry_path = if arg.contains(":/") {
     arg.as_str()
   } else {
@@ -37,29 +82,69 @@ fn export(sof: &mut engine::resource_manager::sof::Manager, resource_manager: Ar
       None => {
         println!("No such hull {}", arg);
 
-        return;
+        return None;
       }
       Some(s) => s
     }.geometry_path()
   };
 
-  let geometry = resource_manager.load(geometry_path).unwrap();
+  return Some(resource_manager.load(geometry_path).unwrap());
+}
+
+fn validate(sof: &mut engine::resource_manager::sof::Manager, resource_manager: Arc<engine::resource_manager::ResourceManager>, arg: &String) {
+  let geometry = match load(sof, resource_manager, arg) {
+    Some(geometry) => geometry, None => return
+  };
 
-  let objs = match *geometry {
-    engine::resource_manager::Resource::Asset(ref m) => engine::exporter::obj::export(m),
-    _ => panic!("{:?} is not an asset, sorry.", geometry_path)
+  let asset = match *geometry {
+    engine::resource_manager::Resource::Asset(ref m) => m,
+    _ => panic!("{:?} is not an asset, sorry.", arg)
   };
 
-  for (path, obj) in objs {
-    let dirpath = format!("{}/{}", &destination, arg);
+  for object in &asset.objects {
+    match object {
+      &engine::asset::Object::Model(ref m) => {
+        let report = m.mesh.validate();
 
-    fs::create_dir_all(&dirpath).unwrap();
+        println!("{} ({}): {} problems", arg, m.mesh.name, report.problems.len());
 
-    let filename = format!("{}/{}", dirpath, path);
-    println!("Writing {} (to {})", path, filename);
+        for problem in &report.problems {
+          println!("  {:?}", problem);
+        }
+      }
+      _ => ()
+    }
+  }
+}
+
+fn export(sof: &mut engine::resource_manager::sof::Manager, resource_manager: Arc<engine::resource_manager::ResourceManager>, arg: &String, destination: &String, exporter: &Exporter, zip: bool) {
+  let geometry = match load(sof, resource_manager, arg) {
+    Some(geometry) => geometry, None => return
+  };
+
+  let asset = match *geometry {
+    engine::resource_manager::Resource::Asset(ref m) => m,
+    _ => panic!("{:?} is not an asset, sorry.", arg)
+  };
+
+  let dirpath = format!("{}/{}", &destination, arg);
+
+  if zip {
+    let zippath = format!("{}.zip", dirpath);
+    println!("Writing {} as {} (to {})", arg, exporter.name(), zippath);
+
+    fs::create_dir_all(Path::new(&zippath).parent().unwrap()).unwrap();
+
+    let mut output = engine::exporter::output::Zip::new(fs::File::create(&zippath).unwrap());
+
+    exporter.export(asset, &mut output).unwrap();
+    output.into_inner().unwrap();
+  } else {
+    println!("Writing {} as {} (to {})", arg, exporter.name(), dirpath);
 
-    let mut f = fs::File::create(filename).unwrap();
+    let mut output = engine::exporter::output::Directory::new(dirpath.as_str());
 
-    f.write_all(obj.as_slice()).unwrap();
+    exporter.export(asset, &mut output).unwrap();
+    output.finish().unwrap();
   }
 }
@@ -0,0 +1,228 @@
+use std::mem;
+
+pub type Vector3 = [f32; 3];
+pub type Matrix4 = [[f32; 4]; 4]; // Rows, points are column vectors multiplied on the right
+
+#[inline(always)]
+pub fn add(a: Vector3, b: Vector3) -> Vector3 {
+  return [a[0] + b[0], a[1] + b[1], a[2] + b[2]];
+}
+
+#[inline(always)]
+pub fn sub(a: Vector3, b: Vector3) -> Vector3 {
+  return [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+}
+
+#[inline(always)]
+pub fn scale(a: Vector3, s: f32) -> Vector3 {
+  return [a[0] * s, a[1] * s, a[2] * s];
+}
+
+#[inline(always)]
+pub fn dot(a: Vector3, b: Vector3) -> f32 {
+  return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+#[inline(always)]
+pub fn cross(a: Vector3, b: Vector3) -> Vector3 {
+  return [
+    a[1] * b[2] - a[2] * b[1],
+    a[2] * b[0] - a[0] * b[2],
+    a[0] * b[1] - a[1] * b[0]
+  ];
+}
+
+#[inline(always)]
+pub fn length(a: Vector3) -> f32 {
+  return dot(a, a).sqrt();
+}
+
+// Zero length vectors stay zero instead of turning into NaN
+pub fn normalize(a: Vector3) -> Vector3 {
+  let length = length(a);
+
+  if length == 0.0 {
+    return a;
+  }
+
+  return scale(a, 1.0 / length);
+}
+
+pub fn angle(a: Vector3, b: Vector3) -> f32 {
+  let lengths = length(a) * length(b);
+
+  if lengths == 0.0 {
+    return 0.0;
+  }
+
+  return (dot(a, b) / lengths).max(-1.0).min(1.0).acos();
+}
+
+pub fn to_vector3(values: &[f32]) -> Vector3 {
+  let value = |i: usize| if i < values.len() { values[i] } else { 0.0 };
+
+  return [value(0), value(1), value(2)];
+}
+
+// The bits of a vector, for hashing. Adding zero turns -0.0 into 0.0 so both give the same key
+#[inline(always)]
+pub fn position_key(position: Vector3) -> [u32; 3] {
+  return unsafe { mem::transmute([position[0] + 0.0, position[1] + 0.0, position[2] + 0.0]) };
+}
+
+pub fn identity() -> Matrix4 {
+  return scaling(1.0);
+}
+
+pub fn scaling(s: f32) -> Matrix4 {
+  return [[s, 0.0, 0.0, 0.0], [0.0, s, 0.0, 0.0], [0.0, 0.0, s, 0.0], [0.0, 0.0, 0.0, 1.0]];
+}
+
+pub fn multiply(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+  let mut m = [[0.0; 4]; 4];
+
+  for r in 0 .. 4 {
+    for c in 0 .. 4 {
+      m[r][c] = (0 .. 4).fold(0.0, |sum, i| sum + a[r][i] * b[i][c]);
+    }
+  }
+
+  return m;
+}
+
+pub fn transpose(a: &Matrix4) -> Matrix4 {
+  let mut m = [[0.0; 4]; 4];
+
+  for r in 0 .. 4 {
+    for c in 0 .. 4 {
+      m[r][c] = a[c][r];
+    }
+  }
+
+  return m;
+}
+
+pub fn transform_point(m: &Matrix4, p: Vector3) -> Vector3 {
+  let w = m[3][0] * p[0] + m[3][1] * p[1] + m[3][2] * p[2] + m[3][3];
+  let p = add(transform_vector(m, p), [m[0][3], m[1][3], m[2][3]]);
+
+  return if w == 1.0 || w == 0.0 { p } else { scale(p, 1.0 / w) };
+}
+
+// Only the upper 3x3 part applies to directions
+pub fn transform_vector(m: &Matrix4, v: Vector3) -> Vector3 {
+  return [dot([m[0][0], m[0][1], m[0][2]], v), dot([m[1][0], m[1][1], m[1][2]], v), dot([m[2][0], m[2][1], m[2][2]], v)];
+}
+
+pub fn determinant3(m: &Matrix4) -> f32 {
+  return dot([m[0][0], m[0][1], m[0][2]], cross([m[1][0], m[1][1], m[1][2]], [m[2][0], m[2][1], m[2][2]]));
+}
+
+// The inverse-transpose of the upper 3x3 part, which keeps normals perpendicular to their surface
+pub fn inverse_transpose3(m: &Matrix4) -> Matrix4 {
+  let rows = [[m[0][0], m[0][1], m[0][2]], [m[1][0], m[1][1], m[1][2]], [m[2][0], m[2][1], m[2][2]]];
+  let determinant = determinant3(m);
+
+  // Rows of the cofactor matrix
+  let c = [cross(rows[1], rows[2]), cross(rows[2], rows[0]), cross(rows[0], rows[1])];
+  let s = if determinant == 0.0 { 0.0 } else { 1.0 / determinant };
+
+  return [
+    [c[0][0] * s, c[0][1] * s, c[0][2] * s, 0.0],
+    [c[1][0] * s, c[1][1] * s, c[1][2] * s, 0.0],
+    [c[2][0] * s, c[2][1] * s, c[2][2] * s, 0.0],
+    [0.0, 0.0, 0.0, 1.0]
+  ];
+}
+
+// Rotation by a unit quaternion stored as [x, y, z, w]
+pub fn rotation(q: [f32; 4]) -> Matrix4 {
+  let (x, y, z, w) = (q[0], q[1], q[2], q[3]);
+
+  return [
+    [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w), 0.0],
+    [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w), 0.0],
+    [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y), 0.0],
+    [0.0, 0.0, 0.0, 1.0]
+  ];
+}
+
+// The identity for a zero quaternion
+pub fn normalize_quaternion(q: [f32; 4]) -> [f32; 4] {
+  let length = (q[0] * q[0] + q[1] * q[1] + q[2] * q[2] + q[3] * q[3]).sqrt();
+
+  if length == 0.0 {
+    return [0.0, 0.0, 0.0, 1.0];
+  }
+
+  return [q[0] / length, q[1] / length, q[2] / length, q[3] / length];
+}
+
+// Scales first, then rotates, then translates
+pub fn compose(translation: Vector3, q: [f32; 4], s: Vector3) -> Matrix4 {
+  let mut m = rotation(q);
+
+  for r in 0 .. 3 {
+    for c in 0 .. 3 {
+      m[r][c] *= s[c];
+    }
+
+    m[r][3] = translation[r];
+  }
+
+  return m;
+}
+
+// Only valid for matrices without a projective part
+pub fn inverse_affine(m: &Matrix4) -> Matrix4 {
+  let mut inverse = transpose(&inverse_transpose3(m));
+  let translation = transform_vector(&inverse, [m[0][3], m[1][3], m[2][3]]);
+
+  for r in 0 .. 3 {
+    inverse[r][3] = -translation[r];
+  }
+
+  return inverse;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+  #[test]
+  fn test_cross() {
+    assert_eq!(cross([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]), [0.0, 0.0, 1.0]);
+  }
+
+  #[test]
+  fn test_normalize() {
+    assert_eq!(normalize([3.0, 0.0, 4.0]), [0.6, 0.0, 0.8]);
+    assert_eq!(normalize([0.0, 0.0, 0.0]), [0.0, 0.0, 0.0]);
+  }
+
+  #[test]
+  fn test_inverse_transpose() {
+    let m = [[2.0, 0.0, 0.0, 5.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, -4.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+    assert_eq!(determinant3(&m), -8.0);
+    assert_eq!(transform_point(&m, [1.0, 1.0, 1.0]), [7.0, 1.0, -4.0]);
+    assert_eq!(transform_vector(&inverse_transpose3(&m), [1.0, 1.0, 1.0]), [0.5, 1.0, -0.25]);
+    assert_eq!(multiply(&m, &identity()), m);
+  }
+
+  #[test]
+  fn test_inverse_affine() {
+    let m = compose([1.0, 2.0, 3.0], [0.0, 0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2], [2.0, 2.0, 2.0]);
+    let p = transform_point(&m, [1.0, 0.0, 0.0]);
+
+    assert!(length(sub(p, [1.0, 4.0, 3.0])) < 1e-5);
+    assert!(length(sub(transform_point(&inverse_affine(&m), p), [1.0, 0.0, 0.0])) < 1e-5);
+  }
+
+  #[test]
+  fn test_angle() {
+    assert_eq!(angle([1.0, 0.0, 0.0], [0.0, 2.0, 0.0]), PI / 2.0);
+  }
+}
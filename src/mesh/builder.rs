@@ -0,0 +1,486 @@
+use std;
+use std::mem;
+
+use buffer::{BufferView, BufferWriter, ScalarValue};
+
+use index;
+use vertex;
+
+use mesh::{Mesh, Submesh};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Layout {
+  Interleaved,
+  Separate,
+  Grouped(Vec<usize>) // Buffer index for each stream
+}
+
+#[derive(Debug, Clone)]
+pub struct Stream {
+  pub name: vertex::AttributeName,
+  pub format: vertex::Format,
+  pub values: Vec<ScalarValue>
+}
+
+#[derive(Debug, Clone)]
+pub struct Area {
+  pub name: String,
+  pub geometry: index::Geometry,
+  pub indices: Vec<u32> // index::RESTART_INDEX restarts the primitive
+}
+
+#[derive(Debug, Clone)]
+pub struct MeshBuilder {
+  pub name: String,
+  pub layout: Layout,
+  pub index_format: Option<index::Format>,
+  pub streams: Vec<Stream>,
+  pub areas: Vec<Area>,
+  pub bone_bindings: Vec<String>
+}
+
+impl MeshBuilder {
+  pub fn new(name: &str) -> MeshBuilder {
+    return MeshBuilder {
+      name: name.to_owned(),
+      layout: Layout::Interleaved,
+      index_format: None,
+      streams: Vec::new(),
+      areas: Vec::new(),
+      bone_bindings: Vec::new()
+    };
+  }
+
+  pub fn from_mesh(mesh: &Mesh) -> MeshBuilder {
+    let streams = mesh.descriptor.attributes.iter().map(|attribute| {
+      let view = mesh.untyped_view_for_attribute(attribute);
+
+      Stream {
+        name: attribute.name.clone(),
+        format: attribute.format,
+        values: (0 .. mesh.vertex_count).flat_map(|i| view.get_vector_value(i).into_iter()).collect()
+      }
+    }).collect();
+
+    let areas = mesh.submeshes.iter().map(|submesh| {
+      let view = submesh.untyped_view();
+      let restart = submesh.index_format.restart_index();
+
+      Area {
+        name: submesh.name.clone(),
+        geometry: submesh.geometry,
+        indices: (0 .. submesh.index_count).map(|i| {
+          match view.get_usize(i) {
+            x if submesh.primitive_restart && x == restart => index::RESTART_INDEX,
+            x => x as u32
+          }
+        }).collect()
+      }
+    }).collect();
+
+    return MeshBuilder {
+      name: mesh.name.clone(),
+      layout: Layout::Grouped(mesh.descriptor.attributes.iter().map(|x| x.buffer_index).collect()),
+      index_format: mesh.submeshes.first().map(|x| x.index_format),
+      streams: streams,
+      areas: areas,
+      bone_bindings: mesh.bone_bindings.clone()
+    };
+  }
+
+  pub fn layout(&mut self, layout: Layout) -> &mut MeshBuilder {
+    self.layout = layout;
+
+    return self;
+  }
+
+  pub fn index_format(&mut self, index_format: index::Format) -> &mut MeshBuilder {
+    self.index_format = Some(index_format);
+
+    return self;
+  }
+
+  pub fn attribute(&mut self, name: vertex::AttributeName, format: vertex::Format, values: &[f32]) -> &mut MeshBuilder {
+    let values = values.chunks(format.elements()).flat_map(|x| ScalarValue::from_f32_vector(format, x).into_iter()).collect();
+
+    return self.attribute_values(name, format, values);
+  }
+
+  pub fn attribute_values(&mut self, name: vertex::AttributeName, format: vertex::Format, values: Vec<ScalarValue>) -> &mut MeshBuilder {
+    assert!(values.len() % format.elements() == 0, "{:?} has a partial element", name);
+    assert!(values.iter().all(|x| x.scalar() == format.scalar()), "{:?} values do not match {:?}", name, format);
+
+    self.streams.push(Stream { name: name, format: format, values: values });
+
+    return self;
+  }
+
+  pub fn positions(&mut self, positions: &[[f32; 3]]) -> &mut MeshBuilder {
+    let values: Vec<f32> = positions.iter().flat_map(|x| x.iter().cloned()).collect();
+
+    return self.attribute(vertex::AttributeName::Position, vertex::Format(vertex::Scalar::f32, vertex::Width::Vector3), &values);
+  }
+
+  pub fn normals(&mut self, normals: &[[f32; 3]]) -> &mut MeshBuilder {
+    let values: Vec<f32> = normals.iter().flat_map(|x| x.iter().cloned()).collect();
+
+    return self.attribute(vertex::AttributeName::Normal, vertex::Format(vertex::Scalar::f32, vertex::Width::Vector3), &values);
+  }
+
+  pub fn texture_coordinates(&mut self, texture_coordinates: &[[f32; 2]]) -> &mut MeshBuilder {
+    let values: Vec<f32> = texture_coordinates.iter().flat_map(|x| x.iter().cloned()).collect();
+
+    return self.attribute(vertex::AttributeName::TextureCoordinate, vertex::Format(vertex::Scalar::f32, vertex::Width::Vector2), &values);
+  }
+
+  pub fn joints(&mut self, indices: &[[u8; 4]], weights: &[[f32; 4]]) -> &mut MeshBuilder {
+    let indices = indices.iter().flat_map(|x| x.iter().map(|i| ScalarValue::u8(*i))).collect();
+    let weights: Vec<f32> = weights.iter().flat_map(|x| x.iter().cloned()).collect();
+
+    self.attribute_values(vertex::AttributeName::JointIndices, vertex::Format(vertex::Scalar::u8, vertex::Width::Vector4), indices);
+
+    return self.attribute(vertex::AttributeName::JointWeights, vertex::Format(vertex::Scalar::f32, vertex::Width::Vector4), &weights);
+  }
+
+  pub fn bone_bindings(&mut self, bone_bindings: &[String]) -> &mut MeshBuilder {
+    self.bone_bindings = bone_bindings.to_vec();
+
+    return self;
+  }
+
+  pub fn submesh(&mut self, name: &str, geometry: index::Geometry, indices: &[u32]) -> &mut MeshBuilder {
+    self.areas.push(Area { name: name.to_owned(), geometry: geometry, indices: indices.to_vec() });
+
+    return self;
+  }
+
+  // Appends copies of the source vertices to every stream
+  pub fn duplicate_vertices(&mut self, sources: &[usize]) -> &mut MeshBuilder {
+    for stream in &mut self.streams {
+      let elements = stream.format.elements();
+
+      for v in sources {
+        let copy: Vec<ScalarValue> = stream.values[v * elements .. (v + 1) * elements].to_vec();
+
+        stream.values.extend(copy.into_iter());
+      }
+    }
+
+    return self;
+  }
+
+  // Takes the place of the first stream with the same name, or goes into the first buffer
+  pub fn replace_attribute(&mut self, stream: Stream) -> &mut MeshBuilder {
+    let buffer_indices = self.buffer_indices();
+
+    let existing = self.streams.iter().position(|x| x.name == stream.name);
+    let buffer_index = existing.map(|i| buffer_indices[i]).unwrap_or(0);
+
+    let mut streams = Vec::new();
+    let mut indices = Vec::new();
+
+    for (x, i) in mem::replace(&mut self.streams, Vec::new()).into_iter().zip(buffer_indices.into_iter()) {
+      if x.name != stream.name {
+        streams.push(x);
+        indices.push(i);
+      }
+    }
+
+    // Every stream before the first match is kept, so its position is unchanged
+    let position = existing.unwrap_or(streams.len());
+
+    streams.insert(position, stream);
+    indices.insert(position, buffer_index);
+
+    self.streams = streams;
+    self.layout = Layout::Grouped(indices);
+
+    return self;
+  }
+
+  // Replaces the faces of every triangle area, each triangle is tagged with the index of its area
+  pub fn replace_triangles(&mut self, triangles: &[(usize, [usize; 3])]) -> &mut MeshBuilder {
+    for (a, area) in self.areas.iter_mut().enumerate() {
+      if !area.geometry.is_triangles() {
+        continue;
+      }
+
+      area.geometry = index::Geometry::Triangles;
+      area.indices = triangles.iter().filter(|x| x.0 == a).flat_map(|x| x.1.iter().map(|i| *i as u32)).collect();
+    }
+
+    return self;
+  }
+
+  // Vertex i of the result copies vertex sources[i], and every index v is rewritten to remap[v]
+  pub fn remap_vertices(&mut self, remap: &[Option<usize>], sources: &[usize]) -> &mut MeshBuilder {
+    for stream in &mut self.streams {
+      let elements = stream.format.elements();
+
+      let values = sources.iter().flat_map(|v| stream.values[v * elements .. (v + 1) * elements].iter().cloned()).collect();
+
+      stream.values = values;
+    }
+
+    for area in &mut self.areas {
+      for i in &mut area.indices {
+        if *i != index::RESTART_INDEX {
+          *i = remap[*i as usize].expect("Index refers to a removed vertex") as u32;
+        }
+      }
+    }
+
+    return self;
+  }
+
+  pub fn remove_unused_vertices(&mut self) -> &mut MeshBuilder {
+    let used = self.used_vertices();
+
+    let sources: Vec<usize> = (0 .. used.len()).filter(|v| used[*v]).collect();
+    let mut remap: Vec<Option<usize>> = used.iter().map(|_| None).collect();
+
+    for (i, v) in sources.iter().enumerate() {
+      remap[*v] = Some(i);
+    }
+
+    return self.remap_vertices(&remap, &sources);
+  }
+
+  pub fn used_vertices(&self) -> Vec<bool> {
+    let mut used: Vec<bool> = (0 .. self.vertex_count()).map(|_| false).collect();
+
+    for area in &self.areas {
+      for i in area.indices.iter().filter(|x| **x != index::RESTART_INDEX) {
+        used[*i as usize] = true;
+      }
+    }
+
+    return used;
+  }
+
+  pub fn buffer_indices(&self) -> Vec<usize> {
+    return match self.layout {
+      Layout::Interleaved => self.streams.iter().map(|_| 0).collect(),
+      Layout::Separate => (0 .. self.streams.len()).collect(),
+      Layout::Grouped(ref buffer_indices) => buffer_indices.clone()
+    };
+  }
+
+  pub fn vertex_count(&self) -> usize {
+    return match self.streams.first() {
+      Some(stream) => stream.values.len() / stream.format.elements(),
+      None => 0
+    };
+  }
+
+  pub fn build(&self) -> Mesh {
+    let vertex_count = self.vertex_count();
+
+    for stream in &self.streams {
+      assert!(stream.values.len() == vertex_count * stream.format.elements(), "{:?} does not have {} vertices", stream.name, vertex_count);
+    }
+
+    let groups: Vec<Vec<usize>> = match self.layout {
+      Layout::Interleaved => vec![(0 .. self.streams.len()).collect()],
+      Layout::Separate => (0 .. self.streams.len()).map(|i| vec![i]).collect(),
+      Layout::Grouped(ref buffer_indices) => {
+        assert!(buffer_indices.len() == self.streams.len(), "Layout does not cover every stream");
+
+        let buffer_count = buffer_indices.iter().map(|x| x + 1).max().unwrap_or(0);
+
+        (0 .. buffer_count).map(|buffer_index| {
+          (0 .. self.streams.len()).filter(|i| buffer_indices[*i] == buffer_index).collect()
+        }).collect()
+      }
+    };
+
+    let mut attributes: Vec<Option<vertex::Attribute>> = self.streams.iter().map(|_| None).collect();
+    let mut layouts = Vec::new();
+    let mut buffers = Vec::new();
+
+    for (buffer_index, group) in groups.iter().enumerate() {
+      let mut offsets = Vec::new();
+      let mut offset = 0;
+
+      for i in group {
+        let format = self.streams[*i].format;
+
+        offset = align(offset, format.scalar().byte_size());
+        offsets.push(offset);
+
+        attributes[*i] = Some(vertex::Attribute {
+          name: self.streams[*i].name.clone(),
+          format: format,
+          offset: offset,
+          buffer_index: buffer_index
+        });
+
+        offset += format.byte_size();
+      }
+
+      let stride = align(offset, 4);
+
+      let mut writer = BufferWriter::with_capacity(stride * vertex_count);
+
+      for v in 0 .. vertex_count {
+        for (i, o) in group.iter().zip(offsets.iter()) {
+          let stream = &self.streams[*i];
+          let elements = stream.format.elements();
+
+          writer.pad_to(v * stride + o);
+
+          for value in &stream.values[v * elements .. (v + 1) * elements] {
+            writer.write_scalar_value(value);
+          }
+        }
+
+        writer.pad_to((v + 1) * stride);
+      }
+
+      let length = writer.len();
+      let buffer = writer.into_buffer(None, Some(self.name.clone()));
+
+      layouts.push(vertex::BufferLayout { stride: stride });
+      buffers.push(BufferView::new(None, buffer, 0, length));
+    }
+
+    return Mesh {
+      name: self.name.clone(),
+      vertex_count: vertex_count,
+      descriptor: vertex::Descriptor {
+        attributes: attributes.into_iter().map(|x| x.expect("Layout does not cover every stream")).collect(),
+        layouts: layouts
+      },
+      buffers: buffers,
+      submeshes: self.build_submeshes(vertex_count),
+      bone_bindings: self.bone_bindings.clone()
+    };
+  }
+
+  fn build_submeshes(&self, vertex_count: usize) -> Vec<Submesh> {
+    let restarts: Vec<bool> = self.areas.iter().map(|x| x.indices.contains(&index::RESTART_INDEX)).collect();
+
+    // The largest u16 is reserved for restarts when any area uses them
+    let u16_limit = if restarts.contains(&true) { std::u16::MAX as usize } else { std::u16::MAX as usize + 1 };
+
+    let index_format = match self.index_format {
+      Some(index_format) => index_format,
+      None if vertex_count <= u16_limit => index::Format::u16,
+      None => index::Format::u32
+    };
+
+    let index_count = self.areas.iter().map(|x| x.indices.len()).fold(0, |sum, x| sum + x);
+
+    let mut writer = BufferWriter::with_capacity(index_count * index_format.byte_size());
+
+    for area in &self.areas {
+      for i in &area.indices {
+        let restart = *i == index::RESTART_INDEX;
+        let i = if restart { index_format.restart_index() as u32 } else { *i };
+
+        assert!((i as usize) < vertex_count || restart, "Index {} in {:?} is out of bounds", i, area.name);
+
+        match index_format {
+          index::Format::u16 => {
+            assert!(i <= std::u16::MAX as u32, "Index {} in {:?} does not fit in u16", i, area.name);
+
+            writer.write_scalar_value(&ScalarValue::u16(i as u16))
+          }
+          index::Format::u32 => writer.write_scalar_value(&ScalarValue::u32(i))
+        };
+      }
+    }
+
+    let buffer = writer.into_buffer(None, Some(self.name.clone()));
+
+    let mut offset = 0;
+
+    return self.areas.iter().zip(restarts.iter()).map(|(area, restart)| {
+      let length = area.indices.len() * index_format.byte_size();
+      let view = BufferView::new(None, buffer.clone(), offset, length);
+
+      offset += length;
+
+      Submesh {
+        name: area.name.clone(),
+        view: view,
+        index_count: area.indices.len(),
+        index_format: index_format,
+        geometry: area.geometry,
+        primitive_restart: *restart
+      }
+    }).collect();
+  }
+}
+
+#[inline(always)]
+fn align(offset: usize, alignment: usize) -> usize {
+  return (offset + alignment - 1) / alignment * alignment;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use index;
+  use vertex;
+
+  fn triangle() -> MeshBuilder {
+    let mut builder = MeshBuilder::new("triangle");
+
+    builder
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]])
+      .texture_coordinates(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]])
+      .joints(&[[0, 1, 2, 3], [4, 5, 6, 7], [8, 9, 10, 11]], &[[1.0, 0.0, 0.0, 0.0]; 3])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2]);
+
+    return builder;
+  }
+
+  #[test]
+  fn test_interleaved() {
+    let mesh = triangle().build();
+
+    assert_eq!(mesh.vertex_count, 3);
+    assert_eq!(mesh.buffers.len(), 1);
+    assert_eq!(mesh.descriptor.layouts, vec![vertex::BufferLayout { stride: 40 }]);
+    assert_eq!(mesh.attribute_for(&vertex::AttributeName::TextureCoordinate).unwrap().offset, 12);
+
+    let positions = mesh.untyped_view_for(&vertex::AttributeName::Position).unwrap();
+    let joints = mesh.untyped_view_for(&vertex::AttributeName::JointIndices).unwrap();
+
+    assert_eq!(positions.get_f32(1), &[1.0, 0.0, 0.0]);
+    assert_eq!(joints.get_f32(2), &[8.0, 9.0, 10.0, 11.0]);
+
+    assert_eq!(mesh.submeshes[0].index_format, index::Format::u16);
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 1, 2]]);
+  }
+
+  #[test]
+  fn test_separate() {
+    let mesh = triangle().layout(Layout::Separate).build();
+
+    assert_eq!(mesh.buffers.len(), 4);
+    assert_eq!(mesh.descriptor.layouts[1], vertex::BufferLayout { stride: 8 });
+
+    let uvs = mesh.untyped_view_for(&vertex::AttributeName::TextureCoordinate).unwrap();
+
+    assert_eq!(uvs.get_f32(2), &[0.0, 1.0]);
+  }
+
+  #[test]
+  fn test_from_mesh() {
+    let mesh = triangle().layout(Layout::Grouped(vec![0, 1, 1, 1])).build();
+    let rebuilt = MeshBuilder::from_mesh(&mesh).build();
+
+    assert_eq!(rebuilt.descriptor, mesh.descriptor);
+    assert_eq!(rebuilt.buffers[1].as_slice(), mesh.buffers[1].as_slice());
+    assert_eq!(rebuilt.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 1, 2]]);
+  }
+
+  #[test]
+  #[should_panic]
+  fn test_out_of_bounds_index() {
+    triangle().submesh("broken", index::Geometry::Triangles, &[0, 1, 3]).build();
+  }
+}
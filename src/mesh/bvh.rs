@@ -0,0 +1,478 @@
+use std::f32;
+
+use math;
+use vertex;
+
+use mesh::Mesh;
+
+// Leaves hold at most this many triangles
+const LEAF_SIZE: usize = 4;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Ray {
+  pub origin: math::Vector3,
+  pub direction: math::Vector3
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Hit {
+  pub submesh: usize,
+  pub triangle: usize, // Index of the face within its submesh
+  pub vertices: [usize; 3],
+  pub barycentrics: math::Vector3, // Weights of the three vertices
+  pub position: math::Vector3,
+  pub distance: f32
+}
+
+impl Hit {
+  // Any attribute at the hit, blended from the three vertices of the triangle
+  pub fn interpolate(&self, mesh: &Mesh, name: &vertex::AttributeName) -> Option<Vec<f32>> {
+    return mesh.untyped_view_for(name).map(|view| {
+      let values: Vec<Vec<f32>> = self.vertices.iter().map(|v| view.get_f32(*v).to_vec()).collect();
+
+      (0 .. values[0].len()).map(|i| {
+        values[0][i] * self.barycentrics[0] + values[1][i] * self.barycentrics[1] + values[2][i] * self.barycentrics[2]
+      }).collect()
+    });
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Triangle {
+  submesh: usize,
+  index: usize,
+  vertices: [usize; 3],
+  positions: [math::Vector3; 3]
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+  min: math::Vector3,
+  max: math::Vector3
+}
+
+impl Bounds {
+  fn empty() -> Bounds {
+    return Bounds { min: [f32::MAX; 3], max: [f32::MIN; 3] };
+  }
+
+  fn add(&self, p: math::Vector3) -> Bounds {
+    return Bounds {
+      min: [self.min[0].min(p[0]), self.min[1].min(p[1]), self.min[2].min(p[2])],
+      max: [self.max[0].max(p[0]), self.max[1].max(p[1]), self.max[2].max(p[2])]
+    };
+  }
+
+  // Slab test, returns the distance along the ray where it enters the box
+  fn intersect(&self, origin: math::Vector3, inverse_direction: math::Vector3, max: f32) -> Option<f32> {
+    let mut near: f32 = 0.0;
+    let mut far = max;
+
+    for i in 0 .. 3 {
+      let t0 = (self.min[i] - origin[i]) * inverse_direction[i];
+      let t1 = (self.max[i] - origin[i]) * inverse_direction[i];
+
+      // NaN comes from a zero direction on the slab boundary, which counts as inside
+      near = near.max(if t0 < t1 { t0 } else { t1 });
+      far = far.min(if t0 < t1 { t1 } else { t0 });
+    }
+
+    return if near <= far { Some(near) } else { None };
+  }
+
+  fn distance_squared(&self, p: math::Vector3) -> f32 {
+    return (0 .. 3).fold(0.0, |sum, i| {
+      let d = (self.min[i] - p[i]).max(0.0).max(p[i] - self.max[i]);
+
+      sum + d * d
+    });
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+  bounds: Bounds,
+  first: usize, // First triangle for leaves, right child otherwise
+  count: usize // Zero for interior nodes, whose left child comes right after them
+}
+
+#[derive(Debug, Clone)]
+pub struct Bvh {
+  nodes: Vec<Node>,
+  triangles: Vec<Triangle>
+}
+
+impl Bvh {
+  // Covers the triangles of every triangle submesh, None when there are none
+  pub fn new(mesh: &Mesh) -> Option<Bvh> {
+    let positions = match mesh.positions() {
+      Some(positions) => positions, None => return None
+    };
+
+    let mut index = 0;
+    let mut previous = None;
+
+    let mut triangles: Vec<Triangle> = mesh.triangles().iter().map(|&(s, face)| {
+      if previous != Some(s) {
+        previous = Some(s);
+        index = 0;
+      }
+
+      index += 1;
+
+      Triangle { submesh: s, index: index - 1, vertices: face, positions: [positions[face[0]], positions[face[1]], positions[face[2]]] }
+    }).collect();
+
+    if triangles.is_empty() {
+      return None;
+    }
+
+    let mut nodes = Vec::new();
+    let count = triangles.len();
+
+    build(&mut nodes, &mut triangles, 0, count);
+
+    return Some(Bvh { nodes: nodes, triangles: triangles });
+  }
+
+  // The closest hit along the ray, triangles are hit from either side
+  pub fn ray(&self, ray: &Ray, max_distance: f32) -> Option<Hit> {
+    let length = math::length(ray.direction);
+
+    if length == 0.0 {
+      return None;
+    }
+
+    let direction = math::scale(ray.direction, 1.0 / length);
+
+    return self.cast(ray.origin, direction, max_distance);
+  }
+
+  pub fn segment(&self, from: math::Vector3, to: math::Vector3) -> Option<Hit> {
+    let direction = math::sub(to, from);
+    let length = math::length(direction);
+
+    if length == 0.0 {
+      return None;
+    }
+
+    return self.cast(from, math::scale(direction, 1.0 / length), length);
+  }
+
+  // The closest point on the surface, `distance` is how far it is from `p`
+  pub fn closest_point(&self, p: math::Vector3, max_distance: f32) -> Option<Hit> {
+    let mut best: Option<Hit> = None;
+    let mut best_squared = max_distance * max_distance;
+
+    let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+
+    while let Some(n) = stack.pop() {
+      let node = &self.nodes[n];
+
+      if node.bounds.distance_squared(p) > best_squared {
+        continue;
+      }
+
+      if node.count == 0 {
+        self.push_children(&mut stack, n, |child| child.bounds.distance_squared(p));
+
+        continue;
+      }
+
+      for triangle in &self.triangles[node.first .. node.first + node.count] {
+        let barycentrics = closest_on_triangle(p, &triangle.positions);
+        let position = interpolate(&triangle.positions, barycentrics);
+        let d = math::sub(position, p);
+        let squared = math::dot(d, d);
+
+        if squared <= best_squared {
+          best_squared = squared;
+          best = Some(hit(triangle, barycentrics, position, squared.sqrt()));
+        }
+      }
+    }
+
+    return best;
+  }
+
+  fn cast(&self, origin: math::Vector3, direction: math::Vector3, max_distance: f32) -> Option<Hit> {
+    let inverse_direction = [1.0 / direction[0], 1.0 / direction[1], 1.0 / direction[2]];
+
+    let mut best: Option<Hit> = None;
+    let mut best_distance = max_distance;
+
+    let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+
+    while let Some(n) = stack.pop() {
+      let node = &self.nodes[n];
+
+      if node.bounds.intersect(origin, inverse_direction, best_distance).is_none() {
+        continue;
+      }
+
+      if node.count == 0 {
+        self.push_children(&mut stack, n, |child| child.bounds.intersect(origin, inverse_direction, f32::INFINITY).unwrap_or(f32::INFINITY));
+
+        continue;
+      }
+
+      for triangle in &self.triangles[node.first .. node.first + node.count] {
+        if let Some((t, barycentrics)) = intersect_triangle(origin, direction, &triangle.positions) {
+          if t <= best_distance {
+            best_distance = t;
+            best = Some(hit(triangle, barycentrics, math::add(origin, math::scale(direction, t)), t));
+          }
+        }
+      }
+    }
+
+    return best;
+  }
+
+  // The nearer child is pushed last so it is visited first
+  fn push_children<F>(&self, stack: &mut Vec<usize>, n: usize, distance: F) where F: Fn(&Node) -> f32 {
+    let left = n + 1;
+    let right = self.nodes[n].first;
+
+    if distance(&self.nodes[left]) <= distance(&self.nodes[right]) {
+      stack.push(right);
+      stack.push(left);
+    } else {
+      stack.push(left);
+      stack.push(right);
+    }
+  }
+}
+
+// Splits at the median of the longest axis of the triangle centroids, nodes are stored depth first
+fn build(nodes: &mut Vec<Node>, triangles: &mut [Triangle], offset: usize, count: usize) -> usize {
+  let n = nodes.len();
+
+  let bounds = triangles.iter().fold(Bounds::empty(), |b, t| b.add(t.positions[0]).add(t.positions[1]).add(t.positions[2]));
+
+  nodes.push(Node { bounds: bounds, first: offset, count: count });
+
+  if count <= LEAF_SIZE {
+    return n;
+  }
+
+  let centroid = |t: &Triangle| math::scale(math::add(math::add(t.positions[0], t.positions[1]), t.positions[2]), 1.0 / 3.0);
+  let centroids = triangles.iter().fold(Bounds::empty(), |b, t| b.add(centroid(t)));
+
+  let extent = math::sub(centroids.max, centroids.min);
+  let axis = if extent[0] >= extent[1] && extent[0] >= extent[2] { 0 } else if extent[1] >= extent[2] { 1 } else { 2 };
+
+  triangles.sort_by(|a, b| centroid(a)[axis].partial_cmp(&centroid(b)[axis]).unwrap_or(::std::cmp::Ordering::Equal));
+
+  let half = count / 2;
+  let (left, right) = triangles.split_at_mut(half);
+
+  build(nodes, left, offset, half);
+
+  let right_index = build(nodes, right, offset + half, count - half);
+
+  nodes[n].first = right_index;
+  nodes[n].count = 0;
+
+  return n;
+}
+
+fn hit(triangle: &Triangle, barycentrics: math::Vector3, position: math::Vector3, distance: f32) -> Hit {
+  return Hit {
+    submesh: triangle.submesh,
+    triangle: triangle.index,
+    vertices: triangle.vertices,
+    barycentrics: barycentrics,
+    position: position,
+    distance: distance
+  };
+}
+
+fn interpolate(p: &[math::Vector3; 3], barycentrics: math::Vector3) -> math::Vector3 {
+  return math::add(math::add(math::scale(p[0], barycentrics[0]), math::scale(p[1], barycentrics[1])), math::scale(p[2], barycentrics[2]));
+}
+
+// Möller-Trumbore
+fn intersect_triangle(origin: math::Vector3, direction: math::Vector3, p: &[math::Vector3; 3]) -> Option<(f32, math::Vector3)> {
+  let e1 = math::sub(p[1], p[0]);
+  let e2 = math::sub(p[2], p[0]);
+
+  let h = math::cross(direction, e2);
+  let a = math::dot(e1, h);
+
+  if a == 0.0 {
+    return None;
+  }
+
+  let f = 1.0 / a;
+  let s = math::sub(origin, p[0]);
+  let u = f * math::dot(s, h);
+
+  if u < 0.0 || u > 1.0 {
+    return None;
+  }
+
+  let q = math::cross(s, e1);
+  let v = f * math::dot(direction, q);
+
+  if v < 0.0 || u + v > 1.0 {
+    return None;
+  }
+
+  let t = f * math::dot(e2, q);
+
+  return if t >= 0.0 { Some((t, [1.0 - u - v, u, v])) } else { None };
+}
+
+// From Real-Time Collision Detection, returns barycentrics of the closest point
+fn closest_on_triangle(point: math::Vector3, p: &[math::Vector3; 3]) -> math::Vector3 {
+  let ab = math::sub(p[1], p[0]);
+  let ac = math::sub(p[2], p[0]);
+  let ap = math::sub(point, p[0]);
+
+  let d1 = math::dot(ab, ap);
+  let d2 = math::dot(ac, ap);
+
+  if d1 <= 0.0 && d2 <= 0.0 {
+    return [1.0, 0.0, 0.0];
+  }
+
+  let bp = math::sub(point, p[1]);
+  let d3 = math::dot(ab, bp);
+  let d4 = math::dot(ac, bp);
+
+  if d3 >= 0.0 && d4 <= d3 {
+    return [0.0, 1.0, 0.0];
+  }
+
+  let vc = d1 * d4 - d3 * d2;
+
+  if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+    let v = d1 / (d1 - d3);
+
+    return [1.0 - v, v, 0.0];
+  }
+
+  let cp = math::sub(point, p[2]);
+  let d5 = math::dot(ab, cp);
+  let d6 = math::dot(ac, cp);
+
+  if d6 >= 0.0 && d5 <= d6 {
+    return [0.0, 0.0, 1.0];
+  }
+
+  let vb = d5 * d2 - d1 * d6;
+
+  if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+    let w = d2 / (d2 - d6);
+
+    return [1.0 - w, 0.0, w];
+  }
+
+  let va = d3 * d6 - d5 * d4;
+
+  if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+    let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+
+    return [0.0, 1.0 - w, w];
+  }
+
+  let denominator = 1.0 / (va + vb + vc);
+  let v = vb * denominator;
+  let w = vc * denominator;
+
+  return [1.0 - v - w, v, w];
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::f32;
+
+  use index;
+  use vertex;
+
+  use mesh::{Mesh, MeshBuilder};
+
+  // A row of unit quads along x in the z = 0 plane, with u running along x, and a second submesh
+  // holding one quad at z = 1
+  fn strip(quads: u32) -> Mesh {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+
+    for x in 0 .. quads + 1 {
+      positions.push([x as f32, 0.0, 0.0]);
+      positions.push([x as f32, 1.0, 0.0]);
+      uvs.push([x as f32 / quads as f32, 0.0]);
+      uvs.push([x as f32 / quads as f32, 1.0]);
+    }
+
+    let indices: Vec<u32> = (0 .. quads).flat_map(|x| vec![2 * x, 2 * x + 2, 2 * x + 3, 2 * x, 2 * x + 3, 2 * x + 1].into_iter()).collect();
+    let top = positions.len() as u32;
+
+    positions.extend_from_slice(&[[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 1.0, 1.0]]);
+    uvs.extend_from_slice(&[[0.0, 0.0]; 4]);
+
+    return MeshBuilder::new("strip")
+      .positions(&positions)
+      .texture_coordinates(&uvs)
+      .submesh("hull", index::Geometry::Triangles, &indices)
+      .submesh("glass", index::Geometry::Triangles, &[top, top + 1, top + 2, top, top + 2, top + 3])
+      .build();
+  }
+
+  #[test]
+  fn test_ray() {
+    let mesh = strip(20);
+    let bvh = Bvh::new(&mesh).unwrap();
+
+    let hit = bvh.ray(&Ray { origin: [10.75, 0.5, -5.0], direction: [0.0, 0.0, 2.0] }, f32::INFINITY).unwrap();
+
+    assert_eq!(hit.submesh, 0);
+    assert_eq!(hit.triangle, 20);
+    assert_eq!(hit.distance, 5.0);
+    assert_eq!(hit.position, [10.75, 0.5, 0.0]);
+    assert!((hit.interpolate(&mesh, &vertex::AttributeName::TextureCoordinate).unwrap()[0] - 10.75 / 20.0).abs() < 1e-6);
+
+    // From above, the glass is in the way
+    let hit = bvh.ray(&Ray { origin: [0.5, 0.5, 5.0], direction: [0.0, 0.0, -1.0] }, f32::INFINITY).unwrap();
+
+    assert_eq!(hit.submesh, 1);
+    assert_eq!(hit.distance, 4.0);
+
+    assert!(bvh.ray(&Ray { origin: [0.5, 0.5, 5.0], direction: [0.0, 0.0, -1.0] }, 3.0).is_none());
+    assert!(bvh.ray(&Ray { origin: [30.0, 0.5, 5.0], direction: [0.0, 0.0, -1.0] }, f32::INFINITY).is_none());
+  }
+
+  #[test]
+  fn test_segment() {
+    let bvh = Bvh::new(&strip(20)).unwrap();
+
+    assert!(bvh.segment([5.5, 0.5, -1.0], [5.5, 0.5, -0.1]).is_none());
+    assert_eq!(bvh.segment([5.5, 0.5, -1.0], [5.5, 0.5, 0.5]).unwrap().position, [5.5, 0.5, 0.0]);
+  }
+
+  #[test]
+  fn test_closest_point() {
+    let bvh = Bvh::new(&strip(20)).unwrap();
+
+    let hit = bvh.closest_point([7.5, 3.0, -0.5], f32::INFINITY).unwrap();
+
+    assert_eq!(hit.submesh, 0);
+    assert_eq!(hit.position, [7.5, 1.0, 0.0]);
+    assert!((hit.distance - (4.0f32 + 0.25).sqrt()).abs() < 1e-6);
+
+    assert!(bvh.closest_point([7.5, 3.0, -0.5], 1.0).is_none());
+  }
+
+  #[test]
+  fn test_no_triangles() {
+    let mesh = MeshBuilder::new("lines")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]])
+      .submesh("hull", index::Geometry::Lines, &[0, 1])
+      .build();
+
+    assert!(Bvh::new(&mesh).is_none());
+  }
+}
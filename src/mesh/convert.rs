@@ -0,0 +1,169 @@
+use buffer::ScalarValue;
+
+use vertex;
+
+use mesh::{Mesh, MeshBuilder, Stream};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Statistics {
+  pub count: usize,
+  pub clamped: usize,
+  pub max_error: f32,
+
+  sum_error: f64,
+  sum_squared_error: f64
+}
+
+impl Statistics {
+  pub fn new() -> Statistics {
+    return Statistics { count: 0, clamped: 0, max_error: 0.0, sum_error: 0.0, sum_squared_error: 0.0 };
+  }
+
+  pub fn mean_error(&self) -> f32 {
+    if self.count == 0 { return 0.0 };
+
+    return (self.sum_error / self.count as f64) as f32;
+  }
+
+  pub fn rms_error(&self) -> f32 {
+    if self.count == 0 { return 0.0 };
+
+    return (self.sum_squared_error / self.count as f64).sqrt() as f32;
+  }
+
+  pub fn merge(&mut self, other: &Statistics) {
+    self.count += other.count;
+    self.clamped += other.clamped;
+    self.max_error = self.max_error.max(other.max_error);
+    self.sum_error += other.sum_error;
+    self.sum_squared_error += other.sum_squared_error;
+  }
+
+  fn add(&mut self, original: f32, converted: f32, clamped: bool) {
+    let error = if original == converted { 0.0 } else { (original - converted).abs() };
+
+    self.count += 1;
+
+    if clamped { self.clamped += 1 };
+
+    if !error.is_nan() { // NaN never contributes to the error
+      self.max_error = self.max_error.max(error);
+      self.sum_error += error as f64;
+      self.sum_squared_error += error as f64 * error as f64;
+    }
+  }
+}
+
+pub fn convert(mesh: &Mesh, name: &vertex::AttributeName, format: vertex::Format) -> Option<(Mesh, Statistics)> {
+  if mesh.attribute_for(name).is_none() {
+    return None;
+  }
+
+  let mut builder = MeshBuilder::from_mesh(mesh);
+  let mut statistics = Statistics::new();
+
+  for stream in &mut builder.streams {
+    if stream.name != *name {
+      continue;
+    }
+
+    let (converted, s) = convert_stream(stream, format);
+
+    *stream = converted;
+    statistics.merge(&s);
+  }
+
+  return Some((builder.build(), statistics));
+}
+
+pub fn convert_stream(stream: &Stream, format: vertex::Format) -> (Stream, Statistics) {
+  let from = stream.format.elements();
+  let to = format.elements();
+
+  let scalar = format.scalar();
+  let (min, max) = scalar.range();
+
+  let vertex_count = stream.values.len() / from;
+
+  let mut statistics = Statistics::new();
+  let mut values = Vec::with_capacity(vertex_count * to);
+
+  for v in 0 .. vertex_count {
+    let originals: Vec<f32> = (0 .. to).map(|i| {
+      if i < from { stream.values[v * from + i].to_f32() } else { 0.0 }
+    }).collect();
+
+    let converted = ScalarValue::from_f32_vector(format, &originals);
+
+    for i in 0 .. from.min(to) {
+      let original = originals[i];
+
+      let out_of_range = (original as f64) < min || (original as f64) > max;
+      let not_a_number = original.is_nan() && scalar != vertex::Scalar::f16 && scalar != vertex::Scalar::f32;
+
+      statistics.add(original, converted[i].to_f32(), out_of_range || not_a_number);
+    }
+
+    values.extend(converted.into_iter());
+  }
+
+  return (Stream { name: stream.name.clone(), format: format, values: values }, statistics);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use index;
+  use vertex;
+
+  use mesh::MeshBuilder;
+
+  #[test]
+  fn test_convert() {
+    let mesh = MeshBuilder::new("quad")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]])
+      .normals(&[[0.0, 0.0, 1.0], [0.0, 0.0, -1.0], [0.6, 0.8, 2.0]])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2])
+      .build();
+
+    let format = vertex::Format(vertex::Scalar::i16_normalized, vertex::Width::Vector4);
+    let (converted, statistics) = convert(&mesh, &vertex::AttributeName::Normal, format).unwrap();
+
+    assert_eq!(converted.attribute_for(&vertex::AttributeName::Normal).unwrap().format, format);
+    assert_eq!(converted.descriptor.layouts[0].stride, 20);
+
+    let normals = converted.untyped_view_for(&vertex::AttributeName::Normal).unwrap();
+
+    assert_eq!(normals.get_f32(1), &[0.0, 0.0, -1.0, 0.0]);
+    assert_eq!(normals.get_f32(2)[3], 0.0);
+
+    assert_eq!(statistics.count, 9);
+    assert_eq!(statistics.clamped, 1);
+    assert_eq!(statistics.max_error, 1.0);
+    assert!(statistics.rms_error() > 0.0);
+
+    assert!(convert(&mesh, &vertex::AttributeName::Color, format).is_none());
+
+    let packed = vertex::Format(vertex::Scalar::i10_10_10_2_normalized, vertex::Width::Vector3);
+    let (converted, _) = convert(&mesh, &vertex::AttributeName::Normal, packed).unwrap();
+
+    assert_eq!(converted.descriptor.layouts[0].stride, 16);
+    assert_eq!(converted.untyped_view_for(&vertex::AttributeName::Normal).unwrap().get_f32(1), &[0.0, 0.0, -1.0]);
+  }
+
+  #[test]
+  fn test_convert_to_f32() {
+    let stream = Stream {
+      name: vertex::AttributeName::Color,
+      format: vertex::Format(vertex::Scalar::u8_normalized, vertex::Width::Vector2),
+      values: vec![ScalarValue::u8_normalized(0), ScalarValue::u8_normalized(255)]
+    };
+
+    let (converted, statistics) = convert_stream(&stream, vertex::Format(vertex::Scalar::f32, vertex::Width::Vector2));
+
+    assert_eq!(converted.values, vec![ScalarValue::f32(0.0), ScalarValue::f32(1.0)]);
+    assert_eq!(statistics.max_error, 0.0);
+    assert_eq!(statistics.clamped, 0);
+  }
+}
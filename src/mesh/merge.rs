@@ -0,0 +1,229 @@
+use buffer::ScalarValue;
+
+use index;
+use vertex;
+
+use mesh::{Area, Mesh, MeshBuilder, Stream};
+use mesh::convert;
+
+// One mesh per submesh, each with only the vertices its submesh uses. The mesh is decoded once,
+// and each submesh copies the values of its own vertices out of it
+pub fn split(mesh: &Mesh) -> Vec<Mesh> {
+  let source = MeshBuilder::from_mesh(mesh);
+
+  return source.areas.iter().map(|area| {
+    let mut sources: Vec<u32> = area.indices.iter().filter(|x| **x != index::RESTART_INDEX).cloned().collect();
+
+    sources.sort();
+    sources.dedup();
+
+    let streams = source.streams.iter().map(|stream| {
+      let elements = stream.format.elements();
+      let values = sources.iter().flat_map(|v| stream.values[*v as usize * elements .. (*v as usize + 1) * elements].iter().cloned()).collect();
+
+      Stream { name: stream.name.clone(), format: stream.format, values: values }
+    }).collect();
+
+    let indices = area.indices.iter().map(|i| if *i == index::RESTART_INDEX { *i } else { sources.binary_search(i).unwrap() as u32 }).collect();
+
+    let builder = MeshBuilder {
+      name: format!("{}_{}", mesh.name, area.name),
+      layout: source.layout.clone(),
+      index_format: None,
+      streams: streams,
+      areas: vec![Area { name: area.name.clone(), geometry: area.geometry, indices: indices }],
+      bone_bindings: source.bone_bindings.clone()
+    };
+
+    builder.build()
+  }).collect();
+}
+
+// Meshes need the same attributes to be merged. When their formats differ, the attribute is
+// converted to f32 with the widest of their widths. Joint indices are remapped to the union of the
+// bone bindings, and the layout is the one of the first mesh
+pub fn merge(name: &str, meshes: &[Mesh]) -> Option<Mesh> {
+  let builders: Vec<MeshBuilder> = meshes.iter().map(|x| MeshBuilder::from_mesh(x)).collect();
+
+  let first = match builders.first() {
+    Some(first) => first, None => return None
+  };
+
+  let keys = stream_keys(first);
+
+  // Keys are unique within a mesh, so this compares them regardless of order
+  for builder in &builders {
+    let other = stream_keys(builder);
+
+    if other.len() != keys.len() || !other.iter().all(|x| keys.contains(x)) {
+      return None;
+    }
+  }
+
+  let mut bone_bindings: Vec<String> = Vec::new();
+
+  for builder in &builders {
+    for bone in &builder.bone_bindings {
+      if !bone_bindings.contains(bone) {
+        bone_bindings.push(bone.clone());
+      }
+    }
+  }
+
+  let mut merged = MeshBuilder::new(name);
+
+  merged.layout = first.layout.clone();
+  merged.bone_bindings = bone_bindings.clone();
+
+  for key in &keys {
+    let streams: Vec<&Stream> = builders.iter().map(|builder| {
+      let position = stream_keys(builder).iter().position(|x| x == key).unwrap();
+
+      &builder.streams[position]
+    }).collect();
+
+    let format = if streams.iter().all(|x| x.format == streams[0].format) {
+      streams[0].format
+    } else {
+      let width = streams.iter().map(|x| x.format.width()).max_by_key(|x| x.elements()).unwrap();
+
+      vertex::Format(vertex::Scalar::f32, width)
+    };
+
+    let mut values = Vec::new();
+
+    for (stream, builder) in streams.iter().zip(builders.iter()) {
+      let stream = if stream.format == format { (*stream).clone() } else { convert::convert_stream(stream, format).0 };
+
+      if stream.name == vertex::AttributeName::JointIndices && builder.bone_bindings != bone_bindings {
+        let remap: Vec<usize> = builder.bone_bindings.iter().map(|x| bone_bindings.iter().position(|y| y == x).unwrap()).collect();
+
+        values.extend(remap_joints(&stream, &remap).into_iter());
+      } else {
+        values.extend(stream.values.into_iter());
+      }
+    }
+
+    merged.streams.push(Stream { name: key.0.clone(), format: format, values: values });
+  }
+
+  let mut offset = 0;
+
+  for builder in &builders {
+    for area in &builder.areas {
+      let indices = area.indices.iter().map(|i| if *i == index::RESTART_INDEX { *i } else { *i + offset }).collect();
+
+      merged.areas.push(Area { name: area.name.clone(), geometry: area.geometry, indices: indices });
+    }
+
+    offset += builder.vertex_count() as u32;
+  }
+
+  return Some(merged.build());
+}
+
+// Streams by name and how many streams of that name come before them, to tell texture coordinate
+// sets and the like apart
+fn stream_keys(builder: &MeshBuilder) -> Vec<(vertex::AttributeName, usize)> {
+  return builder.streams.iter().enumerate().map(|(i, stream)| {
+    (stream.name.clone(), builder.streams[.. i].iter().filter(|x| x.name == stream.name).count())
+  }).collect();
+}
+
+// Joint indices that are not in the bone bindings are left alone, validation reports them
+fn remap_joints(stream: &Stream, remap: &[usize]) -> Vec<ScalarValue> {
+  let elements = stream.format.elements();
+
+  return stream.values.chunks(elements).flat_map(|vertex| {
+    let joints: Vec<f32> = vertex.iter().map(|x| {
+      let joint = x.to_f32() as usize;
+
+      if joint < remap.len() { remap[joint] as f32 } else { joint as f32 }
+    }).collect();
+
+    ScalarValue::from_f32_vector(stream.format, &joints).into_iter()
+  }).collect();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use index;
+  use vertex;
+
+  use mesh::{Mesh, MeshBuilder};
+
+  fn mesh() -> Mesh {
+    return MeshBuilder::new("hull")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [5.0, 0.0, 0.0], [6.0, 0.0, 0.0], [5.0, 1.0, 0.0]])
+      .normals(&[[0.0, 0.0, 1.0]; 6])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2])
+      .submesh("glass", index::Geometry::Triangles, &[3, 4, 5])
+      .build();
+  }
+
+  #[test]
+  fn test_split() {
+    let meshes = split(&mesh());
+
+    assert_eq!(meshes.len(), 2);
+    assert_eq!(meshes[1].name, "hull_glass");
+    assert_eq!(meshes[1].vertex_count, 3);
+    assert_eq!(meshes[1].positions().unwrap()[0], [5.0, 0.0, 0.0]);
+    assert_eq!(meshes[1].submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 1, 2]]);
+  }
+
+  #[test]
+  fn test_merge() {
+    let meshes = split(&mesh());
+    let merged = merge("merged", &meshes).unwrap();
+
+    assert_eq!(merged.vertex_count, 6);
+    assert_eq!(merged.submeshes.len(), 2);
+    assert_eq!(merged.submeshes[1].name, "glass");
+    assert_eq!(merged.submeshes[1].faces().collect::<Vec<_>>(), vec![vec![3, 4, 5]]);
+  }
+
+  #[test]
+  fn test_merge_convertible() {
+    let packed = vertex::Format(vertex::Scalar::i8_normalized, vertex::Width::Vector4);
+    let meshes = split(&mesh());
+    let (glass, _) = convert::convert(&meshes[1], &vertex::AttributeName::Normal, packed).unwrap();
+
+    let merged = merge("merged", &[meshes[0].clone(), glass]).unwrap();
+    let normals = merged.untyped_view_for(&vertex::AttributeName::Normal).unwrap();
+
+    assert_eq!(merged.attribute_for(&vertex::AttributeName::Normal).unwrap().format, vertex::Format(vertex::Scalar::f32, vertex::Width::Vector4));
+    assert_eq!(normals.get_f32(4), &[0.0, 0.0, 1.0, 0.0]);
+
+    let bare = MeshBuilder::new("bare")
+      .positions(&[[0.0, 0.0, 0.0]])
+      .submesh("points", index::Geometry::Points, &[0])
+      .build();
+
+    assert!(merge("merged", &[meshes[0].clone(), bare]).is_none());
+  }
+
+  #[test]
+  fn test_merge_joints() {
+    let a = MeshBuilder::new("a")
+      .positions(&[[0.0, 0.0, 0.0]])
+      .joints(&[[0, 1, 0, 0]], &[[0.5, 0.5, 0.0, 0.0]])
+      .bone_bindings(&["root".to_owned(), "turret".to_owned()])
+      .submesh("a", index::Geometry::Points, &[0])
+      .build();
+
+    let b = MeshBuilder::new("b")
+      .positions(&[[1.0, 0.0, 0.0]])
+      .joints(&[[0, 1, 0, 0]], &[[0.5, 0.5, 0.0, 0.0]])
+      .bone_bindings(&["turret".to_owned(), "barrel".to_owned()])
+      .submesh("b", index::Geometry::Points, &[0])
+      .build();
+
+    let merged = merge("merged", &[a, b]).unwrap();
+
+    assert_eq!(merged.bone_bindings, vec!["root".to_owned(), "turret".to_owned(), "barrel".to_owned()]);
+    assert_eq!(merged.untyped_view_for(&vertex::AttributeName::JointIndices).unwrap().get_f32(1), &[1.0, 2.0, 1.0, 1.0]);
+  }
+}
@@ -1,10 +1,28 @@
+use std::cell::Cell;
 use std::sync::Arc;
 
 use index;
+use math;
 use vertex;
 use vertex::Format;
 
-use buffer::{BufferView, ScalarTypedView, UntypedView, ScalarUntypedView};
+use buffer::{BufferView, BufferWriter, ScalarTypedView, ScalarValue, UntypedView, ScalarUntypedView};
+
+mod builder;
+
+pub mod bvh;
+pub mod convert;
+pub mod merge;
+pub mod normals;
+pub mod optimize;
+pub mod relayout;
+pub mod simplify;
+pub mod tangents;
+pub mod transform;
+pub mod validate;
+pub mod weld;
+
+pub use mesh::builder::*;
 
 #[derive(Debug, Clone)]
 pub struct Submesh {
@@ -12,14 +30,17 @@ pub struct Submesh {
   pub view: Arc<BufferView>,
   pub index_count: usize,
   pub index_format: index::Format,
-  pub geometry: index::Geometry
+  pub geometry: index::Geometry,
+  pub primitive_restart: bool
 }
 
 impl Submesh {
   pub fn faces<'a>(&'a self) -> FaceIterator<'a> {
-    return FaceIterator { view: self.untyped_view(), geometry: self.geometry, current: 0 };
+    let restart = if self.primitive_restart { Some(self.index_format.restart_index()) } else { None };
+
+    return FaceIterator { view: self.untyped_view(), geometry: self.geometry, restart: restart, position: 0, primitive: 0, remaining: Cell::new(None) };
   }
-  
+
   pub fn untyped_view<'a>(&'a self) -> ScalarUntypedView<'a> {
     let view = &self.view;
     let length = self.index_count;
@@ -29,49 +50,137 @@ impl Submesh {
       index::Format::u32 => ScalarUntypedView::u32(ScalarTypedView::<u32>::new(None, view, 0, 0, length))
     };
   }
+
+  pub fn triangulate(&self) -> Option<Submesh> {
+    if !self.geometry.is_triangles() {
+      return None;
+    }
+
+    let mut writer = BufferWriter::with_capacity(3 * self.index_count * self.index_format.byte_size());
+    let mut index_count = 0;
+
+    for face in self.faces() {
+      for i in face {
+        match self.index_format {
+          index::Format::u16 => writer.write_scalar_value(&ScalarValue::u16(i as u16)),
+          index::Format::u32 => writer.write_scalar_value(&ScalarValue::u32(i as u32))
+        };
+
+        index_count += 1;
+      }
+    }
+
+    let length = writer.len();
+    let buffer = writer.into_buffer(None, Some(self.name.clone()));
+
+    return Some(Submesh {
+      name: self.name.clone(),
+      view: BufferView::new(self.view.name.clone(), buffer, 0, length),
+      index_count: index_count,
+      index_format: self.index_format,
+      geometry: index::Geometry::Triangles,
+      primitive_restart: false
+    });
+  }
 }
 
 pub struct FaceIterator<'a> {
   view: ScalarUntypedView<'a>,
   geometry: index::Geometry,
+  restart: Option<usize>,
 
-  current: usize
+  position: usize,
+  primitive: usize,
+  remaining: Cell<Option<usize>> // Counted the first time the length is asked for
 }
 
-impl<'a> Iterator for FaceIterator<'a> {
-  type Item = Vec<usize>;
+impl<'a> FaceIterator<'a> {
+  #[inline(always)]
+  fn is_restart(&self, position: usize) -> bool {
+    return self.restart == Some(self.view.get_usize(position));
+  }
 
-  fn next(&mut self) -> Option<Vec<usize>> {
-    if self.current >= self.len() {
-      return None;
-    }
+  // Moves past a restart index within the next `width` indices, returns whether one was found
+  fn skip_restart(&mut self, width: usize) -> bool {
+    for k in 0 .. width {
+      if self.is_restart(self.position + k) {
+        self.position += k + 1;
+        self.primitive = self.position;
 
-    let mut indices = match self.geometry {
-      index::Geometry::Points => {
-        let offset = self.current;
-        vec![offset + 0]
+        return true;
       }
-      index::Geometry::Lines => {
-        let offset = self.current;
-        vec![offset + 0, offset + 1]
+    }
+
+    return false;
+  }
+
+  fn next_face(&mut self) -> Option<(usize, [usize; 3])> {
+    let index_count = self.view.len();
+
+    loop {
+      let position = self.position;
+
+      let (width, step) = match self.geometry {
+        index::Geometry::Points => (1, 1),
+        index::Geometry::Lines => (2, 2),
+        index::Geometry::LineStrips => (2, 1),
+        index::Geometry::Triangles => (3, 3),
+        index::Geometry::TriangleStrips | index::Geometry::TriangleFans => (3, 1)
+      };
+
+      if position + width > index_count {
+        return None;
       }
-      index::Geometry::TriangleStrips => {
-        let offset = self.current;
-        vec![offset + 0, offset + 1, offset + 2]
+
+      if self.skip_restart(width) {
+        continue;
       }
-      index::Geometry::Triangles => {
-        let offset = 3 * self.current;
-        vec![offset + 0, offset + 1, offset + 2]
+
+      self.position += step;
+
+      let positions = match self.geometry {
+        index::Geometry::Points => [position, 0, 0],
+        index::Geometry::Lines | index::Geometry::LineStrips => [position, position + 1, 0],
+        index::Geometry::Triangles => [position, position + 1, position + 2],
+        // Every other triangle in a strip swaps its first two vertices to keep the winding consistent
+        index::Geometry::TriangleStrips if (position - self.primitive) % 2 == 1 => [position + 1, position, position + 2],
+        index::Geometry::TriangleStrips => [position, position + 1, position + 2],
+        index::Geometry::TriangleFans => [self.primitive, position + 1, position + 2]
+      };
+
+      let mut indices = [0; 3];
+
+      for i in 0 .. width {
+        indices[i] = self.view.get_usize(positions[i]);
       }
-    };
 
-    for i in 0 .. indices.len() {
-      indices[i] = self.view.get_usize(indices[i]);
+      let degenerate = indices[0] == indices[1] || indices[1] == indices[2] || indices[0] == indices[2];
+
+      // Strips and fans are stitched together with degenerate triangles, which are not faces
+      match self.geometry {
+        index::Geometry::TriangleStrips | index::Geometry::TriangleFans if degenerate => continue,
+        _ => return Some((width, indices))
+      };
+    }
+  }
+
+  // Like next, without allocating: the number of vertices in the face, and their indices
+  pub fn next_indices(&mut self) -> Option<(usize, [usize; 3])> {
+    let face = self.next_face();
+
+    if let (Some(_), Some(remaining)) = (face, self.remaining.get()) {
+      self.remaining.set(Some(remaining - 1));
     }
 
-    self.current += 1;
+    return face;
+  }
+}
+
+impl<'a> Iterator for FaceIterator<'a> {
+  type Item = Vec<usize>;
 
-    return Some(indices);
+  fn next(&mut self) -> Option<Vec<usize>> {
+    return self.next_indices().map(|(width, indices)| indices[0 .. width].to_vec());
   }
 
   fn size_hint(&self) -> (usize, Option<usize>) {
@@ -84,14 +193,28 @@ impl<'a> Iterator for FaceIterator<'a> {
 impl<'a> ExactSizeIterator for FaceIterator<'a> {
   #[inline(always)]
   fn len(&self) -> usize {
-    let index_count = self.view.len();
+    if let Some(remaining) = self.remaining.get() {
+      return remaining;
+    }
 
-    return match self.geometry {
-      index::Geometry::Points => index_count,
-      index::Geometry::Lines => index_count - 1,
-      index::Geometry::TriangleStrips => index_count - 2,
-      index::Geometry::Triangles => index_count / 3
+    let mut rest = FaceIterator {
+      view: self.view.clone(),
+      geometry: self.geometry,
+      restart: self.restart,
+      position: self.position,
+      primitive: self.primitive,
+      remaining: Cell::new(None)
     };
+
+    let mut count = 0;
+
+    while let Some(_) = rest.next_face() {
+      count += 1;
+    }
+
+    self.remaining.set(Some(count));
+
+    return count;
   }
 }
 
@@ -101,7 +224,8 @@ pub struct Mesh {
   pub vertex_count: usize,
   pub descriptor: vertex::Descriptor,
   pub buffers: Vec<Arc<BufferView>>,
-  pub submeshes: Vec<Submesh>
+  pub submeshes: Vec<Submesh>,
+  pub bone_bindings: Vec<String> // Names of the bones joint indices refer to
 }
 
 impl Mesh {
@@ -110,17 +234,119 @@ impl Mesh {
   }
 
   pub fn untyped_view_for<'a>(&'a self, name: &vertex::AttributeName) -> Option<UntypedView<'a>> {
-    if let Some(attribute) = self.attribute_for(name) {
+    return self.attribute_for(name).map(|attribute| self.untyped_view_for_attribute(attribute));
+  }
 
-      let view = &self.buffers[attribute.buffer_index];
+  // Every triangle of every triangle submesh, tagged with the index of its submesh
+  pub fn triangles(&self) -> Vec<(usize, [usize; 3])> {
+    let mut triangles = Vec::new();
 
-      let offset = attribute.offset;
-      let stride = self.descriptor.layouts[attribute.buffer_index].stride;
-      let length = self.vertex_count;
+    for (s, submesh) in self.submeshes.iter().enumerate() {
+      if !submesh.geometry.is_triangles() {
+        continue;
+      }
 
-      return Some(UntypedView::new(view, attribute.format, offset, stride, length));
+      triangles.extend(submesh.faces().map(|x| (s, [x[0], x[1], x[2]])));
     }
 
-    return None;
+    return triangles;
+  }
+
+  pub fn validate(&self) -> validate::Report {
+    return validate::validate(self);
+  }
+
+  pub fn positions(&self) -> Option<Vec<math::Vector3>> {
+    return self.untyped_view_for(&vertex::AttributeName::Position).map(|view| {
+      (0 .. self.vertex_count).map(|i| math::to_vector3(&view.get_f32(i))).collect()
+    });
+  }
+
+  pub fn untyped_view_for_attribute<'a>(&'a self, attribute: &vertex::Attribute) -> UntypedView<'a> {
+    let view = &self.buffers[attribute.buffer_index];
+
+    let offset = attribute.offset;
+    let stride = self.descriptor.layouts[attribute.buffer_index].stride;
+    let length = self.vertex_count;
+
+    return UntypedView::new(view, attribute.format, offset, stride, length);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use index;
+
+  use mesh::{Mesh, MeshBuilder};
+
+  fn build(geometry: index::Geometry, indices: &[u32]) -> Mesh {
+    return MeshBuilder::new("strip")
+      .positions(&[[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [2.0, 0.0, 0.0]])
+      .submesh("area", geometry, indices)
+      .build();
+  }
+
+  fn faces(mesh: &Mesh) -> Vec<Vec<usize>> {
+    return mesh.submeshes[0].faces().collect();
+  }
+
+  #[test]
+  fn test_triangle_strip_winding() {
+    let mesh = build(index::Geometry::TriangleStrips, &[0, 1, 2, 3, 4]);
+
+    assert_eq!(mesh.submeshes[0].faces().len(), 3);
+    assert_eq!(faces(&mesh), vec![vec![0, 1, 2], vec![2, 1, 3], vec![2, 3, 4]]);
+  }
+
+  #[test]
+  fn test_primitive_restart() {
+    let mesh = build(index::Geometry::TriangleStrips, &[0, 1, 2, index::RESTART_INDEX, 1, 2, 3, 4]);
+
+    assert!(mesh.submeshes[0].primitive_restart);
+    assert_eq!(faces(&mesh), vec![vec![0, 1, 2], vec![1, 2, 3], vec![3, 2, 4]]);
+
+    let mut iterator = mesh.submeshes[0].faces();
+
+    iterator.next();
+
+    assert_eq!(iterator.len(), 2);
+    assert_eq!(iterator.count(), 2);
+
+    let lines = build(index::Geometry::LineStrips, &[0, 1, 2, index::RESTART_INDEX, 3, 4]);
+
+    assert_eq!(faces(&lines), vec![vec![0, 1], vec![1, 2], vec![3, 4]]);
+  }
+
+  #[test]
+  fn test_degenerate_strip_triangles() {
+    let mesh = build(index::Geometry::TriangleStrips, &[0, 1, 2, 2, 2, 3, 4]);
+
+    assert_eq!(faces(&mesh), vec![vec![0, 1, 2], vec![2, 3, 4]]);
+  }
+
+  #[test]
+  fn test_triangle_fan() {
+    let mesh = build(index::Geometry::TriangleFans, &[0, 1, 3, 4]);
+
+    assert_eq!(faces(&mesh), vec![vec![0, 1, 3], vec![0, 3, 4]]);
+  }
+
+  #[test]
+  fn test_lines() {
+    let mesh = build(index::Geometry::Lines, &[0, 1, 2, 3]);
+
+    assert_eq!(faces(&mesh), vec![vec![0, 1], vec![2, 3]]);
+  }
+
+  #[test]
+  fn test_triangulate() {
+    let strip = build(index::Geometry::TriangleStrips, &[0, 1, 2, index::RESTART_INDEX, 2, 3, 4]);
+    let submesh = strip.submeshes[0].triangulate().unwrap();
+
+    assert_eq!(submesh.geometry, index::Geometry::Triangles);
+    assert_eq!(submesh.index_count, 6);
+    assert_eq!(submesh.faces().collect::<Vec<_>>(), vec![vec![0, 1, 2], vec![2, 3, 4]]);
+
+    assert!(build(index::Geometry::Lines, &[0, 1]).submeshes[0].triangulate().is_none());
   }
 }
@@ -0,0 +1,213 @@
+use std;
+
+use std::collections::HashMap;
+
+use buffer::ScalarValue;
+
+use math;
+use vertex;
+
+use mesh::{Mesh, MeshBuilder, Stream};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Weighting {
+  Uniform, Area, Angle, AreaAngle
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Options {
+  pub weighting: Weighting,
+  pub crease_angle: f32, // Radians, faces meeting at a sharper angle do not share normals
+  pub format: vertex::Format
+}
+
+impl Options {
+  pub fn default() -> Options {
+    return Options {
+      weighting: Weighting::AreaAngle,
+      crease_angle: std::f32::consts::PI / 3.0,
+      format: vertex::Format(vertex::Scalar::f32, vertex::Width::Vector3)
+    };
+  }
+}
+
+struct Face {
+  submesh: usize,
+  vertices: [usize; 3],
+  normal: math::Vector3,
+  weights: [f32; 3]
+}
+
+// Strips and fans come back as triangle lists, since split vertices cannot be shared between their faces
+pub fn generate(mesh: &Mesh, options: &Options) -> Option<Mesh> {
+  let positions = match mesh.positions() {
+    Some(positions) => positions, None => return None
+  };
+
+  let faces = read_faces(mesh, &positions, options.weighting);
+
+  let keys: Vec<[u32; 3]> = positions.iter().map(|x| math::position_key(*x)).collect();
+
+  let mut corners_at: HashMap<[u32; 3], Vec<(usize, usize)>> = HashMap::new();
+
+  for (f, face) in faces.iter().enumerate() {
+    for c in 0 .. 3 {
+      corners_at.entry(keys[face.vertices[c]]).or_insert_with(Vec::new).push((f, c));
+    }
+  }
+
+  let threshold = options.crease_angle.cos();
+
+  let mut normals: Vec<math::Vector3> = positions.iter().map(|_| [0.0, 0.0, 0.0]).collect();
+  let mut assigned: Vec<bool> = positions.iter().map(|_| false).collect();
+  let mut sources: Vec<usize> = Vec::new();
+  let mut splits: HashMap<(usize, [u32; 3]), usize> = HashMap::new();
+
+  let mut corners: Vec<[usize; 3]> = Vec::with_capacity(faces.len());
+
+  for face in &faces {
+    let mut remapped = [0; 3];
+
+    for c in 0 .. 3 {
+      let v = face.vertices[c];
+      let degenerate = face.normal == [0.0, 0.0, 0.0];
+
+      let normal = math::normalize(corners_at[&keys[v]].iter().fold([0.0, 0.0, 0.0], |sum, &(g, cg)| {
+        let other = &faces[g];
+
+        if degenerate || math::dot(face.normal, other.normal) >= threshold {
+          math::add(sum, math::scale(other.normal, other.weights[cg]))
+        } else {
+          sum
+        }
+      }));
+
+      let split = (v, math::position_key(normal));
+
+      let i = match splits.get(&split) {
+        Some(i) => *i,
+        None if !assigned[v] => {
+          assigned[v] = true;
+          normals[v] = normal;
+
+          v
+        }
+        None => {
+          sources.push(v);
+          normals.push(normal);
+
+          normals.len() - 1
+        }
+      };
+
+      splits.insert(split, i);
+      remapped[c] = i;
+    }
+
+    corners.push(remapped);
+  }
+
+  let triangles: Vec<(usize, [usize; 3])> = faces.iter().zip(corners.iter()).map(|(face, c)| (face.submesh, *c)).collect();
+
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  builder
+    .duplicate_vertices(&sources)
+    .replace_attribute(normal_stream(&normals, options.format))
+    .replace_triangles(&triangles);
+
+  return Some(builder.build());
+}
+
+fn read_faces(mesh: &Mesh, positions: &[math::Vector3], weighting: Weighting) -> Vec<Face> {
+  return mesh.triangles().iter().map(|&(s, face)| {
+    let p = [positions[face[0]], positions[face[1]], positions[face[2]]];
+
+    let cross = math::cross(math::sub(p[1], p[0]), math::sub(p[2], p[0]));
+    let area = math::length(cross);
+
+    let weights = (0 .. 3).map(|c: usize| {
+      let angle = math::angle(math::sub(p[(c + 1) % 3], p[c]), math::sub(p[(c + 2) % 3], p[c]));
+
+      match weighting {
+        Weighting::Uniform => 1.0,
+        Weighting::Area => area,
+        Weighting::Angle => angle,
+        Weighting::AreaAngle => area * angle
+      }
+    }).collect::<Vec<f32>>();
+
+    Face {
+      submesh: s,
+      vertices: face,
+      normal: math::normalize(cross),
+      weights: [weights[0], weights[1], weights[2]]
+    }
+  }).collect();
+}
+
+fn normal_stream(normals: &[math::Vector3], format: vertex::Format) -> Stream {
+  let values = normals.iter().flat_map(|x| ScalarValue::from_f32_vector(format, x).into_iter()).collect();
+
+  return Stream { name: vertex::AttributeName::Normal, format: format, values: values };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std;
+
+  use index;
+  use vertex;
+
+  use mesh::{Mesh, MeshBuilder};
+
+  // Two triangles folded 90 degrees along the edge between vertex 0 and 1
+  fn fold() -> Mesh {
+    return MeshBuilder::new("fold")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2, 1, 0, 3])
+      .build();
+  }
+
+  #[test]
+  fn test_hard_edge() {
+    let mesh = generate(&fold(), &Options::default()).unwrap();
+
+    assert_eq!(mesh.vertex_count, 6);
+
+    let normals = mesh.untyped_view_for(&vertex::AttributeName::Normal).unwrap();
+    let faces: Vec<Vec<usize>> = mesh.submeshes[0].faces().collect();
+
+    for i in &faces[0] {
+      assert_eq!(normals.get_f32(*i), &[0.0, 0.0, 1.0]);
+    }
+
+    for i in &faces[1] {
+      assert_eq!(normals.get_f32(*i), &[0.0, 1.0, 0.0]);
+    }
+  }
+
+  #[test]
+  fn test_smooth() {
+    let options = Options { crease_angle: std::f32::consts::PI, .. Options::default() };
+    let mesh = generate(&fold(), &options).unwrap();
+
+    assert_eq!(mesh.vertex_count, 4);
+
+    let normal = mesh.untyped_view_for(&vertex::AttributeName::Normal).unwrap().get_f32(0);
+
+    assert!((normal[1] - normal[2]).abs() < 1e-6);
+    assert!((normal[1] - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+  }
+
+  #[test]
+  fn test_format() {
+    let options = Options { format: vertex::Format(vertex::Scalar::i8_normalized, vertex::Width::Vector4), .. Options::default() };
+    let mesh = generate(&fold(), &options).unwrap();
+
+    assert_eq!(mesh.attribute_for(&vertex::AttributeName::Normal).unwrap().format, options.format);
+    assert_eq!(mesh.descriptor.layouts[0].stride, 16);
+  }
+}
@@ -0,0 +1,398 @@
+use std::f32;
+
+use index;
+use math;
+
+use mesh::{Mesh, MeshBuilder};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Statistics {
+  pub acmr: f32, // Average cache misses per triangle, 0.5 is the best a regular grid can get
+  pub atvr: f32 // Average cache misses per referenced vertex, 1.0 is ideal
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Report {
+  pub before: Statistics,
+  pub after: Statistics
+}
+
+// Simulates a FIFO post-transform cache like the ones found in most hardware
+pub fn analyze(triangles: &[[usize; 3]], cache_size: usize) -> Statistics {
+  let mut cache: Vec<usize> = Vec::with_capacity(cache_size);
+  let mut referenced: Vec<usize> = Vec::new();
+  let mut misses = 0;
+
+  for triangle in triangles {
+    for v in triangle {
+      if cache.contains(v) {
+        continue;
+      }
+
+      misses += 1;
+
+      if cache.len() == cache_size {
+        cache.remove(0);
+      }
+
+      cache.push(*v);
+      referenced.push(*v);
+    }
+  }
+
+  referenced.sort();
+  referenced.dedup();
+
+  return Statistics {
+    acmr: if triangles.is_empty() { 0.0 } else { misses as f32 / triangles.len() as f32 },
+    atvr: if referenced.is_empty() { 0.0 } else { misses as f32 / referenced.len() as f32 }
+  };
+}
+
+pub fn analyze_mesh(mesh: &Mesh, cache_size: usize) -> Statistics {
+  let triangles: Vec<[usize; 3]> = mesh.triangles().iter().map(|x| x.1).collect();
+
+  return analyze(&triangles, cache_size);
+}
+
+// Reorders the triangles of every triangle submesh with Tom Forsyth's linear-speed algorithm,
+// strips and fans come back as triangle lists
+pub fn optimize_vertex_cache(mesh: &Mesh, cache_size: usize) -> (Mesh, Report) {
+  let before = analyze_mesh(mesh, cache_size);
+
+  let triangles = reorder_areas(mesh, |triangles| forsyth(triangles, mesh.vertex_count, cache_size));
+
+  return rebuild(mesh, &triangles, cache_size, before);
+}
+
+// Sorts clusters of cache-coherent triangles so that the ones facing away from the center of the
+// mesh come first, as in Sander et al. The result is only kept while its ACMR stays within
+// `threshold` times the one of the input
+pub fn optimize_overdraw(mesh: &Mesh, cache_size: usize, threshold: f32) -> (Mesh, Report) {
+  let before = analyze_mesh(mesh, cache_size);
+
+  let positions = match mesh.positions() {
+    Some(positions) => positions,
+    None => return rebuild(mesh, &mesh.triangles(), cache_size, before)
+  };
+
+  let triangles = reorder_areas(mesh, |triangles| {
+    let order = overdraw(triangles, &positions, cache_size);
+    let reordered: Vec<[usize; 3]> = order.iter().map(|t| triangles[*t]).collect();
+
+    if analyze(&reordered, cache_size).acmr <= analyze(triangles, cache_size).acmr * threshold {
+      order
+    } else {
+      (0 .. triangles.len()).collect()
+    }
+  });
+
+  return rebuild(mesh, &triangles, cache_size, before);
+}
+
+// Renumbers vertices in the order the submeshes first use them, so vertex fetches walk the buffers
+// front to back. Vertices no submesh uses are moved to the end
+pub fn optimize_vertex_fetch(mesh: &Mesh, cache_size: usize) -> (Mesh, Report) {
+  let before = analyze_mesh(mesh, cache_size);
+
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  let mut remap: Vec<Option<usize>> = (0 .. mesh.vertex_count).map(|_| None).collect();
+  let mut sources: Vec<usize> = Vec::with_capacity(mesh.vertex_count);
+
+  for area in &builder.areas {
+    for i in area.indices.iter().filter(|x| **x != index::RESTART_INDEX) {
+      if remap[*i as usize].is_none() {
+        remap[*i as usize] = Some(sources.len());
+        sources.push(*i as usize);
+      }
+    }
+  }
+
+  for v in 0 .. mesh.vertex_count {
+    if remap[v].is_none() {
+      remap[v] = Some(sources.len());
+      sources.push(v);
+    }
+  }
+
+  builder.remap_vertices(&remap, &sources);
+
+  let optimized = builder.build();
+  let after = analyze_mesh(&optimized, cache_size);
+
+  return (optimized, Report { before: before, after: after });
+}
+
+// Runs `order` on the triangles of each submesh, it returns the new order as indices into them
+fn reorder_areas<F>(mesh: &Mesh, order: F) -> Vec<(usize, [usize; 3])> where F: Fn(&[[usize; 3]]) -> Vec<usize> {
+  let triangles = mesh.triangles();
+
+  let mut reordered = Vec::with_capacity(triangles.len());
+
+  for s in 0 .. mesh.submeshes.len() {
+    let area: Vec<[usize; 3]> = triangles.iter().filter(|x| x.0 == s).map(|x| x.1).collect();
+
+    reordered.extend(order(&area).into_iter().map(|t| (s, area[t])));
+  }
+
+  return reordered;
+}
+
+fn rebuild(mesh: &Mesh, triangles: &[(usize, [usize; 3])], cache_size: usize, before: Statistics) -> (Mesh, Report) {
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  builder.replace_triangles(triangles);
+
+  let optimized = builder.build();
+  let after = analyze_mesh(&optimized, cache_size);
+
+  return (optimized, Report { before: before, after: after });
+}
+
+const FORSYTH_CACHE_DECAY_POWER: f32 = 1.5;
+const FORSYTH_LAST_TRIANGLE_SCORE: f32 = 0.75;
+const FORSYTH_VALENCE_BOOST_SCALE: f32 = 2.0;
+const FORSYTH_VALENCE_BOOST_POWER: f32 = 0.5;
+
+fn forsyth_score(cache_position: Option<usize>, cache_size: usize, remaining: usize) -> f32 {
+  if remaining == 0 {
+    return -1.0;
+  }
+
+  let cache_score = match cache_position {
+    None => 0.0,
+    // The vertices of the last triangle get the same score whatever order they were used in
+    Some(p) if p < 3 => FORSYTH_LAST_TRIANGLE_SCORE,
+    Some(p) => (1.0 - (p - 3) as f32 / (cache_size - 3) as f32).powf(FORSYTH_CACHE_DECAY_POWER)
+  };
+
+  return cache_score + FORSYTH_VALENCE_BOOST_SCALE * (remaining as f32).powf(-FORSYTH_VALENCE_BOOST_POWER);
+}
+
+fn forsyth(triangles: &[[usize; 3]], vertex_count: usize, cache_size: usize) -> Vec<usize> {
+  // Scores fall off past the last triangle, which needs a cache larger than one
+  let cache_size = cache_size.max(4);
+
+  let mut adjacency: Vec<Vec<usize>> = (0 .. vertex_count).map(|_| Vec::new()).collect();
+
+  for (t, triangle) in triangles.iter().enumerate() {
+    for v in triangle {
+      adjacency[*v].push(t);
+    }
+  }
+
+  let mut vertex_scores: Vec<f32> = adjacency.iter().map(|x| forsyth_score(None, cache_size, x.len())).collect();
+  let mut triangle_scores: Vec<f32> = triangles.iter().map(|x| x.iter().fold(0.0, |sum, v| sum + vertex_scores[*v])).collect();
+  let mut emitted: Vec<bool> = triangles.iter().map(|_| false).collect();
+
+  let mut cache: Vec<usize> = Vec::with_capacity(cache_size + 3);
+  let mut order = Vec::with_capacity(triangles.len());
+
+  let mut best = if triangles.is_empty() { None } else { Some(0) };
+
+  while let Some(t) = best {
+    order.push(t);
+    emitted[t] = true;
+
+    // The triangle goes to the front of the cache, the vertices it pushes out are rescored too
+    let mut updated: Vec<usize> = triangles[t].to_vec();
+
+    updated.extend(cache.iter().filter(|v| !triangles[t].contains(v)));
+
+    cache = updated.iter().take(cache_size).cloned().collect();
+
+    for v in &triangles[t] {
+      adjacency[*v].retain(|x| *x != t);
+    }
+
+    for (p, v) in updated.iter().enumerate() {
+      let position = if p < cache_size { Some(p) } else { None };
+      let score = forsyth_score(position, cache_size, adjacency[*v].len());
+      let delta = score - vertex_scores[*v];
+
+      vertex_scores[*v] = score;
+
+      for x in &adjacency[*v] {
+        triangle_scores[*x] += delta;
+      }
+    }
+
+    best = None;
+
+    let mut best_score = f32::NEG_INFINITY;
+
+    for v in &cache {
+      for x in &adjacency[*v] {
+        if triangle_scores[*x] > best_score {
+          best = Some(*x);
+          best_score = triangle_scores[*x];
+        }
+      }
+    }
+
+    // Nothing left around the cache, start over at the best remaining triangle
+    if best.is_none() {
+      for x in (0 .. triangles.len()).filter(|x| !emitted[*x]) {
+        if triangle_scores[x] > best_score {
+          best = Some(x);
+          best_score = triangle_scores[x];
+        }
+      }
+    }
+  }
+
+  return order;
+}
+
+fn overdraw(triangles: &[[usize; 3]], positions: &[math::Vector3], cache_size: usize) -> Vec<usize> {
+  if triangles.is_empty() {
+    return Vec::new();
+  }
+
+  // A new cluster starts wherever the cache is flushed, which keeps the cache behaviour of each
+  // cluster intact however they are ordered
+  let mut clusters: Vec<Vec<usize>> = Vec::new();
+  let mut cache: Vec<usize> = Vec::with_capacity(cache_size);
+
+  for (t, triangle) in triangles.iter().enumerate() {
+    let mut misses = 0;
+
+    for v in triangle {
+      if cache.contains(v) {
+        continue;
+      }
+
+      misses += 1;
+
+      if cache.len() == cache_size {
+        cache.remove(0);
+      }
+
+      cache.push(*v);
+    }
+
+    if misses == 3 || clusters.is_empty() {
+      clusters.push(Vec::new());
+    }
+
+    clusters.last_mut().unwrap().push(t);
+  }
+
+  let area_weighted = |cluster: &[usize]| {
+    cluster.iter().fold(([0.0, 0.0, 0.0], [0.0, 0.0, 0.0], 0.0), |(centroid, normal, area), t| {
+      let p = [positions[triangles[*t][0]], positions[triangles[*t][1]], positions[triangles[*t][2]]];
+
+      let cross = math::cross(math::sub(p[1], p[0]), math::sub(p[2], p[0]));
+      let a = math::length(cross);
+      let center = math::scale(math::add(math::add(p[0], p[1]), p[2]), 1.0 / 3.0);
+
+      (math::add(centroid, math::scale(center, a)), math::add(normal, cross), area + a)
+    })
+  };
+
+  let all: Vec<usize> = (0 .. triangles.len()).collect();
+  let (mesh_centroid, _, mesh_area) = area_weighted(&all);
+  let mesh_centroid = if mesh_area > 0.0 { math::scale(mesh_centroid, 1.0 / mesh_area) } else { mesh_centroid };
+
+  let mut keyed: Vec<(f32, &Vec<usize>)> = clusters.iter().map(|cluster| {
+    let (centroid, normal, area) = area_weighted(cluster);
+    let centroid = if area > 0.0 { math::scale(centroid, 1.0 / area) } else { centroid };
+
+    (math::dot(math::sub(centroid, mesh_centroid), math::normalize(normal)), cluster)
+  }).collect();
+
+  // Clusters facing out the most are drawn first, the sort is stable so ties keep their order
+  keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(::std::cmp::Ordering::Equal));
+
+  return keyed.iter().flat_map(|x| x.1.iter().cloned()).collect();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use index;
+  use vertex;
+
+  use mesh::{Mesh, MeshBuilder};
+
+  // A grid of size * size quads with its triangles in a scattered order
+  fn grid(size: u32) -> Mesh {
+    let mut positions = Vec::new();
+
+    for y in 0 .. size + 1 {
+      for x in 0 .. size + 1 {
+        positions.push([x as f32, y as f32, 0.0]);
+      }
+    }
+
+    let mut triangles = Vec::new();
+
+    for y in 0 .. size {
+      for x in 0 .. size {
+        let i = y * (size + 1) + x;
+
+        triangles.push([i, i + 1, i + size + 2]);
+        triangles.push([i, i + size + 2, i + size + 1]);
+      }
+    }
+
+    let count = triangles.len();
+    let indices: Vec<u32> = (0 .. count).flat_map(|t| triangles[(t * 37) % count].to_vec().into_iter()).collect();
+
+    return MeshBuilder::new("grid")
+      .positions(&positions)
+      .submesh("hull", index::Geometry::Triangles, &indices)
+      .build();
+  }
+
+  #[test]
+  fn test_analyze() {
+    let statistics = analyze(&[[0, 1, 2], [2, 1, 3]], 16);
+
+    assert_eq!(statistics.acmr, 2.0);
+    assert_eq!(statistics.atvr, 1.0);
+  }
+
+  #[test]
+  fn test_vertex_cache() {
+    let mesh = grid(16);
+    let (optimized, report) = optimize_vertex_cache(&mesh, 16);
+
+    assert!(report.after.acmr < report.before.acmr);
+    assert!(report.after.atvr < 1.5);
+    assert_eq!(optimized.submeshes[0].faces().len(), 512);
+
+    let (_, report) = optimize_vertex_cache(&mesh, 6);
+
+    assert!(report.after.acmr < report.before.acmr);
+  }
+
+  #[test]
+  fn test_overdraw() {
+    let (mesh, _) = optimize_vertex_cache(&grid(8), 16);
+    let (optimized, report) = optimize_overdraw(&mesh, 16, 1.05);
+
+    assert!(report.after.acmr <= report.before.acmr * 1.05);
+    assert_eq!(optimized.submeshes[0].faces().len(), 128);
+  }
+
+  #[test]
+  fn test_vertex_fetch() {
+    let mesh = MeshBuilder::new("triangle")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [9.0, 9.0, 9.0]])
+      .submesh("hull", index::Geometry::Triangles, &[2, 0, 1])
+      .build();
+
+    let (optimized, report) = optimize_vertex_fetch(&mesh, 16);
+
+    assert_eq!(report.before, report.after);
+    assert_eq!(optimized.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 1, 2]]);
+
+    let positions = optimized.untyped_view_for(&vertex::AttributeName::Position).unwrap();
+
+    assert_eq!(positions.get_f32(0), &[0.0, 1.0, 0.0]);
+    assert_eq!(positions.get_f32(3), &[9.0, 9.0, 9.0]);
+  }
+}
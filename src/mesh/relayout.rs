@@ -0,0 +1,131 @@
+use std::mem;
+
+use vertex;
+
+use mesh::{Layout, Mesh, MeshBuilder};
+
+pub fn relayout(mesh: &Mesh, layout: Layout) -> Mesh {
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  builder.layout(layout);
+
+  return builder.build();
+}
+
+pub fn interleave(mesh: &Mesh) -> Mesh {
+  return relayout(mesh, Layout::Interleaved);
+}
+
+pub fn deinterleave(mesh: &Mesh) -> Mesh {
+  return relayout(mesh, Layout::Separate);
+}
+
+// Attributes that are not part of any group are stripped, None when that would be all of them
+pub fn group(mesh: &Mesh, groups: &[Vec<vertex::AttributeName>]) -> Option<Mesh> {
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  let buffer_indices: Vec<Option<usize>> = builder.streams.iter().map(|stream| {
+    groups.iter().position(|group| group.contains(&stream.name))
+  }).collect();
+
+  if buffer_indices.iter().all(|x| x.is_none()) {
+    return None;
+  }
+
+  let streams = mem::replace(&mut builder.streams, Vec::new()).into_iter().zip(buffer_indices.iter()).filter(|&(_, i)| i.is_some()).map(|(x, _)| x).collect();
+
+  builder.streams = streams;
+  builder.layout(Layout::Grouped(compact(buffer_indices.into_iter().filter_map(|x| x).collect())));
+
+  return Some(builder.build());
+}
+
+// None when every attribute would be stripped, as a mesh needs at least one
+pub fn strip(mesh: &Mesh, names: &[vertex::AttributeName]) -> Option<Mesh> {
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  let keep: Vec<bool> = builder.streams.iter().map(|x| !names.contains(&x.name)).collect();
+
+  if !keep.contains(&true) {
+    return None;
+  }
+
+  let buffer_indices = builder.buffer_indices();
+
+  let streams = mem::replace(&mut builder.streams, Vec::new()).into_iter().zip(keep.iter()).filter(|&(_, k)| *k).map(|(x, _)| x).collect();
+  let buffer_indices = buffer_indices.into_iter().zip(keep.iter()).filter(|&(_, k)| *k).map(|(x, _)| x).collect();
+
+  builder.streams = streams;
+  builder.layout(Layout::Grouped(compact(buffer_indices)));
+
+  return Some(builder.build());
+}
+
+// Renumbers buffer indices so that buffers left without attributes disappear
+fn compact(buffer_indices: Vec<usize>) -> Vec<usize> {
+  let mut used: Vec<usize> = buffer_indices.clone();
+
+  used.sort();
+  used.dedup();
+
+  return buffer_indices.iter().map(|x| used.iter().position(|y| y == x).unwrap()).collect();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use index;
+  use vertex;
+
+  use mesh::{Layout, MeshBuilder};
+
+  fn mesh() -> Mesh {
+    return MeshBuilder::new("triangle")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]])
+      .normals(&[[0.0, 0.0, 1.0]; 3])
+      .texture_coordinates(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2])
+      .build();
+  }
+
+  #[test]
+  fn test_deinterleave() {
+    let mesh = deinterleave(&mesh());
+
+    assert_eq!(mesh.buffers.len(), 3);
+    assert_eq!(mesh.untyped_view_for(&vertex::AttributeName::TextureCoordinate).unwrap().get_f32(1), &[1.0, 0.0]);
+
+    let mesh = interleave(&mesh);
+
+    assert_eq!(mesh.buffers.len(), 1);
+    assert_eq!(mesh.descriptor.layouts[0].stride, 32);
+  }
+
+  #[test]
+  fn test_group() {
+    let groups = vec![vec![vertex::AttributeName::Position], vec![vertex::AttributeName::TextureCoordinate]];
+    let mesh = group(&mesh(), &groups).unwrap();
+
+    assert_eq!(mesh.buffers.len(), 2);
+    assert!(mesh.attribute_for(&vertex::AttributeName::Normal).is_none());
+    assert_eq!(mesh.attribute_for(&vertex::AttributeName::TextureCoordinate).unwrap().buffer_index, 1);
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 1, 2]]);
+
+    assert!(group(&mesh, &[vec![vertex::AttributeName::Normal]]).is_none());
+    assert!(group(&mesh, &[]).is_none());
+  }
+
+  #[test]
+  fn test_strip() {
+    let mesh = relayout(&mesh(), Layout::Grouped(vec![0, 1, 2]));
+    let mesh = strip(&mesh, &[vertex::AttributeName::Normal]).unwrap();
+
+    assert_eq!(mesh.buffers.len(), 2);
+    assert_eq!(mesh.descriptor.attributes.len(), 2);
+    assert_eq!(mesh.attribute_for(&vertex::AttributeName::TextureCoordinate).unwrap().buffer_index, 1);
+    assert_eq!(mesh.untyped_view_for(&vertex::AttributeName::Position).unwrap().get_f32(2), &[0.0, 1.0, 0.0]);
+
+    assert!(strip(&mesh, &[vertex::AttributeName::Position, vertex::AttributeName::TextureCoordinate]).is_none());
+  }
+}
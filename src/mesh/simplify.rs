@@ -0,0 +1,561 @@
+use std::mem;
+
+use std::collections::HashMap;
+
+use asset;
+use math;
+
+use mesh::{Mesh, MeshBuilder};
+use mesh::weld;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Options {
+  pub target_triangles: usize,
+  pub target_error: f32 // Relative to the diagonal of the bounding box of the mesh
+}
+
+impl Options {
+  pub fn default() -> Options {
+    return Options { target_triangles: 0, target_error: 0.01 };
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LodOptions {
+  pub ratios: Vec<f32>, // Fraction of the triangles of the original mesh each level aims for
+  pub target_error: f32
+}
+
+impl LodOptions {
+  pub fn default() -> LodOptions {
+    return LodOptions { ratios: vec![0.5, 0.25, 0.125], target_error: 0.05 };
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Quadric {
+  q: [f64; 10],
+  weight: f64
+}
+
+impl Quadric {
+  fn new() -> Quadric {
+    return Quadric { q: [0.0; 10], weight: 0.0 };
+  }
+
+  // The plane through a triangle, weighted by its area
+  fn from_triangle(p: [math::Vector3; 3]) -> Quadric {
+    let cross = math::cross(math::sub(p[1], p[0]), math::sub(p[2], p[0]));
+    let area = math::length(cross) as f64;
+
+    let n = math::normalize(cross);
+    let (a, b, c) = (n[0] as f64, n[1] as f64, n[2] as f64);
+    let d = -math::dot(n, p[0]) as f64;
+
+    let q = [a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d];
+
+    return Quadric { q: [q[0] * area, q[1] * area, q[2] * area, q[3] * area, q[4] * area, q[5] * area, q[6] * area, q[7] * area, q[8] * area, q[9] * area], weight: area };
+  }
+
+  fn add(&self, other: &Quadric) -> Quadric {
+    let mut q = self.q;
+
+    for i in 0 .. 10 {
+      q[i] += other.q[i];
+    }
+
+    return Quadric { q: q, weight: self.weight + other.weight };
+  }
+
+  // Mean squared distance from the planes
+  fn error(&self, p: math::Vector3) -> f64 {
+    if self.weight == 0.0 {
+      return 0.0;
+    }
+
+    let (x, y, z) = (p[0] as f64, p[1] as f64, p[2] as f64);
+    let q = &self.q;
+
+    let e = q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+          + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+          + q[7] * z * z + 2.0 * q[8] * z
+          + q[9];
+
+    return e.abs() / self.weight;
+  }
+}
+
+// Collapses edges onto one of their existing vertices, so every remaining vertex keeps its own
+// normal and texture coordinates. Vertices split along a UV or normal seam move together, open
+// borders and boundaries between submeshes never move. Returns the simplified mesh and the error it
+// reached, relative like `target_error`
+pub fn simplify(mesh: &Mesh, options: &Options) -> Option<(Mesh, f32)> {
+  let positions = match mesh.positions() {
+    Some(positions) => positions, None => return None
+  };
+
+  let mut triangles = mesh.triangles();
+
+  let extent = bounding_box_diagonal(&positions, &triangles);
+  let scale = if extent > 0.0 { 1.0 / extent as f64 } else { 1.0 };
+
+  let (group_of, groups) = position_groups(&positions);
+  let locked = locked_groups(&group_of, groups.len(), &triangles);
+
+  let mut quadrics: Vec<Quadric> = groups.iter().map(|_| Quadric::new()).collect();
+  let mut adjacency: Vec<Vec<usize>> = positions.iter().map(|_| Vec::new()).collect();
+
+  for (t, &(_, face)) in triangles.iter().enumerate() {
+    let quadric = Quadric::from_triangle([positions[face[0]], positions[face[1]], positions[face[2]]]);
+
+    for v in &face {
+      quadrics[group_of[*v]] = quadrics[group_of[*v]].add(&quadric);
+      adjacency[*v].push(t);
+    }
+  }
+
+  let max_error = options.target_error as f64 / scale;
+  let max_cost = max_error * max_error;
+
+  let mut alive: Vec<bool> = triangles.iter().map(|_| true).collect();
+  let mut live = triangles.len();
+  let mut reached: f64 = 0.0;
+
+  while live > options.target_triangles {
+    let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+
+    for (t, &(_, face)) in triangles.iter().enumerate() {
+      if !alive[t] {
+        continue;
+      }
+
+      for c in 0 .. 3 {
+        for &(from, to) in &[(face[c], face[(c + 1) % 3]), (face[(c + 1) % 3], face[c])] {
+          let (a, b) = (group_of[from], group_of[to]);
+
+          if !locked[a] && a != b {
+            candidates.push((quadrics[a].add(&quadrics[b]).error(positions[to]), from, to));
+          }
+        }
+      }
+    }
+
+    candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+
+    // Each pass collapses every edge it can without touching a neighbourhood twice, as the costs
+    // around a collapse are stale until the next pass
+    let mut touched: Vec<bool> = groups.iter().map(|_| false).collect();
+    let mut collapsed = 0;
+
+    for &(cost, from, to) in &candidates {
+      if live <= options.target_triangles || cost > max_cost {
+        break;
+      }
+
+      let (a, b) = (group_of[from], group_of[to]);
+
+      if touched[a] || touched[b] || !link_condition(&triangles, &alive, &adjacency, &group_of, &groups, a, b) {
+        continue;
+      }
+
+      let pairs = match wedge_pairs(&triangles, &alive, &adjacency, &groups, a, b) {
+        Some(pairs) => pairs, None => continue
+      };
+
+      if !pairs.iter().all(|&(f, t)| can_collapse(&positions, &triangles, &alive, &adjacency[f], &group_of, f, t)) {
+        continue;
+      }
+
+      for &(f, t) in &pairs {
+        for x in mem::replace(&mut adjacency[f], Vec::new()) {
+          if !alive[x] {
+            continue;
+          }
+
+          let face = &mut triangles[x].1;
+
+          if face.iter().any(|v| group_of[*v] == b) {
+            alive[x] = false;
+            live -= 1;
+          } else {
+            for v in face.iter_mut() {
+              if *v == f { *v = t };
+            }
+
+            adjacency[t].push(x);
+          }
+        }
+      }
+
+      quadrics[b] = quadrics[b].add(&quadrics[a]);
+      reached = reached.max(cost);
+      collapsed += 1;
+
+      for v in &groups[b] {
+        for x in adjacency[*v].iter().filter(|x| alive[**x]) {
+          for w in &triangles[*x].1 {
+            touched[group_of[*w]] = true;
+          }
+        }
+      }
+
+      touched[a] = true;
+    }
+
+    if collapsed == 0 {
+      break;
+    }
+  }
+
+  let remaining: Vec<(usize, [usize; 3])> = triangles.into_iter().zip(alive.into_iter()).filter(|x| x.1).map(|x| x.0).collect();
+
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  builder.replace_triangles(&remaining);
+
+  return Some((weld::remove_unused(&builder.build()), (reached.sqrt() * scale) as f32));
+}
+
+// Each level is simplified from the one before it, the chain stops early once a level no longer
+// gets any smaller
+pub fn lod_chain(mesh: &Mesh, options: &LodOptions) -> Vec<Mesh> {
+  let triangle_count = mesh.triangles().len();
+
+  let mut lods: Vec<Mesh> = Vec::new();
+
+  for (level, ratio) in options.ratios.iter().enumerate() {
+    let simplified = {
+      let source = lods.last().unwrap_or(mesh);
+      let target = Options { target_triangles: (triangle_count as f32 * ratio) as usize, target_error: options.target_error };
+
+      match simplify(source, &target) {
+        Some((simplified, _)) if simplified.triangles().len() < source.triangles().len() => simplified,
+        _ => break
+      }
+    };
+
+    lods.push(Mesh { name: format!("{}_lod{}", mesh.name, level + 1), .. simplified });
+  }
+
+  return lods;
+}
+
+pub fn generate_lods(asset: &mut asset::Asset, options: &LodOptions) {
+  for object in &mut asset.objects {
+    match object {
+      &mut asset::Object::Model(ref mut model) => model.lods = lod_chain(&model.mesh, options),
+      _ => ()
+    }
+  }
+}
+
+fn can_collapse(positions: &[math::Vector3], triangles: &[(usize, [usize; 3])], alive: &[bool], adjacency: &[usize], group_of: &[usize], from: usize, to: usize) -> bool {
+  for t in adjacency.iter().filter(|t| alive[**t]) {
+    let face = triangles[*t].1;
+
+    if face.iter().any(|v| group_of[*v] == group_of[to]) {
+      continue;
+    }
+
+    let p = [positions[face[0]], positions[face[1]], positions[face[2]]];
+    let q = [0, 1, 2].iter().map(|c| if face[*c] == from { positions[to] } else { p[*c] }).collect::<Vec<_>>();
+
+    let before = math::normalize(math::cross(math::sub(p[1], p[0]), math::sub(p[2], p[0])));
+    let after = math::normalize(math::cross(math::sub(q[1], q[0]), math::sub(q[2], q[0])));
+
+    // Faces may not flip over or collapse to nothing
+    if math::dot(before, after) <= 0.0 {
+      return false;
+    }
+  }
+
+  return true;
+}
+
+// Groups of vertices at the same position, the wedges of one corner of the surface
+fn position_groups(positions: &[math::Vector3]) -> (Vec<usize>, Vec<Vec<usize>>) {
+  let mut indices: HashMap<[u32; 3], usize> = HashMap::new();
+  let mut group_of: Vec<usize> = Vec::with_capacity(positions.len());
+  let mut groups: Vec<Vec<usize>> = Vec::new();
+
+  for (v, p) in positions.iter().enumerate() {
+    let g = *indices.entry(math::position_key(*p)).or_insert(groups.len());
+
+    if g == groups.len() {
+      groups.push(Vec::new());
+    }
+
+    groups[g].push(v);
+    group_of.push(g);
+  }
+
+  return (group_of, groups);
+}
+
+// The groups on any face around `group`, other than itself
+fn neighbours(triangles: &[(usize, [usize; 3])], alive: &[bool], adjacency: &[Vec<usize>], group_of: &[usize], groups: &[Vec<usize>], group: usize) -> Vec<usize> {
+  let mut result = Vec::new();
+
+  for v in &groups[group] {
+    for t in adjacency[*v].iter().filter(|t| alive[**t]) {
+      for w in &triangles[*t].1 {
+        if group_of[*w] != group {
+          result.push(group_of[*w]);
+        }
+      }
+    }
+  }
+
+  result.sort();
+  result.dedup();
+
+  return result;
+}
+
+// Collapsing an edge keeps the surface manifold only when the neighbours its ends share are the
+// corners opposite the edge, anything else gets pinched together
+fn link_condition(triangles: &[(usize, [usize; 3])], alive: &[bool], adjacency: &[Vec<usize>], group_of: &[usize], groups: &[Vec<usize>], a: usize, b: usize) -> bool {
+  let around_b = neighbours(triangles, alive, adjacency, group_of, groups, b);
+
+  let shared: Vec<usize> = neighbours(triangles, alive, adjacency, group_of, groups, a).into_iter().filter(|x| *x != b && around_b.contains(x)).collect();
+
+  let mut opposite = Vec::new();
+
+  for v in &groups[a] {
+    for t in adjacency[*v].iter().filter(|t| alive[**t]) {
+      let face = triangles[*t].1;
+
+      if face.iter().any(|w| group_of[*w] == b) {
+        opposite.extend(face.iter().map(|w| group_of[*w]).filter(|g| *g != a && *g != b));
+      }
+    }
+  }
+
+  opposite.sort();
+  opposite.dedup();
+
+  return shared == opposite;
+}
+
+// Every wedge of `from` in use moves onto a wedge of `to` it shares an edge with, so a seam stays
+// closed. None when one of them has no such partner
+fn wedge_pairs(triangles: &[(usize, [usize; 3])], alive: &[bool], adjacency: &[Vec<usize>], groups: &[Vec<usize>], from: usize, to: usize) -> Option<Vec<(usize, usize)>> {
+  let mut pairs = Vec::new();
+
+  for f in &groups[from] {
+    let faces: Vec<usize> = adjacency[*f].iter().cloned().filter(|t| alive[*t]).collect();
+
+    if faces.is_empty() {
+      continue;
+    }
+
+    match faces.iter().flat_map(|t| triangles[*t].1.iter()).find(|v| groups[to].contains(v)) {
+      Some(t) => pairs.push((*f, *t)),
+      None => return None
+    }
+  }
+
+  return Some(pairs);
+}
+
+// Open borders, edges shared by more than two faces and corners of several submeshes stay where
+// they are. Seams between wedges are not borders, as edges are counted between groups
+fn locked_groups(group_of: &[usize], group_count: usize, triangles: &[(usize, [usize; 3])]) -> Vec<bool> {
+  let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+  let mut submeshes: Vec<Option<usize>> = (0 .. group_count).map(|_| None).collect();
+  let mut locked: Vec<bool> = (0 .. group_count).map(|_| false).collect();
+
+  for &(s, face) in triangles {
+    for c in 0 .. 3 {
+      let (a, b) = (group_of[face[c]], group_of[face[(c + 1) % 3]]);
+
+      *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+
+      match submeshes[a] {
+        Some(other) if other != s => locked[a] = true,
+        _ => submeshes[a] = Some(s)
+      };
+    }
+  }
+
+  for (&(a, b), count) in &edges {
+    if *count != 2 {
+      locked[a] = true;
+      locked[b] = true;
+    }
+  }
+
+  return locked;
+}
+
+fn bounding_box_diagonal(positions: &[math::Vector3], triangles: &[(usize, [usize; 3])]) -> f32 {
+  let mut min = [::std::f32::MAX; 3];
+  let mut max = [::std::f32::MIN; 3];
+
+  for &(_, face) in triangles {
+    for v in &face {
+      for i in 0 .. 3 {
+        min[i] = min[i].min(positions[*v][i]);
+        max[i] = max[i].max(positions[*v][i]);
+      }
+    }
+  }
+
+  return if triangles.is_empty() { 0.0 } else { math::length(math::sub(max, min)) };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use index;
+  use vertex;
+
+  use mesh::{Mesh, MeshBuilder};
+
+  // A flat grid of size * size quads, split into a left and a right submesh
+  fn grid(size: u32, height: &Fn(u32, u32) -> f32) -> Mesh {
+    let mut positions = Vec::new();
+
+    for y in 0 .. size + 1 {
+      for x in 0 .. size + 1 {
+        positions.push([x as f32, y as f32, height(x, y)]);
+      }
+    }
+
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+
+    for y in 0 .. size {
+      for x in 0 .. size {
+        let i = y * (size + 1) + x;
+        let area = if x < size / 2 { &mut left } else { &mut right };
+
+        area.extend_from_slice(&[i, i + 1, i + size + 2, i, i + size + 2, i + size + 1]);
+      }
+    }
+
+    return MeshBuilder::new("grid")
+      .positions(&positions)
+      .normals(&positions.iter().map(|_| [0.0, 0.0, 1.0]).collect::<Vec<_>>())
+      .submesh("left", index::Geometry::Triangles, &left)
+      .submesh("right", index::Geometry::Triangles, &right)
+      .build();
+  }
+
+  #[test]
+  fn test_simplify() {
+    let mesh = grid(8, &|_, _| 0.0);
+    let (simplified, error) = simplify(&mesh, &Options::default()).unwrap();
+
+    assert!(simplified.triangles().len() < mesh.triangles().len() / 2);
+    assert!(simplified.vertex_count < mesh.vertex_count);
+    assert_eq!(error, 0.0);
+
+    // The column between the submeshes survives, as does every border vertex
+    let positions = simplified.positions().unwrap();
+
+    for y in 0 .. 9 {
+      assert!(positions.contains(&[4.0, y as f32, 0.0]));
+      assert!(positions.contains(&[0.0, y as f32, 0.0]));
+    }
+
+    for submesh in &simplified.submeshes {
+      assert!(submesh.faces().len() > 0);
+    }
+  }
+
+  #[test]
+  fn test_seams() {
+    let mesh = grid(8, &|_, _| 0.0);
+
+    // The column at x = 2 is split, quads right of it use copies with another normal
+    let positions = mesh.positions().unwrap();
+    let seam: Vec<usize> = (0 .. positions.len()).filter(|v| positions[*v][0] == 2.0).collect();
+
+    let mut split_positions = positions.clone();
+    let mut normals: Vec<[f32; 3]> = positions.iter().map(|_| [0.0, 0.0, 1.0]).collect();
+
+    for v in &seam {
+      split_positions.push(positions[*v]);
+      normals.push([0.0, 1.0, 0.0]);
+    }
+
+    let triangles: Vec<u32> = mesh.triangles().iter().flat_map(|&(_, face)| {
+      let right = face.iter().any(|v| positions[*v][0] > 2.0);
+
+      face.iter().map(|v| match seam.iter().position(|x| x == v) {
+        Some(i) if right => (positions.len() + i) as u32,
+        _ => *v as u32
+      }).collect::<Vec<_>>()
+    }).collect();
+
+    let mesh = MeshBuilder::new("seam")
+      .positions(&split_positions)
+      .normals(&normals)
+      .submesh("hull", index::Geometry::Triangles, &triangles)
+      .build();
+
+    let (simplified, _) = simplify(&mesh, &Options::default()).unwrap();
+
+    assert!(simplified.triangles().len() < mesh.triangles().len() / 2);
+
+    // Both sides of the seam are left at the same positions
+    let positions = simplified.positions().unwrap();
+    let normals = simplified.untyped_view_for(&vertex::AttributeName::Normal).unwrap();
+
+    let mut left: Vec<f32> = (0 .. positions.len()).filter(|v| positions[*v][0] == 2.0 && normals.get_f32(*v)[2] == 1.0).map(|v| positions[v][1]).collect();
+    let mut right: Vec<f32> = (0 .. positions.len()).filter(|v| positions[*v][0] == 2.0 && normals.get_f32(*v)[1] == 1.0).map(|v| positions[v][1]).collect();
+
+    left.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    right.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    assert!(left.len() < 9);
+    assert_eq!(left, right);
+  }
+
+  #[test]
+  fn test_link_condition() {
+    // A closed bipyramid over a triangle, the two apexes come first
+    let triangles = vec![(0, [0, 2, 3]), (0, [0, 3, 4]), (0, [0, 4, 2]), (0, [1, 3, 2]), (0, [1, 4, 3]), (0, [1, 2, 4])];
+    let alive = vec![true; 6];
+
+    let mut adjacency: Vec<Vec<usize>> = (0 .. 5).map(|_| Vec::new()).collect();
+
+    for (t, &(_, face)) in triangles.iter().enumerate() {
+      for v in &face {
+        adjacency[*v].push(t);
+      }
+    }
+
+    let group_of: Vec<usize> = (0 .. 5).collect();
+    let groups: Vec<Vec<usize>> = (0 .. 5).map(|x| vec![x]).collect();
+
+    // Both ends of a ring edge are next to the third ring vertex, but no face holds all three
+    assert!(!link_condition(&triangles, &alive, &adjacency, &group_of, &groups, 2, 3));
+    assert!(link_condition(&triangles, &alive, &adjacency, &group_of, &groups, 0, 2));
+  }
+
+  #[test]
+  fn test_target_error() {
+    let mesh = grid(8, &|x, y| if x == 2 && y == 4 { 2.0 } else { 0.0 });
+    let (simplified, _) = simplify(&mesh, &Options::default()).unwrap();
+
+    assert!(simplified.positions().unwrap().contains(&[2.0, 4.0, 2.0]));
+
+    let (simplified, error) = simplify(&mesh, &Options { target_error: 1.0, .. Options::default() }).unwrap();
+
+    assert!(!simplified.positions().unwrap().contains(&[2.0, 4.0, 2.0]));
+    assert!(error > 0.0);
+  }
+
+  #[test]
+  fn test_lod_chain() {
+    let mesh = grid(8, &|_, _| 0.0);
+    let lods = lod_chain(&mesh, &LodOptions::default());
+
+    assert!(lods.len() >= 1);
+    assert_eq!(lods[0].name, "grid_lod1");
+    assert!(lods[0].triangles().len() <= mesh.triangles().len() / 2);
+  }
+}
@@ -0,0 +1,267 @@
+use std::mem;
+
+use std::collections::HashMap;
+
+use buffer::ScalarValue;
+
+use math;
+use vertex;
+
+use mesh::{Mesh, MeshBuilder, Stream};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Options {
+  pub uv_set: usize, // Which of the texture coordinate attributes the tangents follow
+  pub format: vertex::Format,
+  pub bitangent_format: Option<vertex::Format> // Bitangents are cross(normal, tangent) * handedness
+}
+
+impl Options {
+  pub fn default() -> Options {
+    return Options {
+      uv_set: 0,
+      format: vertex::Format(vertex::Scalar::f32, vertex::Width::Vector4),
+      bitangent_format: None
+    };
+  }
+}
+
+// Corners that share a position, normal and texture coordinate and whose faces have the same
+// texture orientation share a tangent, as in MikkTSpace
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+struct Key {
+  position: [u32; 3],
+  normal: [u32; 3],
+  uv: [u32; 2],
+  orientation: bool
+}
+
+pub fn generate(mesh: &Mesh, options: &Options) -> Option<Mesh> {
+  let positions = match mesh.positions() {
+    Some(positions) => positions, None => return None
+  };
+
+  let normals: Vec<math::Vector3> = match mesh.untyped_view_for(&vertex::AttributeName::Normal) {
+    Some(view) => (0 .. mesh.vertex_count).map(|i| math::normalize(math::to_vector3(&view.get_f32(i)))).collect(),
+    None => return None
+  };
+
+  let uvs: Vec<[f32; 2]> = match mesh.descriptor.attributes.iter().filter(|x| x.name == vertex::AttributeName::TextureCoordinate).nth(options.uv_set) {
+    Some(attribute) => {
+      let view = mesh.untyped_view_for_attribute(attribute);
+
+      (0 .. mesh.vertex_count).map(|i| {
+        let uv = view.get_f32(i);
+
+        [uv[0], if uv.len() > 1 { uv[1] } else { 0.0 }]
+      }).collect()
+    }
+    None => return None
+  };
+
+  let triangles = mesh.triangles();
+
+  let key = |v: usize, orientation: bool| Key {
+    position: math::position_key(positions[v]),
+    normal: math::position_key(normals[v]),
+    uv: unsafe { mem::transmute([uvs[v][0] + 0.0, uvs[v][1] + 0.0]) },
+    orientation: orientation
+  };
+
+  let mut orientations: Vec<bool> = Vec::with_capacity(triangles.len());
+  let mut sums: HashMap<Key, math::Vector3> = HashMap::new();
+
+  for &(_, face) in &triangles {
+    let p = [positions[face[0]], positions[face[1]], positions[face[2]]];
+    let t = [uvs[face[0]], uvs[face[1]], uvs[face[2]]];
+
+    let d1 = math::sub(p[1], p[0]);
+    let d2 = math::sub(p[2], p[0]);
+
+    let (s1, t1) = (t[1][0] - t[0][0], t[1][1] - t[0][1]);
+    let (s2, t2) = (t[2][0] - t[0][0], t[2][1] - t[0][1]);
+
+    let signed_area = s1 * t2 - t1 * s2;
+    let orientation = signed_area > 0.0;
+
+    // Faces without texture area have no tangent direction of their own
+    let os = if signed_area == 0.0 {
+      [0.0, 0.0, 0.0]
+    } else {
+      math::scale(math::sub(math::scale(d1, t2), math::scale(d2, t1)), signed_area.signum())
+    };
+
+    orientations.push(orientation);
+
+    for c in 0 .. 3 {
+      let v = face[c];
+      let n = normals[v];
+
+      let tangent = math::normalize(project(os, n));
+
+      let e1 = math::normalize(project(math::sub(p[(c + 1) % 3], p[c]), n));
+      let e2 = math::normalize(project(math::sub(p[(c + 2) % 3], p[c]), n));
+
+      let sum = sums.entry(key(v, orientation)).or_insert([0.0, 0.0, 0.0]);
+
+      *sum = math::add(*sum, math::scale(tangent, math::angle(e1, e2)));
+    }
+  }
+
+  let mut tangents: Vec<[f32; 4]> = positions.iter().map(|_| [0.0, 0.0, 0.0, 0.0]).collect();
+  let mut assigned: Vec<bool> = positions.iter().map(|_| false).collect();
+  let mut sources: Vec<usize> = Vec::new();
+  let mut splits: HashMap<(usize, [u32; 4]), usize> = HashMap::new();
+
+  let mut remapped: Vec<(usize, [usize; 3])> = Vec::with_capacity(triangles.len());
+
+  for (&(s, face), &orientation) in triangles.iter().zip(orientations.iter()) {
+    let mut corners = [0; 3];
+
+    for c in 0 .. 3 {
+      let v = face[c];
+
+      let tangent = corner_tangent(&sums, key(v, orientation), normals[v]);
+      let split = (v, unsafe { mem::transmute::<[f32; 4], [u32; 4]>([tangent[0] + 0.0, tangent[1] + 0.0, tangent[2] + 0.0, tangent[3]]) });
+
+      let i = match splits.get(&split) {
+        Some(i) => *i,
+        None if !assigned[v] => {
+          assigned[v] = true;
+          tangents[v] = tangent;
+
+          v
+        }
+        None => {
+          sources.push(v);
+          tangents.push(tangent);
+
+          tangents.len() - 1
+        }
+      };
+
+      splits.insert(split, i);
+      corners[c] = i;
+    }
+
+    remapped.push((s, corners));
+  }
+
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  builder
+    .duplicate_vertices(&sources)
+    .replace_attribute(stream(vertex::AttributeName::Tangent, &tangents, options.format))
+    .replace_triangles(&remapped);
+
+  if let Some(format) = options.bitangent_format {
+    let normals: Vec<math::Vector3> = (0 .. tangents.len()).map(|i| {
+      if i < normals.len() { normals[i] } else { normals[sources[i - normals.len()]] }
+    }).collect();
+
+    let bitangents: Vec<[f32; 4]> = tangents.iter().zip(normals.iter()).map(|(t, n)| {
+      let b = math::scale(math::cross(*n, [t[0], t[1], t[2]]), t[3]);
+
+      [b[0], b[1], b[2], 0.0]
+    }).collect();
+
+    builder.replace_attribute(stream(vertex::AttributeName::Bitangent, &bitangents, format));
+  }
+
+  return Some(builder.build());
+}
+
+// A corner without a tangent of its own borrows the one of the opposite orientation, and failing
+// that any vector perpendicular to the normal
+fn corner_tangent(sums: &HashMap<Key, math::Vector3>, key: Key, normal: math::Vector3) -> [f32; 4] {
+  let other = Key { orientation: !key.orientation, .. key };
+
+  let (tangent, orientation) = match (math::normalize(sums[&key]), sums.get(&other).map(|x| math::normalize(*x))) {
+    (t, _) if t != [0.0, 0.0, 0.0] => (t, key.orientation),
+    (_, Some(t)) if t != [0.0, 0.0, 0.0] => (t, other.orientation),
+    _ => (perpendicular(normal), true)
+  };
+
+  return [tangent[0], tangent[1], tangent[2], if orientation { 1.0 } else { -1.0 }];
+}
+
+#[inline(always)]
+fn project(a: math::Vector3, normal: math::Vector3) -> math::Vector3 {
+  return math::sub(a, math::scale(normal, math::dot(normal, a)));
+}
+
+fn perpendicular(normal: math::Vector3) -> math::Vector3 {
+  let axis = if normal[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+
+  return math::normalize(math::cross(normal, axis));
+}
+
+fn stream(name: vertex::AttributeName, values: &[[f32; 4]], format: vertex::Format) -> Stream {
+  let values = values.iter().flat_map(|x| ScalarValue::from_f32_vector(format, x).into_iter()).collect();
+
+  return Stream { name: name, format: format, values: values };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use index;
+  use vertex;
+
+  use mesh::{Mesh, MeshBuilder};
+
+  // A quad in the xy plane whose right half has its texture mirrored along u
+  fn mirrored() -> Mesh {
+    return MeshBuilder::new("mirrored")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0], [2.0, 0.0, 0.0], [2.0, 1.0, 0.0]])
+      .normals(&[[0.0, 0.0, 1.0]; 6])
+      .texture_coordinates(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [0.0, 0.0], [0.0, 1.0]])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2])
+      .build();
+  }
+
+  #[test]
+  fn test_tangents() {
+    let mesh = generate(&mirrored(), &Options::default()).unwrap();
+
+    // The seam vertices get one tangent per side
+    assert_eq!(mesh.vertex_count, 8);
+
+    let tangents = mesh.untyped_view_for(&vertex::AttributeName::Tangent).unwrap();
+    let faces: Vec<Vec<usize>> = mesh.submeshes[0].faces().collect();
+
+    for i in &faces[0] {
+      assert_eq!(tangents.get_f32(*i), &[1.0, 0.0, 0.0, 1.0]);
+    }
+
+    for i in &faces[2] {
+      assert_eq!(tangents.get_f32(*i), &[-1.0, 0.0, 0.0, -1.0]);
+    }
+  }
+
+  #[test]
+  fn test_bitangents() {
+    let options = Options { bitangent_format: Some(vertex::Format(vertex::Scalar::f32, vertex::Width::Vector3)), .. Options::default() };
+    let mesh = generate(&mirrored(), &options).unwrap();
+
+    let bitangents = mesh.untyped_view_for(&vertex::AttributeName::Bitangent).unwrap();
+
+    for face in mesh.submeshes[0].faces() {
+      for i in face {
+        assert_eq!(bitangents.get_f32(i), &[0.0, 1.0, 0.0]);
+      }
+    }
+  }
+
+  #[test]
+  fn test_missing_attributes() {
+    let mesh = MeshBuilder::new("triangle")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]])
+      .normals(&[[0.0, 0.0, 1.0]; 3])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2])
+      .build();
+
+    assert!(generate(&mesh, &Options::default()).is_none());
+  }
+}
@@ -0,0 +1,384 @@
+use asset;
+use buffer::ScalarValue;
+use math;
+use vertex;
+
+use animation::{animation, sampler, skeleton};
+use mesh::{Mesh, MeshBuilder, Stream};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Handedness {
+  Left, Right
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Up {
+  Y, Z
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CoordinateSystem {
+  pub handedness: Handedness,
+  pub up: Up,
+  pub metres_per_unit: f32
+}
+
+pub const EVE: CoordinateSystem = CoordinateSystem { handedness: Handedness::Left, up: Up::Y, metres_per_unit: 1.0 };
+pub const GLTF: CoordinateSystem = CoordinateSystem { handedness: Handedness::Right, up: Up::Y, metres_per_unit: 1.0 };
+pub const BLENDER: CoordinateSystem = CoordinateSystem { handedness: Handedness::Right, up: Up::Z, metres_per_unit: 1.0 };
+pub const MAYA: CoordinateSystem = CoordinateSystem { handedness: Handedness::Right, up: Up::Y, metres_per_unit: 0.01 };
+pub const MAX: CoordinateSystem = CoordinateSystem { handedness: Handedness::Right, up: Up::Z, metres_per_unit: 0.01 };
+pub const UNITY: CoordinateSystem = CoordinateSystem { handedness: Handedness::Left, up: Up::Y, metres_per_unit: 1.0 };
+pub const UNREAL: CoordinateSystem = CoordinateSystem { handedness: Handedness::Left, up: Up::Z, metres_per_unit: 0.01 };
+pub const USD: CoordinateSystem = CoordinateSystem { handedness: Handedness::Right, up: Up::Y, metres_per_unit: 1.0 };
+
+impl CoordinateSystem {
+  // Takes right-handed Y-up coordinates into this system, ignoring units
+  fn basis(&self) -> math::Matrix4 {
+    let (x, y, z) = ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]);
+
+    let rows: [math::Vector3; 3] = match (self.handedness, self.up) {
+      (Handedness::Right, Up::Y) => [x, y, z],
+      (Handedness::Left, Up::Y) => [x, y, math::scale(z, -1.0)],
+      (Handedness::Right, Up::Z) => [x, math::scale(z, -1.0), y],
+      (Handedness::Left, Up::Z) => [x, z, y]
+    };
+
+    return [
+      [rows[0][0], rows[0][1], rows[0][2], 0.0],
+      [rows[1][0], rows[1][1], rows[1][2], 0.0],
+      [rows[2][0], rows[2][1], rows[2][2], 0.0],
+      [0.0, 0.0, 0.0, 1.0]
+    ];
+  }
+}
+
+pub fn conversion(from: &CoordinateSystem, to: &CoordinateSystem) -> math::Matrix4 {
+  return math::multiply(&math::scaling(from.metres_per_unit / to.metres_per_unit), &rotation(from, to));
+}
+
+// The conversion without the change of units
+fn rotation(from: &CoordinateSystem, to: &CoordinateSystem) -> math::Matrix4 {
+  // Bases are orthonormal, so their transpose is their inverse
+  return math::multiply(&to.basis(), &math::transpose(&from.basis()));
+}
+
+pub fn convert(mesh: &Mesh, from: &CoordinateSystem, to: &CoordinateSystem) -> Mesh {
+  return transform(mesh, &conversion(from, to));
+}
+
+// Converts every mesh in the asset, including blend shapes and levels of detail, along with the
+// skeletons and animations that move them
+pub fn convert_asset(asset: &asset::Asset, from: &CoordinateSystem, to: &CoordinateSystem) -> asset::Asset {
+  let m = conversion(from, to);
+
+  let objects = asset.objects.iter().map(|object| {
+    match object {
+      &asset::Object::Model(ref model) => {
+        let mut model = model.clone();
+
+        model.mesh = transform(&model.mesh, &m);
+        model.blend_shapes = model.blend_shapes.iter().map(|x| transform_blend_shape(x, &m)).collect();
+        model.lods = model.lods.iter().map(|x| transform(x, &m)).collect();
+        model.skeleton = convert_skeleton(&model.skeleton, from, to);
+
+        asset::Object::Model(model)
+      }
+      &asset::Object::Animation(ref animation) => asset::Object::Animation(convert_animation(animation, from, to))
+    }
+  }).collect();
+
+  return asset::Asset { buffers: asset.buffers.clone(), objects: objects };
+}
+
+pub fn convert_skeleton(skeleton: &skeleton::Skeleton, from: &CoordinateSystem, to: &CoordinateSystem) -> skeleton::Skeleton {
+  let m = conversion(from, to);
+  let r = rotation(from, to);
+
+  let mut skeleton = skeleton.clone();
+
+  for bone in &mut skeleton.bones {
+    bone.position = math::transform_vector(&m, bone.position);
+    bone.orientation = rotate_quaternion(&r, bone.orientation);
+    bone.scale_shear = rotate_scale_shear(&r, bone.scale_shear);
+  }
+
+  return skeleton;
+}
+
+pub fn convert_animation(animation: &animation::Animation, from: &CoordinateSystem, to: &CoordinateSystem) -> animation::Animation {
+  let m = conversion(from, to);
+  let r = rotation(from, to);
+
+  let mut animation = animation.clone();
+
+  for track in &mut animation.tracks {
+    map_keys(&mut track.position, 3, |x| math::transform_vector(&m, math::to_vector3(x)).to_vec());
+    map_keys(&mut track.orientation, 4, |x| rotate_quaternion(&r, [x[0], x[1], x[2], x[3]]).to_vec());
+    map_keys(&mut track.scale_shear, 9, |x| rotate_scale_shear(&r, [x[0], x[1], x[2], x[3], x[4], x[5], x[6], x[7], x[8]]).to_vec());
+
+    // Scale curves without shear hold the diagonal only
+    map_keys(&mut track.scale_shear, 3, |x| {
+      let s = rotate_scale_shear(&r, [x[0], 0.0, 0.0, 0.0, x[1], 0.0, 0.0, 0.0, x[2]]);
+
+      vec![s[0], s[4], s[8]]
+    });
+  }
+
+  return animation;
+}
+
+// Normals take the inverse-transpose to stay perpendicular to the surface, while tangents and
+// bitangents lie in the surface and take the matrix itself. A negative determinant mirrors the
+// mesh, so triangle winding and tangent handedness flip with it
+pub fn transform(mesh: &Mesh, m: &math::Matrix4) -> Mesh {
+  let normal_matrix = math::inverse_transpose3(m);
+  let mirrored = math::determinant3(m) < 0.0;
+
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  for stream in &mut builder.streams {
+    let transformed = match stream.name {
+      vertex::AttributeName::Position => map_stream(stream, |v| math::transform_point(m, v), false),
+      vertex::AttributeName::Normal => map_stream(stream, |v| renormalize(v, math::transform_vector(&normal_matrix, v)), false),
+      vertex::AttributeName::Tangent => map_stream(stream, |v| renormalize(v, math::transform_vector(m, v)), mirrored),
+      vertex::AttributeName::Bitangent | vertex::AttributeName::Binormal => map_stream(stream, |v| renormalize(v, math::transform_vector(m, v)), false),
+      _ => continue
+    };
+
+    *stream = transformed;
+  }
+
+  if mirrored {
+    let triangles: Vec<(usize, [usize; 3])> = mesh.triangles().iter().map(|&(s, t)| (s, [t[0], t[2], t[1]])).collect();
+
+    builder.replace_triangles(&triangles);
+  }
+
+  return builder.build();
+}
+
+// Blend shapes list the vertices of the mesh they move, which the builder would check against
+// their own vertices, so their points are put back untouched
+fn transform_blend_shape(shape: &Mesh, m: &math::Matrix4) -> Mesh {
+  let mut transformed = transform(&Mesh { submeshes: Vec::new(), .. shape.clone() }, m);
+
+  transformed.submeshes = shape.submeshes.clone();
+
+  return transformed;
+}
+
+// Bone and key transforms change basis as C L C^-1. That leaves the rotation axis turned along with
+// the basis, and flipped once more by a mirror as it is an axial vector
+fn rotate_quaternion(r: &math::Matrix4, q: [f32; 4]) -> [f32; 4] {
+  let axis = math::transform_vector(r, [q[0], q[1], q[2]]);
+  let sign = if math::determinant3(r) < 0.0 { -1.0 } else { 1.0 };
+
+  return [sign * axis[0], sign * axis[1], sign * axis[2], q[3]];
+}
+
+// The scale/shear matrix is row by row, as in `Bone::transform`
+fn rotate_scale_shear(r: &math::Matrix4, s: [f32; 9]) -> [f32; 9] {
+  let mut result = [0.0; 9];
+
+  for i in 0 .. 3 {
+    for j in 0 .. 3 {
+      for k in 0 .. 3 {
+        for l in 0 .. 3 {
+          result[i * 3 + j] += r[i][k] * s[k * 3 + l] * r[j][l];
+        }
+      }
+    }
+  }
+
+  return result;
+}
+
+// Maps the value of every key of a sampler with `components` outputs, others are left alone
+fn map_keys<F>(sampler: &mut Option<sampler::Sampler>, components: usize, f: F) where F: Fn(&[f32]) -> Vec<f32> {
+  let sampler = match *sampler {
+    Some(ref mut sampler) if sampler.outputs.len() == components => sampler,
+    _ => return
+  };
+
+  let keys = sampler.outputs.iter().map(|x| x.len()).min().unwrap_or(0);
+
+  for k in 0 .. keys {
+    let value: Vec<f32> = sampler.outputs.iter().map(|x| x[k]).collect();
+
+    for (c, x) in f(&value).into_iter().enumerate() {
+      sampler.outputs[c][k] = x;
+    }
+  }
+}
+
+// Unit vectors stay unit vectors, anything else keeps its scaled length
+fn renormalize(original: math::Vector3, transformed: math::Vector3) -> math::Vector3 {
+  if (math::length(original) - 1.0).abs() > 1e-3 {
+    return transformed;
+  }
+
+  return math::normalize(transformed);
+}
+
+// Maps the first three elements of every vertex, a fourth one is kept or negated with `flip_w`
+fn map_stream<F>(stream: &Stream, f: F, flip_w: bool) -> Stream where F: Fn(math::Vector3) -> math::Vector3 {
+  let elements = stream.format.elements();
+
+  let values = stream.values.chunks(elements).flat_map(|vertex| {
+    let mut values: Vec<f32> = vertex.iter().map(|x| x.to_f32()).collect();
+    let mapped = f(math::to_vector3(&values));
+
+    for i in 0 .. elements.min(3) {
+      values[i] = mapped[i];
+    }
+
+    if flip_w && elements > 3 {
+      values[3] = -values[3];
+    }
+
+    ScalarValue::from_f32_vector(stream.format, &values).into_iter()
+  }).collect();
+
+  return Stream { name: stream.name.clone(), format: stream.format, values: values };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::f32::consts::FRAC_1_SQRT_2;
+
+  use asset;
+  use index;
+  use math;
+  use model;
+  use vertex;
+
+  use animation::{animation, sampler, skeleton};
+
+  use mesh::{Mesh, MeshBuilder};
+
+  fn triangle() -> Mesh {
+    return MeshBuilder::new("triangle")
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 1.0]])
+      .normals(&[[0.0, -FRAC_1_SQRT_2, FRAC_1_SQRT_2]; 3])
+      .attribute(vertex::AttributeName::Tangent, vertex::Format(vertex::Scalar::f32, vertex::Width::Vector4), &[1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2])
+      .build();
+  }
+
+  #[test]
+  fn test_transform() {
+    let m = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 3.0], [0.0, 0.0, 2.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+    let mesh = transform(&triangle(), &m);
+
+    assert_eq!(mesh.positions().unwrap()[2], [0.0, 4.0, 2.0]);
+
+    // The normal follows the stretched face instead of the stretch
+    let normal = math::to_vector3(&mesh.untyped_view_for(&vertex::AttributeName::Normal).unwrap().get_f32(0));
+    let edge = math::sub(mesh.positions().unwrap()[2], mesh.positions().unwrap()[0]);
+
+    assert!(math::dot(normal, edge).abs() < 1e-6);
+    assert!((math::length(normal) - 1.0).abs() < 1e-6);
+
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 1, 2]]);
+  }
+
+  #[test]
+  fn test_mirror() {
+    let m = [[-1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+    let mesh = transform(&triangle(), &m);
+
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 2, 1]]);
+    assert_eq!(mesh.untyped_view_for(&vertex::AttributeName::Tangent).unwrap().get_f32(0), &[-1.0, 0.0, 0.0, -1.0]);
+  }
+
+  #[test]
+  fn test_conversion() {
+    let m = conversion(&EVE, &BLENDER);
+
+    assert_eq!(math::transform_point(&m, [1.0, 2.0, 3.0]), [1.0, 3.0, 2.0]);
+    assert_eq!(math::transform_point(&conversion(&EVE, &UNREAL), [0.0, 1.0, 0.0]), [0.0, 0.0, 100.0]);
+    assert_eq!(conversion(&MAYA, &MAYA), math::identity());
+
+    let mesh = convert(&triangle(), &EVE, &GLTF);
+
+    assert_eq!(mesh.positions().unwrap()[2], [0.0, 1.0, -1.0]);
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 2, 1]]);
+  }
+
+  #[test]
+  fn test_convert_asset() {
+    let bone = skeleton::Bone {
+      name: "turret".to_owned(),
+      parent_index: None,
+      position: [1.0, 2.0, 3.0],
+      orientation: [0.0, 0.0, 0.6, 0.8],
+      scale_shear: [2.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]
+    };
+
+    let model = model::Model {
+      name: "hull".to_owned(),
+      mesh: triangle(),
+      blend_shapes: Vec::new(),
+      lods: Vec::new(),
+      skeleton: skeleton::Skeleton { bones: vec![bone.clone()] }
+    };
+
+    let curve = |outputs: Vec<Vec<f32>>| sampler::Sampler {
+      pre_behavior: sampler::Behavior::Constant,
+      post_behavior: sampler::Behavior::Constant,
+      degree: 1,
+      input: vec![0.0],
+      outputs: outputs
+    };
+
+    let animation = animation::Animation {
+      name: "spin".to_owned(),
+      duration: 1.0,
+      tracks: vec![animation::TransformTrack {
+        group: "hull".to_owned(),
+        bone: "turret".to_owned(),
+        orientation: Some(curve(vec![vec![0.0], vec![0.0], vec![0.6], vec![0.8]])),
+        position: Some(curve(vec![vec![1.0], vec![2.0], vec![3.0]])),
+        scale_shear: Some(curve(vec![vec![2.0], vec![1.0], vec![1.0]]))
+      }]
+    };
+
+    let asset = asset::Asset { buffers: Vec::new(), objects: vec![asset::Object::Model(model), asset::Object::Animation(animation)] };
+
+    for to in &[GLTF, BLENDER, UNREAL] {
+      let converted = convert_asset(&asset, &EVE, to);
+      let m = conversion(&EVE, to);
+
+      // The bone moves converted points the way it moved the original ones
+      let expected = math::multiply(&math::multiply(&m, &bone.transform()), &math::inverse_affine(&m));
+
+      let actual = match converted.objects[0] {
+        asset::Object::Model(ref model) => model.skeleton.bones[0].transform(),
+        _ => unreachable!()
+      };
+
+      for i in 0 .. 4 {
+        for j in 0 .. 4 {
+          assert!((actual[i][j] - expected[i][j]).abs() < 1e-4);
+        }
+      }
+
+      let track = match converted.objects[1] {
+        asset::Object::Animation(ref animation) => animation.tracks[0].clone(),
+        _ => unreachable!()
+      };
+
+      assert_eq!(track.position.unwrap().outputs, match converted.objects[0] {
+        asset::Object::Model(ref model) => model.skeleton.bones[0].position.iter().map(|x| vec![*x]).collect::<Vec<_>>(),
+        _ => unreachable!()
+      });
+
+      let orientation: Vec<f32> = track.orientation.unwrap().outputs.iter().map(|x| x[0]).collect();
+
+      assert_eq!(orientation, rotate_quaternion(&rotation(&EVE, to), bone.orientation).to_vec());
+    }
+  }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+
+use math;
+use vertex;
+
+use mesh::Mesh;
+
+// Normals and joint weights are stored quantized often enough that exact checks would be noise
+const TOLERANCE: f32 = 0.01;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Problem {
+  BufferViewOutOfBounds { buffer_index: usize },
+  AttributeOutOfBounds { attribute: vertex::AttributeName },
+  AttributeMisaligned { attribute: vertex::AttributeName }, // Offset or stride not a multiple of the size of its scalar
+  SubmeshViewOutOfBounds { submesh: usize },
+  IndexOutOfBounds { submesh: usize, position: usize, index: usize },
+  DegenerateTriangle { submesh: usize, triangle: [usize; 3] },
+  DuplicateTriangle { submesh: usize, triangle: [usize; 3] },
+  InvalidPosition { vertex: usize },
+  UnnormalizedNormal { vertex: usize, length: f32 },
+  JointWeightSum { vertex: usize, sum: f32 },
+  JointIndexOutOfBounds { vertex: usize, joint: usize }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Report {
+  pub problems: Vec<Problem>
+}
+
+impl Report {
+  pub fn is_valid(&self) -> bool {
+    return self.problems.is_empty();
+  }
+}
+
+// Anything that would make reading the mesh panic is checked first, and the checks depending on
+// it are skipped when it fails
+pub fn validate(mesh: &Mesh) -> Report {
+  let mut problems = Vec::new();
+
+  for (i, view) in mesh.buffers.iter().enumerate() {
+    if view.offset + view.length > view.buffer.len() {
+      problems.push(Problem::BufferViewOutOfBounds { buffer_index: i });
+    }
+  }
+
+  let readable: Vec<bool> = mesh.descriptor.attributes.iter().map(|attribute| {
+    let in_bounds = attribute.buffer_index < mesh.buffers.len() && attribute.buffer_index < mesh.descriptor.layouts.len() && {
+      let view = &mesh.buffers[attribute.buffer_index];
+      let stride = mesh.descriptor.layouts[attribute.buffer_index].stride;
+
+      let end = if mesh.vertex_count == 0 { 0 } else { attribute.offset + stride * (mesh.vertex_count - 1) + attribute.format.byte_size() };
+
+      view.offset + view.length <= view.buffer.len() && end <= view.length
+    };
+
+    if !in_bounds {
+      problems.push(Problem::AttributeOutOfBounds { attribute: attribute.name.clone() });
+
+      return false;
+    }
+
+    let alignment = attribute.format.scalar().byte_size();
+    let aligned = attribute.offset % alignment == 0 && mesh.descriptor.layouts[attribute.buffer_index].stride % alignment == 0;
+
+    if !aligned {
+      problems.push(Problem::AttributeMisaligned { attribute: attribute.name.clone() });
+    }
+
+    aligned
+  }).collect();
+
+  let readable_attribute = |name: vertex::AttributeName| {
+    mesh.descriptor.attributes.iter().zip(readable.iter()).find(|x| x.0.name == name && *x.1).map(|x| x.0)
+  };
+
+  let positions = readable_attribute(vertex::AttributeName::Position).map(|attribute| {
+    let view = mesh.untyped_view_for_attribute(attribute);
+
+    (0 .. mesh.vertex_count).map(|i| math::to_vector3(&view.get_f32(i))).collect::<Vec<_>>()
+  });
+
+  for (s, submesh) in mesh.submeshes.iter().enumerate() {
+    let view = &submesh.view;
+
+    if view.offset + view.length > view.buffer.len() || submesh.index_count * submesh.index_format.byte_size() > view.length {
+      problems.push(Problem::SubmeshViewOutOfBounds { submesh: s });
+
+      continue;
+    }
+
+    let indices = submesh.untyped_view();
+    let restart = submesh.index_format.restart_index();
+
+    let count = problems.len();
+
+    for position in 0 .. submesh.index_count {
+      let index = indices.get_usize(position);
+
+      if index >= mesh.vertex_count && !(submesh.primitive_restart && index == restart) {
+        problems.push(Problem::IndexOutOfBounds { submesh: s, position: position, index: index });
+      }
+    }
+
+    if problems.len() > count || !submesh.geometry.is_triangles() {
+      continue;
+    }
+
+    let mut seen: HashSet<[usize; 3]> = HashSet::new();
+
+    for face in submesh.faces() {
+      let triangle = [face[0], face[1], face[2]];
+
+      let zero_area = match positions {
+        Some(ref p) => math::length(math::cross(math::sub(p[face[1]], p[face[0]]), math::sub(p[face[2]], p[face[0]]))) == 0.0,
+        None => false
+      };
+
+      if face[0] == face[1] || face[1] == face[2] || face[0] == face[2] || zero_area {
+        problems.push(Problem::DegenerateTriangle { submesh: s, triangle: triangle });
+
+        continue;
+      }
+
+      // Rotations of a triangle are the same triangle, the reverse winding is the back face
+      let first = (0 .. 3).min_by_key(|c| face[*c]).unwrap();
+      let key = [face[first], face[(first + 1) % 3], face[(first + 2) % 3]];
+
+      if !seen.insert(key) {
+        problems.push(Problem::DuplicateTriangle { submesh: s, triangle: triangle });
+      }
+    }
+  }
+
+  if let Some(ref positions) = positions {
+    for (v, p) in positions.iter().enumerate() {
+      if !(p[0].is_finite() && p[1].is_finite() && p[2].is_finite()) {
+        problems.push(Problem::InvalidPosition { vertex: v });
+      }
+    }
+  }
+
+  if let Some(attribute) = readable_attribute(vertex::AttributeName::Normal) {
+    let view = mesh.untyped_view_for_attribute(attribute);
+
+    for v in 0 .. mesh.vertex_count {
+      let length = math::length(math::to_vector3(&view.get_f32(v)));
+
+      if !((length - 1.0).abs() <= TOLERANCE) {
+        problems.push(Problem::UnnormalizedNormal { vertex: v, length: length });
+      }
+    }
+  }
+
+  let weights = readable_attribute(vertex::AttributeName::JointWeights).map(|attribute| mesh.untyped_view_for_attribute(attribute));
+  let joints = readable_attribute(vertex::AttributeName::JointIndices).map(|attribute| mesh.untyped_view_for_attribute(attribute));
+
+  for v in 0 .. mesh.vertex_count {
+    let w = weights.as_ref().map(|view| view.get_f32(v));
+
+    if let Some(ref w) = w {
+      let sum = w.iter().fold(0.0, |sum, x| sum + x);
+
+      if !((sum - 1.0).abs() <= TOLERANCE) {
+        problems.push(Problem::JointWeightSum { vertex: v, sum: sum });
+      }
+    }
+
+    if let Some(ref view) = joints {
+      // Influences without weight do not refer to a bone
+      for (k, joint) in view.get_f32(v).iter().enumerate() {
+        let weighted = w.as_ref().map(|w| k < w.len() && w[k] != 0.0).unwrap_or(true);
+
+        if weighted && *joint as usize >= mesh.bone_bindings.len() {
+          problems.push(Problem::JointIndexOutOfBounds { vertex: v, joint: *joint as usize });
+        }
+      }
+    }
+  }
+
+  return Report { problems: problems };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std;
+
+  use index;
+  use vertex;
+
+  use mesh::MeshBuilder;
+
+  fn builder(positions: &[[f32; 3]], normals: &[[f32; 3]], joints: &[[u8; 4]], weights: &[[f32; 4]]) -> MeshBuilder {
+    let mut builder = MeshBuilder::new("triangle");
+
+    builder
+      .positions(positions)
+      .normals(normals)
+      .joints(joints, weights)
+      .bone_bindings(&["root".to_owned(), "turret".to_owned()])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2]);
+
+    return builder;
+  }
+
+  fn triangle() -> MeshBuilder {
+    return builder(
+      &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+      &[[0.0, 0.0, 1.0]; 3],
+      &[[0, 1, 0, 0], [1, 0, 0, 0], [0, 0, 0, 0]],
+      &[[0.5, 0.5, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0]]
+    );
+  }
+
+  #[test]
+  fn test_valid() {
+    assert!(triangle().build().validate().is_valid());
+  }
+
+  #[test]
+  fn test_problems() {
+    let mut builder = builder(
+      &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [std::f32::NAN, 1.0, 0.0]],
+      &[[0.0, 0.0, 1.0], [0.0, 0.0, 2.0], [0.0, 0.0, 1.0]],
+      &[[0, 1, 0, 0], [2, 0, 0, 0], [0, 3, 0, 0]],
+      &[[0.5, 0.5, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0], [0.5, 0.0, 0.0, 0.0]]
+    );
+
+    builder.submesh("copy", index::Geometry::Triangles, &[0, 1, 2, 1, 2, 0, 0, 0, 1]);
+
+    let problems = builder.build().validate().problems;
+
+    assert_eq!(problems, vec![
+      Problem::DuplicateTriangle { submesh: 1, triangle: [1, 2, 0] },
+      Problem::DegenerateTriangle { submesh: 1, triangle: [0, 0, 1] },
+      Problem::InvalidPosition { vertex: 2 },
+      Problem::UnnormalizedNormal { vertex: 1, length: 2.0 },
+      Problem::JointIndexOutOfBounds { vertex: 1, joint: 2 },
+      Problem::JointWeightSum { vertex: 2, sum: 0.5 }
+    ]);
+  }
+
+  #[test]
+  fn test_out_of_bounds() {
+    let mut mesh = triangle().build();
+
+    mesh.vertex_count = 2;
+    mesh.submeshes[0].index_count = 100;
+
+    assert_eq!(mesh.validate().problems[0], Problem::SubmeshViewOutOfBounds { submesh: 0 });
+
+    mesh.submeshes[0].index_count = 3;
+
+    assert_eq!(mesh.validate().problems, vec![Problem::IndexOutOfBounds { submesh: 0, position: 2, index: 2 }]);
+  }
+
+  #[test]
+  fn test_misaligned() {
+    let mut mesh = triangle().build();
+
+    mesh.vertex_count = 2;
+    mesh.submeshes[0].index_count = 2;
+    mesh.descriptor.attributes[0].offset += 2;
+
+    assert_eq!(mesh.validate().problems, vec![Problem::AttributeMisaligned { attribute: vertex::AttributeName::Position }]);
+  }
+}
@@ -0,0 +1,194 @@
+use std::mem;
+
+use std::collections::HashMap;
+
+use vertex;
+
+use mesh::{Mesh, MeshBuilder};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Options {
+  pub tolerances: Vec<(vertex::AttributeName, f32)>,
+  pub default_tolerance: f32 // Largest distance between values of an attribute, zero only welds identical ones
+}
+
+impl Options {
+  pub fn default() -> Options {
+    return Options { tolerances: Vec::new(), default_tolerance: 0.0 };
+  }
+
+  pub fn tolerance_for(&self, name: &vertex::AttributeName) -> f32 {
+    return self.tolerances.iter().find(|x| x.0 == *name).map(|x| x.1).unwrap_or(self.default_tolerance);
+  }
+}
+
+// Welds matching vertices, drops the ones no submesh references and picks the smallest index format
+pub fn weld(mesh: &Mesh, options: &Options) -> Mesh {
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  let tolerances: Vec<f32> = builder.streams.iter().map(|x| options.tolerance_for(&x.name)).collect();
+
+  let used = builder.used_vertices();
+
+  let mut cells: HashMap<(Vec<u32>, [i64; 3]), Vec<usize>> = HashMap::new();
+  let mut remap: Vec<Option<usize>> = used.iter().map(|_| None).collect();
+  let mut sources: Vec<usize> = Vec::new();
+
+  {
+    let streams = &builder.streams;
+
+    let value = |v: usize, s: usize, i: usize| streams[s].values[v * streams[s].format.elements() + i].to_f32() + 0.0;
+
+    // Attributes without a tolerance have to match exactly
+    let exact = |v: usize| -> Vec<u32> {
+      return (0 .. streams.len()).filter(|s| tolerances[*s] <= 0.0).flat_map(|s| {
+        (0 .. streams[s].format.elements()).map(move |i| unsafe { mem::transmute::<f32, u32>(value(v, s, i)) })
+      }).collect();
+    };
+
+    // Vertices are put in cells as large as the tolerance along the first three components of the
+    // first attribute that has one, so any vertex to weld with is in the same cell or in one of the
+    // 26 around it
+    let grid = (0 .. streams.len()).find(|s| tolerances[*s] > 0.0);
+
+    let cell = |v: usize| -> [i64; 3] {
+      let mut cell = [0; 3];
+
+      if let Some(s) = grid {
+        for i in 0 .. streams[s].format.elements().min(3) {
+          cell[i] = (value(v, s, i) / tolerances[s]).floor() as i64;
+        }
+      }
+
+      return cell;
+    };
+
+    let within_tolerance = |a: usize, b: usize| -> bool {
+      return (0 .. streams.len()).filter(|s| tolerances[*s] > 0.0).all(|s| {
+        let distance_squared: f32 = (0 .. streams[s].format.elements()).map(|i| (value(a, s, i) - value(b, s, i)).powi(2)).sum();
+
+        distance_squared <= tolerances[s] * tolerances[s]
+      });
+    };
+
+    for v in (0 .. used.len()).filter(|v| used[*v]) {
+      let key = (exact(v), cell(v));
+
+      // The first vertex kept that is close enough, so welding does not depend on hash order
+      let c = key.1;
+      let neighbours = (0 .. 27).map(|n| [c[0] + n % 3 - 1, c[1] + n / 3 % 3 - 1, c[2] + n / 9 - 1]);
+
+      let found = neighbours.filter_map(|n| cells.get(&(key.0.clone(), n))).flat_map(|x| x.iter()).filter(|i| within_tolerance(sources[**i], v)).min().cloned();
+
+      remap[v] = Some(match found {
+        Some(i) => i,
+        None => {
+          sources.push(v);
+          cells.entry(key).or_insert(Vec::new()).push(sources.len() - 1);
+
+          sources.len() - 1
+        }
+      });
+    }
+  }
+
+  builder.remap_vertices(&remap, &sources);
+  builder.index_format = None;
+
+  return builder.build();
+}
+
+pub fn remove_unused(mesh: &Mesh) -> Mesh {
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  builder.remove_unused_vertices();
+  builder.index_format = None;
+
+  return builder.build();
+}
+
+// Switches u32 indices to u16 when every vertex can be addressed with them
+pub fn shrink_indices(mesh: &Mesh) -> Mesh {
+  let mut builder = MeshBuilder::from_mesh(mesh);
+
+  builder.index_format = None;
+
+  return builder.build();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use index;
+  use vertex;
+
+  use mesh::{Mesh, MeshBuilder};
+
+  // Two triangles of a quad that do not share vertices, with an unreferenced vertex at the end
+  fn quad() -> Mesh {
+    return MeshBuilder::new("quad")
+      .index_format(index::Format::u32)
+      .positions(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0001, 0.0], [0.0, 1.0, 0.0], [5.0, 5.0, 5.0]])
+      .normals(&[[0.0, 0.0, 1.0]; 7])
+      .submesh("hull", index::Geometry::Triangles, &[0, 1, 2, 3, 4, 5])
+      .build();
+  }
+
+  #[test]
+  fn test_weld() {
+    let mesh = weld(&quad(), &Options::default());
+
+    assert_eq!(mesh.vertex_count, 5);
+    assert_eq!(mesh.submeshes[0].index_format, index::Format::u16);
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 1, 2], vec![0, 3, 4]]);
+
+    let options = Options { tolerances: vec![(vertex::AttributeName::Position, 0.01)], .. Options::default() };
+    let mesh = weld(&quad(), &options);
+
+    assert_eq!(mesh.vertex_count, 4);
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 1, 2], vec![0, 2, 3]]);
+    assert_eq!(mesh.untyped_view_for(&vertex::AttributeName::Position).unwrap().get_f32(3), &[0.0, 1.0, 0.0]);
+  }
+
+  #[test]
+  fn test_weld_across_cells() {
+    let mesh = MeshBuilder::new("points")
+      .positions(&[[0.49, 0.0, 0.0], [0.51, 0.0, 0.0], [0.99, 0.0, 0.0], [1.01, 0.0, 0.0], [3.0, 0.0, 0.0], [4.5, 0.0, 0.0]])
+      .submesh("hull", index::Geometry::Points, &[0, 1, 2, 3, 4, 5])
+      .build();
+
+    let options = Options { tolerances: vec![(vertex::AttributeName::Position, 1.0)], .. Options::default() };
+    let mesh = weld(&mesh, &options);
+
+    assert_eq!(mesh.vertex_count, 3);
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0], vec![0], vec![0], vec![0], vec![1], vec![2]]);
+
+    // Points on one plane only share cells with their neighbours, and still weld across them
+    let mesh = MeshBuilder::new("plane")
+      .positions(&[[0.0, 0.0, 0.0], [0.0, 10.0, 0.0], [0.0, 0.0, 10.0], [0.0, 9.6, 0.4], [0.0, 0.99, 0.0]])
+      .submesh("hull", index::Geometry::Points, &[0, 1, 2, 3, 4])
+      .build();
+
+    let mesh = weld(&mesh, &options);
+
+    assert_eq!(mesh.vertex_count, 3);
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0], vec![1], vec![2], vec![1], vec![0]]);
+  }
+
+  #[test]
+  fn test_remove_unused() {
+    let mesh = remove_unused(&quad());
+
+    assert_eq!(mesh.vertex_count, 6);
+    assert_eq!(mesh.submeshes[0].faces().collect::<Vec<_>>(), vec![vec![0, 1, 2], vec![3, 4, 5]]);
+  }
+
+  #[test]
+  fn test_shrink_indices() {
+    let mesh = quad();
+
+    assert_eq!(mesh.submeshes[0].index_format, index::Format::u32);
+    assert_eq!(shrink_indices(&mesh).submeshes[0].index_format, index::Format::u16);
+  }
+}
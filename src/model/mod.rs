@@ -7,5 +7,6 @@ pub struct Model {
   pub name: String,
   pub mesh: mesh::Mesh,
   pub blend_shapes: Vec<mesh::Mesh>,
+  pub lods: Vec<mesh::Mesh>,
   pub skeleton: skeleton::Skeleton,
 }
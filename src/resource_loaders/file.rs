@@ -3,6 +3,9 @@ use std::fs;
 
 use std::io::Read;
 
+use std::sync::Arc;
+
+use buffer;
 use resource_loaders;
 
 pub struct Loader {
@@ -37,4 +40,16 @@ impl resource_loaders::ResourceLoader for Loader {
 
     return None;
   }
+
+  fn load_buffer(&self, path: &str, uri: &str) -> Option<(String, Arc<buffer::Buffer>)> {
+    let path = format!("{}{}", self.prefix, path);
+
+    if let Ok(buffer) = buffer::Buffer::map(Some(uri.to_owned()), None, &path) {
+      let mime = resource_loaders::path_to_mime(&path).unwrap_or("application/octet-stream");
+
+      return Some((mime.to_owned(), buffer));
+    }
+
+    return None;
+  }
 }
@@ -1,11 +1,19 @@
 use std::path;
 
+use std::sync::Arc;
+
+use buffer;
+
 pub mod cdn;
 pub mod file;
 
 pub trait ResourceLoader : Send + Sync {
   fn load(&self, path: &str) -> Option<(String, Vec<u8>)>;
 
+  fn load_buffer(&self, path: &str, uri: &str) -> Option<(String, Arc<buffer::Buffer>)> {
+    return self.load(path).map(|(mime, data)| (mime, buffer::Buffer::new(Some(uri.to_owned()), None, data)));
+  }
+
   fn prefetch(&self, path: &str) {
     let _ = self.load(path);
   }
@@ -80,11 +80,7 @@ impl ResourceManager {
 
     return match p {
       Some((l, r)) => {
-        if let Some((mime, data)) = l.load(r.as_str()) {
-          Some(to_resource(mime, buffer::Buffer::new(Some(path.to_owned()), None, data)))
-        } else {
-          None
-        }
+        l.load_buffer(r.as_str(), path).map(|(mime, data)| to_resource(mime, data))
       }
       _ => None
     };
@@ -101,6 +97,15 @@ fn to_resource(mime: String, data: Arc<buffer::Buffer>) -> Arc<Resource> {
     "application/x-ccp-wbg" => {
       Resource::Asset(importer::wbg::import(data).unwrap())
     }
+    "application/x-wavefront-obj" => {
+      match importer::obj::import(data.clone()) {
+        Some(asset) => Resource::Asset(asset),
+        None => {
+          println!("Could not read {:?} as a Wavefront .obj, interpreting as Binary", data.uri);
+          Resource::Binary(data)
+        }
+      }
+    }
     _ => {
       println!("Unknown MIME {:?}, interpreting as Binary", mime);
       Resource::Binary(data)
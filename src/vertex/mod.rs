@@ -1,12 +1,18 @@
 #![allow(non_camel_case_types)]
 
+use std;
+
+pub mod packed;
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Scalar {
   f16, f32,
   u8, u16, u32,
   u8_normalized, u16_normalized, u32_normalized,
   i8, i16, i32,
-  i8_normalized, i16_normalized, i32_normalized
+  i8_normalized, i16_normalized, i32_normalized,
+  u10_10_10_2_normalized, i10_10_10_2_normalized,
+  f11_11_10, f9_9_9_e5
 }
 
 impl Scalar {
@@ -15,14 +21,51 @@ impl Scalar {
     return match *self {
       Scalar::u8 | Scalar::u8_normalized | Scalar::i8 | Scalar::i8_normalized => 1,
       Scalar::f16 | Scalar::u16 | Scalar::u16_normalized | Scalar::i16 | Scalar::i16_normalized => 2,
-      Scalar::f32 | Scalar::u32 | Scalar::u32_normalized | Scalar::i32 | Scalar::i32_normalized => 4
+      Scalar::f32 | Scalar::u32 | Scalar::u32_normalized | Scalar::i32 | Scalar::i32_normalized => 4,
+      Scalar::u10_10_10_2_normalized | Scalar::i10_10_10_2_normalized => 4,
+      Scalar::f11_11_10 | Scalar::f9_9_9_e5 => 4
+    };
+  }
+
+  // Packed scalars store a whole vector in a single 32 bit word
+  #[inline(always)]
+  pub fn is_packed(&self) -> bool {
+    return match *self {
+      Scalar::u10_10_10_2_normalized | Scalar::i10_10_10_2_normalized => true,
+      Scalar::f11_11_10 | Scalar::f9_9_9_e5 => true,
+      _ => false
+    };
+  }
+
+  pub fn is_normalized(&self) -> bool {
+    return match *self {
+      Scalar::u8_normalized | Scalar::u16_normalized | Scalar::u32_normalized => true,
+      Scalar::i8_normalized | Scalar::i16_normalized | Scalar::i32_normalized => true,
+      Scalar::u10_10_10_2_normalized | Scalar::i10_10_10_2_normalized => true,
+      _ => false
+    };
+  }
+
+  pub fn range(&self) -> (f64, f64) {
+    return match *self {
+      Scalar::f16 => (-65504.0, 65504.0),
+      Scalar::f32 => (std::f32::MIN as f64, std::f32::MAX as f64),
+      Scalar::u8 => (0.0, std::u8::MAX as f64),
+      Scalar::u16 => (0.0, std::u16::MAX as f64),
+      Scalar::u32 => (0.0, std::u32::MAX as f64),
+      Scalar::i8 => (std::i8::MIN as f64, std::i8::MAX as f64),
+      Scalar::i16 => (std::i16::MIN as f64, std::i16::MAX as f64),
+      Scalar::i32 => (std::i32::MIN as f64, std::i32::MAX as f64),
+      Scalar::u8_normalized | Scalar::u16_normalized | Scalar::u32_normalized => (0.0, 1.0),
+      Scalar::i8_normalized | Scalar::i16_normalized | Scalar::i32_normalized => (-1.0, 1.0),
+      packed => packed::range(packed)
     };
   }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Width {
-  Scalar, Vector2, Vector3, Vector4, Matrix3x3
+  Scalar, Vector2, Vector3, Vector4, Matrix3x3, Matrix4x4
 }
 
 impl Width {
@@ -33,6 +76,7 @@ impl Width {
       3 => Some(Width::Vector3),
       4 => Some(Width::Vector4),
       9 => Some(Width::Matrix3x3),
+      16 => Some(Width::Matrix4x4),
       _ => None
     };
   }
@@ -44,7 +88,8 @@ impl Width {
       Width::Vector2 => 2,
       Width::Vector3 => 3,
       Width::Vector4 => 4,
-      Width::Matrix3x3 => 9
+      Width::Matrix3x3 => 9,
+      Width::Matrix4x4 => 16
     };
   }
 }
@@ -75,6 +120,7 @@ impl Format {
   #[inline(always)]
   pub fn byte_size(&self) -> usize {
     return match *self {
+      Format(scalar, _) if scalar.is_packed() => scalar.byte_size(),
       Format(scalar, width) => scalar.byte_size() * width.elements()
     };
   }
@@ -0,0 +1,190 @@
+use std;
+use std::mem;
+
+use vertex::Scalar;
+
+pub fn components(scalar: Scalar) -> usize {
+  return match scalar {
+    Scalar::u10_10_10_2_normalized | Scalar::i10_10_10_2_normalized => 4,
+    Scalar::f11_11_10 | Scalar::f9_9_9_e5 => 3,
+    _ => panic!("{:?} is not a packed format", scalar)
+  };
+}
+
+pub fn pack(scalar: Scalar, values: &[f32]) -> u32 {
+  let value = |i: usize| if i < values.len() { values[i] } else { 0.0 };
+
+  return match scalar {
+    Scalar::u10_10_10_2_normalized => {
+      (0 .. 4).fold(0, |word, i| {
+        let (shift, bits) = (10 * i as u32, if i == 3 { 2 } else { 10 });
+        let max = ((1 << bits) - 1) as f32;
+
+        word | (quantize(value(i), 0.0, 1.0, max) as u32) << shift
+      })
+    }
+    Scalar::i10_10_10_2_normalized => {
+      (0 .. 4).fold(0, |word, i| {
+        let (shift, bits) = (10 * i as u32, if i == 3 { 2 } else { 10 });
+        let max = ((1 << (bits - 1)) - 1) as f32;
+        let mask = (1u32 << bits) - 1;
+
+        word | ((quantize(value(i), -1.0, 1.0, max) as i32 as u32) & mask) << shift
+      })
+    }
+    Scalar::f11_11_10 => {
+      pack_unsigned_float(value(0), 6) | pack_unsigned_float(value(1), 6) << 11 | pack_unsigned_float(value(2), 5) << 22
+    }
+    Scalar::f9_9_9_e5 => pack_shared_exponent([value(0), value(1), value(2)]),
+    _ => panic!("{:?} is not a packed format", scalar)
+  };
+}
+
+pub fn unpack(scalar: Scalar, word: u32, component: usize) -> f32 {
+  return match scalar {
+    Scalar::u10_10_10_2_normalized => {
+      let bits = if component == 3 { 2 } else { 10 };
+      let max = (1 << bits) - 1;
+
+      ((word >> (10 * component)) & max) as f32 / max as f32
+    }
+    Scalar::i10_10_10_2_normalized => {
+      let bits = if component == 3 { 2 } else { 10 };
+      let max = (1 << (bits - 1)) - 1;
+
+      // Shift the field to the top of the word so the arithmetic shift back sign extends it
+      let value = ((word << (32 - 10 * component - bits)) as i32) >> (32 - bits);
+
+      (value as f32 / max as f32).max(-1.0)
+    }
+    Scalar::f11_11_10 => {
+      match component {
+        0 => unpack_unsigned_float(word & 0x7FF, 6),
+        1 => unpack_unsigned_float((word >> 11) & 0x7FF, 6),
+        _ => unpack_unsigned_float((word >> 22) & 0x3FF, 5)
+      }
+    }
+    Scalar::f9_9_9_e5 => {
+      let exponent = (word >> 27) as i32 - 15 - 9;
+
+      ((word >> (9 * component)) & 0x1FF) as f32 * 2f32.powi(exponent)
+    }
+    _ => panic!("{:?} is not a packed format", scalar)
+  };
+}
+
+pub fn range(scalar: Scalar) -> (f64, f64) {
+  return match scalar {
+    Scalar::u10_10_10_2_normalized => (0.0, 1.0),
+    Scalar::i10_10_10_2_normalized => (-1.0, 1.0),
+    Scalar::f11_11_10 => (0.0, 64512.0), // The 10 bit channel is the limiting one
+    Scalar::f9_9_9_e5 => (0.0, 65408.0),
+    _ => panic!("{:?} is not a packed format", scalar)
+  };
+}
+
+fn quantize(x: f32, min: f32, max: f32, scale: f32) -> f32 {
+  if x.is_nan() { return 0.0 };
+
+  return (x.max(min).min(max) * scale).round();
+}
+
+fn pack_unsigned_float(x: f32, mantissa_bits: u32) -> u32 {
+  let infinity = 0x1F << mantissa_bits;
+  let max_finite = (0x1E << mantissa_bits) | ((1 << mantissa_bits) - 1);
+
+  if x.is_nan() { return infinity | 1 };
+  if x <= 0.0 { return 0 };
+
+  let bits: u32 = unsafe { mem::transmute(x) };
+
+  let mut exponent = ((bits >> 23) & 0xFF) as i32 - 127 + 15;
+
+  if exponent <= 0 {
+    let denormal = (x as f64 * 2f64.powi(14 + mantissa_bits as i32)).round() as u32;
+
+    return denormal.min(max_finite); // Rounding up to 1 << mantissa_bits yields the smallest normal
+  }
+
+  let shift = 23 - mantissa_bits;
+  let mut mantissa = ((bits & 0x7FFFFF) + (1 << (shift - 1))) >> shift;
+
+  if mantissa == 1 << mantissa_bits {
+    mantissa = 0;
+    exponent += 1;
+  }
+
+  if exponent >= 0x1F {
+    return max_finite;
+  }
+
+  return (exponent as u32) << mantissa_bits | mantissa;
+}
+
+fn unpack_unsigned_float(value: u32, mantissa_bits: u32) -> f32 {
+  let exponent = (value >> mantissa_bits) as i32;
+  let mantissa = (value & ((1 << mantissa_bits) - 1)) as f32 / (1 << mantissa_bits) as f32;
+
+  return match exponent {
+    0 => mantissa * 2f32.powi(-14),
+    0x1F if mantissa == 0.0 => std::f32::INFINITY,
+    0x1F => std::f32::NAN,
+    e => (1.0 + mantissa) * 2f32.powi(e - 15)
+  };
+}
+
+// See EXT_texture_shared_exponent for the reference algorithm
+fn pack_shared_exponent(values: [f32; 3]) -> u32 {
+  let (_, max) = range(Scalar::f9_9_9_e5);
+
+  let clamped: Vec<f64> = values.iter().map(|x| if x.is_nan() { 0.0 } else { (*x as f64).max(0.0).min(max) }).collect();
+  let largest = clamped.iter().fold(0.0f64, |a, b| a.max(*b));
+
+  if largest == 0.0 { return 0 };
+
+  let mut exponent = (largest.log2().floor() as i32).max(-16) + 1 + 15;
+
+  if (largest / 2f64.powi(exponent - 15 - 9) + 0.5).floor() >= 512.0 {
+    exponent += 1;
+  }
+
+  let scale = 2f64.powi(exponent - 15 - 9);
+
+  let mantissas: Vec<u32> = clamped.iter().map(|x| ((x / scale + 0.5).floor() as u32).min(511)).collect();
+
+  return mantissas[0] | mantissas[1] << 9 | mantissas[2] << 18 | (exponent as u32) << 27;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use vertex::Scalar;
+
+  fn round_trip(scalar: Scalar, values: &[f32]) -> Vec<f32> {
+    let word = pack(scalar, values);
+
+    return (0 .. values.len()).map(|i| unpack(scalar, word, i)).collect();
+  }
+
+  #[test]
+  fn test_10_10_10_2() {
+    assert_eq!(round_trip(Scalar::u10_10_10_2_normalized, &[0.0, 1.0, 2.0, 1.0]), &[0.0, 1.0, 1.0, 1.0]);
+    assert_eq!(round_trip(Scalar::i10_10_10_2_normalized, &[-1.0, 1.0, 0.0, -1.0]), &[-1.0, 1.0, 0.0, -1.0]);
+    assert_eq!(pack(Scalar::u10_10_10_2_normalized, &[1.0, 0.0, 0.0, 1.0]), 0xC00003FF);
+  }
+
+  #[test]
+  fn test_11_11_10() {
+    assert_eq!(round_trip(Scalar::f11_11_10, &[1.0, 0.5, 2.0]), &[1.0, 0.5, 2.0]);
+    assert_eq!(round_trip(Scalar::f11_11_10, &[-1.0, 100000.0, 0.0]), &[0.0, 65024.0, 0.0]);
+    assert!((round_trip(Scalar::f11_11_10, &[0.3, 0.0, 0.0])[0] - 0.3).abs() < 0.3 / 64.0);
+  }
+
+  #[test]
+  fn test_9_9_9_e5() {
+    assert_eq!(round_trip(Scalar::f9_9_9_e5, &[1.0, 0.5, 0.25]), &[1.0, 0.5, 0.25]);
+    assert_eq!(round_trip(Scalar::f9_9_9_e5, &[0.0, 0.0, 0.0]), &[0.0, 0.0, 0.0]);
+    assert_eq!(round_trip(Scalar::f9_9_9_e5, &[1.0e6, 0.0, 0.0])[0], 65408.0);
+  }
+}